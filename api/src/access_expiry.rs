@@ -0,0 +1,45 @@
+use anyhow::Result;
+use platz_db::schema::{
+    access_request::AccessRequest, deployment_permission::DeploymentPermission,
+    env_user_permission::EnvUserPermission,
+};
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, info};
+
+/// Deletes temporary permissions once they expire, and marks the access
+/// requests that granted them as expired. Expired permissions are already
+/// ignored by permission checks, this makes the revocation visible through
+/// DB events and keeps the permission tables clean.
+async fn revoke_expired() -> Result<()> {
+    for permission in EnvUserPermission::delete_expired().await? {
+        info!(
+            "Revoked expired {} role of user {} in env {}",
+            permission.role, permission.user_id, permission.env_id
+        );
+    }
+    for permission in DeploymentPermission::delete_expired().await? {
+        info!(
+            "Revoked expired {} role of user {} on kind {} in env {}",
+            permission.role, permission.user_id, permission.kind_id, permission.env_id
+        );
+    }
+    for request in AccessRequest::expire_approved().await? {
+        info!(
+            "Access request {} of user {} expired",
+            request.id, request.user_id
+        );
+    }
+    Ok(())
+}
+
+#[tracing::instrument(err, skip_all, name = "access-expiry")]
+pub async fn revoke_expired_task(check_interval: Duration) -> Result<()> {
+    let mut interval = time::interval(check_interval);
+    loop {
+        interval.tick().await;
+        if let Err(err) = revoke_expired().await {
+            error!("Failed revoking expired access: {err:?}");
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use actix_web::{
+    body::{BoxBody, MessageBody, to_bytes},
+    dev::{ServiceRequest, ServiceResponse},
+    error::ErrorInternalServerError,
+    http::{Method, header::USER_AGENT},
+    middleware::Next,
+};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    DbTable, Json,
+    json_diff::json_diff,
+    schema::audit_log::{AuditLogEntry, NewAuditLogEntry},
+};
+use serde_json::Value;
+use tracing::error;
+use uuid::Uuid;
+
+const API_PREFIX: &str = "/api/v2/";
+
+/// The table and row a request is about, as far as can be told from its path
+#[derive(Debug, PartialEq, Eq)]
+struct Target {
+    table_name: String,
+    table: Option<DbTable>,
+    id: Option<Uuid>,
+}
+
+impl Target {
+    /// `/api/v2/deployment-tasks/{id}/...` targets row `id` of the
+    /// `deployment_tasks` table
+    fn from_path(path: &str) -> Option<Self> {
+        let mut segments = path.strip_prefix(API_PREFIX)?.split('/');
+        let table_name = segments
+            .next()
+            .filter(|name| !name.is_empty())?
+            .replace('-', "_");
+        Some(Self {
+            table: serde_json::from_value(Value::String(table_name.clone())).ok(),
+            table_name,
+            id: segments.next().and_then(|id| id.parse().ok()),
+        })
+    }
+
+    async fn snapshot(&self, id: Option<Uuid>) -> Option<Value> {
+        let (Some(table), Some(id)) = (self.table, id) else {
+            return None;
+        };
+        match AuditLogEntry::snapshot(table, id).await {
+            Ok(row) => row,
+            Err(err) => {
+                error!("Failed taking audit snapshot of {table} {id}: {err}");
+                None
+            }
+        }
+    }
+}
+
+/// Records every POST, PUT and DELETE request in the audit log, along with
+/// how it changed the row it targets. Rows created by a request are found by
+/// the `id` in the response.
+pub async fn audit_mutations(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if !matches!(*req.method(), Method::POST | Method::PUT | Method::DELETE) {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    // Unauthenticated requests are audited too, without an identity
+    let identity = req
+        .extract::<ApiIdentity>()
+        .await
+        .ok()
+        .map(ApiIdentity::into_inner);
+    let method = req.method().to_string();
+    let path = req.path().to_owned();
+    let action = format!(
+        "{method} {}",
+        req.match_pattern().unwrap_or_else(|| path.clone())
+    );
+    let source_ip = req
+        .connection_info()
+        .realip_remote_addr()
+        .map(ToOwned::to_owned);
+    let user_agent = req
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned);
+    let target = Target::from_path(&path);
+    let before = match &target {
+        Some(target) => target.snapshot(target.id).await,
+        None => None,
+    };
+
+    let res = next.call(req).await?;
+    let status = res.status();
+
+    let (res, target_id) = match &target {
+        Some(Target {
+            table: Some(_),
+            id: None,
+            ..
+        }) if status.is_success() => {
+            let (req, res) = res.into_parts();
+            let (res, body) = res.into_parts();
+            let body = to_bytes(body).await.map_err(|err| {
+                let err: Box<dyn std::error::Error> = err.into();
+                ErrorInternalServerError(err.to_string())
+            })?;
+            let created_id = serde_json::from_slice::<Value>(&body)
+                .ok()
+                .and_then(|body| body.get("id")?.as_str()?.parse().ok());
+            (
+                ServiceResponse::new(req, res.set_body(BoxBody::new(body))),
+                created_id,
+            )
+        }
+        target => (
+            res.map_into_boxed_body(),
+            target.as_ref().and_then(|t| t.id),
+        ),
+    };
+
+    let diff = if status.is_success() {
+        let after = match &target {
+            Some(target) => target.snapshot(target_id).await,
+            None => None,
+        };
+        let diff = json_diff(
+            before.as_ref().unwrap_or(&Value::Null),
+            after.as_ref().unwrap_or(&Value::Null),
+        );
+        Some(Json(diff))
+    } else {
+        None
+    };
+
+    if let Err(err) = (NewAuditLogEntry {
+        acting_user_id: identity.as_ref().and_then(|identity| identity.user_id()),
+        acting_bot_id: identity.as_ref().and_then(|identity| identity.bot_id()),
+        acting_deployment_id: identity
+            .as_ref()
+            .and_then(|identity| identity.deployment_id()),
+        method,
+        action,
+        path,
+        status_code: status.as_u16().into(),
+        target_table: target.as_ref().map(|target| target.table_name.clone()),
+        target_id,
+        diff,
+        source_ip,
+        user_agent,
+    })
+    .insert()
+    .await
+    {
+        error!("Failed writing audit log entry: {err}");
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_from_path() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            Target::from_path(&format!("/api/v2/deployment-tasks/{id}")),
+            Some(Target {
+                table_name: "deployment_tasks".to_owned(),
+                table: Some(DbTable::DeploymentTasks),
+                id: Some(id),
+            })
+        );
+        assert_eq!(
+            Target::from_path(&format!("/api/v2/access-requests/{id}/approve")),
+            Some(Target {
+                table_name: "access_requests".to_owned(),
+                table: Some(DbTable::AccessRequests),
+                id: Some(id),
+            })
+        );
+        assert_eq!(
+            Target::from_path("/api/v2/auth/google/callback"),
+            Some(Target {
+                table_name: "auth".to_owned(),
+                table: None,
+                id: None,
+            })
+        );
+        assert_eq!(Target::from_path("/status"), None);
+    }
+}
@@ -1,17 +1,26 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use clap::{Parser, Subcommand};
-use platz_db::{NotificationListeningOpts, init_db};
+use platz_db::{
+    NotificationListeningOpts, encryption, init_db,
+    schema::{
+        deployment_resource::DeploymentResource, secret::Secret, secret_version::SecretVersion,
+        webhook::Webhook,
+    },
+};
 use routes::openapi::SchemaFormat;
 use tokio::{
     select,
     signal::unix::{SignalKind, signal},
 };
-use tracing::warn;
+use tracing::{info, warn};
 
+mod access_expiry;
+mod audit;
 mod permissions;
 mod result;
 mod routes;
 mod server;
+mod webhooks;
 
 #[derive(Parser)]
 enum Command {
@@ -19,6 +28,8 @@ enum Command {
     Run(Box<RunCommand>),
     #[command(subcommand)]
     Openapi(OpenapiCommand),
+    #[command(subcommand)]
+    Encryption(EncryptionCommand),
 }
 
 #[derive(clap::Args)]
@@ -27,11 +38,24 @@ struct RunCommand {
     server_config: server::Config,
     #[clap(long, default_value = "5secs")]
     prometheus_update_interval: humantime::Duration,
+    /// How often to revoke expired temporary permissions
+    #[clap(long, default_value = "30secs")]
+    access_expiry_interval: humantime::Duration,
+    /// How often to check for webhook deliveries due for a retry
+    #[clap(long, default_value = "5secs")]
+    webhook_delivery_interval: humantime::Duration,
+    /// How many times to attempt a webhook delivery before keeping it as a
+    /// dead letter
+    #[clap(long, default_value = "10")]
+    webhook_max_attempts: i32,
+    /// How long to keep delivered webhook deliveries and dead letters
+    #[clap(long, default_value = "30days")]
+    webhook_delivery_retention: humantime::Duration,
 }
 
 impl RunCommand {
     async fn run(self) -> Result<()> {
-        platz_otel::init()?;
+        let _otel = platz_otel::init(env!("CARGO_PKG_NAME"))?;
         let db = init_db().await?;
 
         db.run_migrations()
@@ -63,6 +87,23 @@ impl RunCommand {
                 result
             }
 
+            result = access_expiry::revoke_expired_task(
+                self.access_expiry_interval.into(),
+            ) => {
+                warn!("Access expiry task finished: {result:?}");
+                result
+            }
+
+            result = webhooks::deliver_webhooks_task(
+                db.subscribe_to_events(),
+                self.webhook_delivery_interval.into(),
+                self.webhook_max_attempts,
+                self.webhook_delivery_retention.into(),
+            ) => {
+                warn!("Webhooks task finished: {result:?}");
+                result
+            }
+
             result = server::serve(self.server_config) => {
                 warn!("API server finished: {result:?}");
                 result
@@ -90,6 +131,39 @@ impl OpenapiCommand {
     }
 }
 
+#[derive(Subcommand)]
+#[command(name = "encryption")]
+enum EncryptionCommand {
+    /// Re-wrap every encrypted value with the current master key, and encrypt
+    /// values stored before encryption was enabled. Safe to run while the
+    /// other workers are up, as long as they already have the new key.
+    #[command(name = "rotate")]
+    Rotate,
+}
+
+impl EncryptionCommand {
+    async fn run(self) -> Result<()> {
+        let EncryptionCommand::Rotate = self;
+        let _otel = platz_otel::init(env!("CARGO_PKG_NAME"))?;
+        let Some(keys) = encryption::master_keys()? else {
+            bail!(
+                "No master key configured, set PLATZ_ENCRYPTION_KEY or PLATZ_ENCRYPTION_KEY_FILE"
+            );
+        };
+        init_db().await?;
+        info!("Rotating to master key {}", keys.current_key_id());
+        let secrets = Secret::rewrap_all().await?;
+        info!("Updated {secrets} secrets");
+        let versions = SecretVersion::rewrap_all().await?;
+        info!("Updated {versions} secret versions");
+        let resources = DeploymentResource::rewrap_all().await?;
+        info!("Updated {resources} deployment resources");
+        let webhooks = Webhook::rewrap_all().await?;
+        info!("Updated {webhooks} webhooks");
+        Ok(())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     rustls::crypto::aws_lc_rs::default_provider()
@@ -100,6 +174,7 @@ async fn main() -> Result<()> {
     match command {
         Command::Run(command) => command.run().await?,
         Command::Openapi(command) => command.run()?,
+        Command::Encryption(command) => command.run().await?,
     }
     Ok(())
 }
@@ -1,78 +1,176 @@
-use super::verify_env_admin;
+use super::{
+    envs::{env_permissions, env_role_bindings},
+    roles::role_permissions,
+    verify_env_admin,
+};
 use crate::result::ApiError;
 use platz_db::{
-    Identity,
+    AccessScope, Identity,
     schema::{
         bot::Bot,
-        deployment::Deployment,
+        custom_role::RolePermission,
+        deployment_action_restriction::DeploymentActionRestriction,
+        deployment_bot_permission::DeploymentBotPermission,
         deployment_permission::{DeploymentPermission, UserDeploymentRole},
         k8s_cluster::K8sCluster,
     },
 };
 use uuid::Uuid;
 
-pub async fn verify_deployment_owner<I>(
+/// The identity's roles for deployments of a kind in an env, along with the
+/// custom role ID for custom roles. When a deployment is given, roles granted
+/// for that deployment only are included.
+async fn kind_role_bindings<I>(
+    env_id: Uuid,
+    kind_id: Uuid,
+    deployment_id: Option<Uuid>,
+    identity: &I,
+) -> Result<Vec<(UserDeploymentRole, Option<Uuid>)>, ApiError>
+where
+    I: std::borrow::Borrow<Identity>,
+{
+    Ok(match identity.borrow() {
+        Identity::User(user_id) => DeploymentPermission::find_user_permissions(
+            env_id,
+            user_id.to_owned(),
+            kind_id,
+            deployment_id,
+        )
+        .await?
+        .into_iter()
+        .map(|p| (p.role, p.custom_role_id))
+        .collect(),
+        Identity::Bot(bot_id) => {
+            let bot = Bot::find(bot_id.to_owned())
+                .await?
+                .ok_or(ApiError::NoPermission)?;
+            if bot.unrestricted {
+                vec![(UserDeploymentRole::Maintainer, None)]
+            } else {
+                DeploymentBotPermission::find_bot_permissions(
+                    env_id,
+                    bot.id,
+                    kind_id,
+                    deployment_id,
+                )
+                .await?
+                .into_iter()
+                .map(|p| (p.role, p.custom_role_id))
+                .collect()
+            }
+        }
+        // Deployments may maintain deployments in their own env, or in envs
+        // explicitly granted to their kind
+        Identity::Deployment(_) => {
+            match AccessScope::for_identity(identity.borrow())
+                .await?
+                .allows_env(Some(env_id))
+            {
+                true => vec![(UserDeploymentRole::Maintainer, None)],
+                false => Vec::new(),
+            }
+        }
+    })
+}
+
+async fn cluster_env_id(cluster_id: Uuid) -> Result<Uuid, ApiError> {
+    K8sCluster::find(cluster_id)
+        .await?
+        .env_id
+        .ok_or(ApiError::NoPermission)
+}
+
+/// Verifies the identity has the given permission on deployments of a kind
+/// in a cluster, either through its env role or through its role for the kind.
+/// Pass the deployment when checking an existing deployment, so roles granted
+/// for that deployment alone are considered too.
+pub async fn verify_deployment_permission<I>(
     cluster_id: Uuid,
     kind_id: Uuid,
+    deployment_id: Option<Uuid>,
+    permission: RolePermission,
     identity: &I,
 ) -> Result<(), ApiError>
 where
     I: std::borrow::Borrow<Identity>,
 {
-    let env_id = K8sCluster::find(cluster_id)
+    let env_id = cluster_env_id(cluster_id).await?;
+    if env_permissions(env_id, identity)
         .await?
-        .env_id
-        .ok_or(ApiError::NoPermission)?;
-    match verify_env_admin(env_id, identity).await {
-        Ok(()) => Ok(()),
-        Err(ApiError::NoPermission) => match identity.borrow().user_id() {
-            None => Err(ApiError::NoPermission),
-            Some(user_id) => {
-                match DeploymentPermission::find_user_role(env_id, user_id, kind_id).await? {
-                    Some(UserDeploymentRole::Owner) => Ok(()),
-                    _ => Err(ApiError::NoPermission),
-                }
-            }
-        },
-        Err(err) => Err(err),
+        .contains(&permission)
+    {
+        return Ok(());
     }
+    for (role, custom_role_id) in
+        kind_role_bindings(env_id, kind_id, deployment_id, identity).await?
+    {
+        if role_permissions(role.built_in_permissions(), custom_role_id)
+            .await?
+            .contains(&permission)
+        {
+            return Ok(());
+        }
+    }
+    Err(ApiError::NoPermission)
 }
 
-pub async fn verify_deployment_maintainer<I>(
+/// Verifies the identity may invoke an action on deployments of a kind in a
+/// cluster: it must have the invoke-action permission and, if the action is
+/// restricted in the env, be allowed by the restriction.
+pub async fn verify_action_allowed<I>(
     cluster_id: Uuid,
     kind_id: Uuid,
+    deployment_id: Option<Uuid>,
+    action_id: &str,
     identity: &I,
 ) -> Result<(), ApiError>
 where
     I: std::borrow::Borrow<Identity>,
 {
-    let env_id = K8sCluster::find(cluster_id)
+    verify_deployment_permission(
+        cluster_id,
+        kind_id,
+        deployment_id,
+        RolePermission::InvokeAction,
+        identity,
+    )
+    .await?;
+    let env_id = cluster_env_id(cluster_id).await?;
+    let Some(restriction) =
+        DeploymentActionRestriction::find_for_action(env_id, kind_id, action_id).await?
+    else {
+        return Ok(());
+    };
+    if verify_env_admin(env_id, identity).await.is_ok() {
+        return Ok(());
+    }
+    let listed = match identity.borrow() {
+        Identity::User(user_id) => restriction.allowed_user_ids.contains(user_id),
+        Identity::Bot(bot_id) => restriction.allowed_bot_ids.contains(bot_id),
+        Identity::Deployment(_) => false,
+    };
+    if listed {
+        return Ok(());
+    }
+    if env_role_bindings(env_id, identity)
         .await?
-        .env_id
-        .ok_or(ApiError::NoPermission)?;
-    match verify_env_admin(env_id, identity).await {
-        Ok(()) => Ok(()),
-        Err(ApiError::NoPermission) => match identity.borrow() {
-            Identity::User(user_id) => {
-                DeploymentPermission::find_user_role(env_id, user_id.to_owned(), kind_id)
-                    .await?
-                    .map(|_| ())
-                    .ok_or(ApiError::NoPermission)
-            }
-            Identity::Bot(bot_id) => {
-                let _bot = Bot::find(bot_id.to_owned()).await?;
-                // TODO: Add bot permissions
-                Ok(())
-            }
-            Identity::Deployment(deployment_id) => {
-                let identity_deployment = Deployment::find(deployment_id.to_owned()).await?;
-                K8sCluster::find(identity_deployment.cluster_id)
-                    .await?
-                    .env_id
-                    .map(|_| ())
-                    .ok_or(ApiError::NoPermission)
-            }
-        },
-        Err(err) => Err(err),
+        .into_iter()
+        .any(|(_, custom_role_id)| {
+            custom_role_id.is_some_and(|custom_role_id| {
+                restriction
+                    .allowed_custom_role_ids
+                    .contains(&custom_role_id)
+            })
+        })
+    {
+        return Ok(());
+    }
+    match kind_role_bindings(env_id, kind_id, deployment_id, identity)
+        .await?
+        .into_iter()
+        .any(|(role, custom_role_id)| restriction.allows_role(role, custom_role_id))
+    {
+        true => Ok(()),
+        false => Err(ApiError::NoPermission),
     }
 }
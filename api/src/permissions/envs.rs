@@ -1,8 +1,12 @@
-use super::verify_site_admin;
+use super::{roles::role_permissions, verify_site_admin};
 use crate::result::ApiError;
 use platz_db::{
     Identity,
-    schema::env_user_permission::{EnvUserPermission, EnvUserRole},
+    schema::{
+        custom_role::RolePermission,
+        env_bot_permission::EnvBotPermission,
+        env_user_permission::{EnvUserPermission, EnvUserRole},
+    },
 };
 use uuid::Uuid;
 
@@ -10,11 +14,75 @@ pub async fn verify_env_admin<I>(env_id: Uuid, identity: &I) -> Result<(), ApiEr
 where
     I: std::borrow::Borrow<Identity>,
 {
-    match identity.borrow().user_id() {
-        None => Err(ApiError::NoPermission),
-        Some(user_id) => match EnvUserPermission::find_user_role_in_env(env_id, user_id).await? {
-            Some(EnvUserRole::Admin) => Ok(()),
-            _ => verify_site_admin(identity).await,
-        },
+    match env_role_bindings(env_id, identity)
+        .await?
+        .iter()
+        .any(|(role, _)| *role == EnvUserRole::Admin)
+    {
+        true => Ok(()),
+        false => verify_site_admin(identity).await,
+    }
+}
+
+/// The identity's env roles, along with the custom role ID for custom roles.
+/// Users may hold temporary roles in addition to their permanent one.
+pub(super) async fn env_role_bindings<I>(
+    env_id: Uuid,
+    identity: &I,
+) -> Result<Vec<(EnvUserRole, Option<Uuid>)>, ApiError>
+where
+    I: std::borrow::Borrow<Identity>,
+{
+    Ok(match identity.borrow() {
+        Identity::User(user_id) => {
+            EnvUserPermission::find_user_permissions_in_env(env_id, user_id.to_owned())
+                .await?
+                .into_iter()
+                .map(|p| (p.role, p.custom_role_id))
+                .collect()
+        }
+        Identity::Bot(bot_id) => {
+            EnvBotPermission::find_bot_permission_in_env(env_id, bot_id.to_owned())
+                .await?
+                .map(|p| (p.role, p.custom_role_id))
+                .into_iter()
+                .collect()
+        }
+        Identity::Deployment(_) => Vec::new(),
+    })
+}
+
+/// Permissions the identity has on the whole env, from its env roles
+pub(super) async fn env_permissions<I>(
+    env_id: Uuid,
+    identity: &I,
+) -> Result<Vec<RolePermission>, ApiError>
+where
+    I: std::borrow::Borrow<Identity>,
+{
+    if verify_site_admin(identity).await.is_ok() {
+        return Ok(RolePermission::all());
+    }
+    let mut permissions = Vec::new();
+    for (role, custom_role_id) in env_role_bindings(env_id, identity).await? {
+        permissions.extend(role_permissions(role.built_in_permissions(), custom_role_id).await?);
+    }
+    Ok(permissions)
+}
+
+pub async fn verify_env_permission<I>(
+    env_id: Uuid,
+    permission: RolePermission,
+    identity: &I,
+) -> Result<(), ApiError>
+where
+    I: std::borrow::Borrow<Identity>,
+{
+    match env_permissions(env_id, identity)
+        .await?
+        .contains(&permission)
+    {
+        true => Ok(()),
+        false => Err(ApiError::NoPermission),
     }
 }
@@ -1,5 +1,6 @@
 mod deployments;
 mod envs;
+mod roles;
 mod site;
 
 pub use deployments::*;
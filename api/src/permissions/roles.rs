@@ -0,0 +1,16 @@
+use crate::result::ApiError;
+use platz_db::schema::custom_role::{CustomRole, RolePermission};
+use uuid::Uuid;
+
+/// Permissions of a role binding: the permissions of a built-in role, or the
+/// ones of the custom role the binding points to.
+pub(super) async fn role_permissions(
+    built_in: Option<Vec<RolePermission>>,
+    custom_role_id: Option<Uuid>,
+) -> Result<Vec<RolePermission>, ApiError> {
+    match (built_in, custom_role_id) {
+        (Some(permissions), _) => Ok(permissions),
+        (None, Some(custom_role_id)) => Ok(CustomRole::find(custom_role_id).await?.permissions.0),
+        (None, None) => Ok(Vec::new()),
+    }
+}
@@ -0,0 +1,316 @@
+use super::utils::ensure_user;
+use crate::{permissions::verify_env_admin, result::ApiResult};
+use actix_web::{HttpResponse, get, post, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    AccessScope,
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::{
+        access_request::{AccessRequest, AccessRequestFilters, NewAccessRequest},
+        deployment::Deployment,
+        deployment_permission::UserDeploymentRole,
+        env_user_permission::EnvUserRole,
+        k8s_cluster::K8sCluster,
+    },
+};
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Longest duration access can be requested for, one week
+const MAX_ACCESS_DURATION_SECS: i32 = 7 * 24 * 60 * 60;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Access Requests",
+    operation_id = "allAccessRequests",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(AccessRequestFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<AccessRequest>,
+        ),
+    ),
+)]
+#[get("/access-requests")]
+async fn get_all(
+    identity: ApiIdentity,
+    filters: web::Query<AccessRequestFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        AccessRequest::all_filtered(
+            filters.into_inner(),
+            pagination.into_inner(),
+            &scope,
+            identity.inner().user_id(),
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Access Requests",
+    operation_id = "getAccessRequest",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = AccessRequest,
+        ),
+    ),
+)]
+#[get("/access-requests/{id}")]
+async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        AccessRequest::find_scoped(id.into_inner(), &scope, identity.inner().user_id()).await?,
+    ))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateAccessRequest {
+    pub env_id: Uuid,
+    /// Request a deployment role on deployments of this kind. Leave empty to
+    /// request an env role.
+    #[serde(default)]
+    #[schema(required)]
+    pub kind_id: Option<Uuid>,
+    /// Limit a deployment role to a single deployment of the kind
+    #[serde(default)]
+    #[schema(required)]
+    pub deployment_id: Option<Uuid>,
+    /// The requested env role, required when `kind_id` is empty
+    #[serde(default)]
+    #[schema(required)]
+    pub env_role: Option<EnvUserRole>,
+    /// The requested deployment role, required when `kind_id` is set
+    #[serde(default)]
+    #[schema(required)]
+    pub deployment_role: Option<UserDeploymentRole>,
+    #[serde(default)]
+    #[schema(required)]
+    pub custom_role_id: Option<Uuid>,
+    /// How long the access is needed for, up to a week
+    pub duration_secs: i32,
+    pub justification: String,
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Access Requests",
+    operation_id = "createAccessRequest",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = CreateAccessRequest,
+    responses(
+        (
+            status = CREATED,
+            body = AccessRequest,
+        ),
+    ),
+)]
+#[post("/access-requests")]
+async fn create(identity: ApiIdentity, body: web::Json<CreateAccessRequest>) -> ApiResult {
+    let user = ensure_user(&identity).await?;
+    let body = body.into_inner();
+
+    let role_is_custom = match (body.kind_id, body.env_role, body.deployment_role) {
+        (None, Some(role), None) => role == EnvUserRole::Custom,
+        (Some(_), None, Some(role)) => role == UserDeploymentRole::Custom,
+        _ => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Set env_role to request an env role, or kind_id and deployment_role to request a deployment role",
+            })));
+        }
+    };
+    if role_is_custom != body.custom_role_id.is_some() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "custom_role_id must be set for the Custom role, and only for it",
+        })));
+    }
+    if !(1..=MAX_ACCESS_DURATION_SECS).contains(&body.duration_secs) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": format!("Duration must be between 1 and {MAX_ACCESS_DURATION_SECS} seconds"),
+        })));
+    }
+    if body.justification.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "A justification is required",
+        })));
+    }
+    if let Some(deployment_id) = body.deployment_id {
+        let deployment = Deployment::find(deployment_id).await?;
+        let cluster = K8sCluster::find(deployment.cluster_id).await?;
+        if Some(deployment.kind_id) != body.kind_id || cluster.env_id != Some(body.env_id) {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "The deployment must be of the requested kind and in the requested env",
+            })));
+        }
+    }
+
+    let request = NewAccessRequest {
+        user_id: user.id,
+        env_id: body.env_id,
+        kind_id: body.kind_id,
+        deployment_id: body.deployment_id,
+        env_role: body.env_role,
+        deployment_role: body.deployment_role,
+        custom_role_id: body.custom_role_id,
+        duration_secs: body.duration_secs,
+        justification: body.justification,
+    }
+    .insert()
+    .await?;
+    Ok(HttpResponse::Created().json(request))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReviewAccessRequest {
+    #[serde(default)]
+    #[schema(required)]
+    pub comment: Option<String>,
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Access Requests",
+    operation_id = "approveAccessRequest",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = ReviewAccessRequest,
+    responses(
+        (
+            status = OK,
+            body = AccessRequest,
+        ),
+    ),
+)]
+#[post("/access-requests/{id}/approve")]
+async fn approve(
+    identity: ApiIdentity,
+    id: web::Path<Uuid>,
+    body: web::Json<ReviewAccessRequest>,
+) -> ApiResult {
+    let request = AccessRequest::find(id.into_inner()).await?;
+    verify_env_admin(request.env_id, &identity).await?;
+    let reviewer = ensure_user(&identity).await?;
+    if reviewer.id == request.user_id {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Access requests can't be approved by the requesting user",
+        })));
+    }
+    match request
+        .approve(reviewer.id, body.into_inner().comment)
+        .await?
+    {
+        Some(request) => Ok(HttpResponse::Ok().json(request)),
+        None => Ok(HttpResponse::Conflict().json(json!({
+            "error": "This request has already been reviewed",
+        }))),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Access Requests",
+    operation_id = "rejectAccessRequest",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = ReviewAccessRequest,
+    responses(
+        (
+            status = OK,
+            body = AccessRequest,
+        ),
+    ),
+)]
+#[post("/access-requests/{id}/reject")]
+async fn reject(
+    identity: ApiIdentity,
+    id: web::Path<Uuid>,
+    body: web::Json<ReviewAccessRequest>,
+) -> ApiResult {
+    let request = AccessRequest::find(id.into_inner()).await?;
+    verify_env_admin(request.env_id, &identity).await?;
+    let reviewer = ensure_user(&identity).await?;
+    match request
+        .reject(reviewer.id, body.into_inner().comment)
+        .await?
+    {
+        Some(request) => Ok(HttpResponse::Ok().json(request)),
+        None => Ok(HttpResponse::Conflict().json(json!({
+            "error": "This request has already been reviewed",
+        }))),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Access Requests",
+    operation_id = "cancelAccessRequest",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = AccessRequest,
+        ),
+    ),
+)]
+#[post("/access-requests/{id}/cancel")]
+async fn cancel(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let user = ensure_user(&identity).await?;
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    let request = AccessRequest::find_scoped(id.into_inner(), &scope, Some(user.id)).await?;
+    if request.user_id != user.id {
+        return Ok(HttpResponse::Forbidden().json(json!({
+            "error": "Only the requesting user can cancel a request",
+        })));
+    }
+    match request.cancel().await? {
+        Some(request) => Ok(HttpResponse::Ok().json(request)),
+        None => Ok(HttpResponse::Conflict().json(json!({
+            "error": "This request has already been reviewed",
+        }))),
+    }
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Access Requests",
+        description = "\
+This collection contains requests for temporary elevated access.
+
+Users request an env role, or a deployment role on a kind or a single
+deployment, for a limited duration and with a justification. An env admin
+other than the requesting user approves or rejects the request. Approving
+creates the permission with an expiry time, after which it's ignored and
+revoked, and the request is marked as expired.
+
+Requests are never deleted, keeping a record of who was granted what access,
+by whom, and until when.
+",
+    )),
+    paths(get_all, get_one, create, approve, reject, cancel),
+)]
+pub(super) struct OpenApi;
@@ -0,0 +1,73 @@
+use crate::{permissions::verify_site_admin, result::ApiResult};
+use actix_web::{HttpResponse, get, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::audit_log::{AuditLogEntry, AuditLogFilters},
+};
+use uuid::Uuid;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Audit Log",
+    operation_id = "allAuditLogEntries",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(AuditLogFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<AuditLogEntry>,
+        ),
+    ),
+)]
+#[get("/audit-log")]
+async fn get_all(
+    identity: ApiIdentity,
+    filters: web::Query<AuditLogFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    Ok(HttpResponse::Ok()
+        .json(AuditLogEntry::all_filtered(filters.into_inner(), pagination.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Audit Log",
+    operation_id = "getAuditLogEntry",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = AuditLogEntry,
+        ),
+    ),
+)]
+#[get("/audit-log/{id}")]
+async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    Ok(HttpResponse::Ok().json(AuditLogEntry::find(id.into_inner()).await?))
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Audit Log",
+        description = "\
+This collection contains an entry for every POST, PUT and DELETE request
+made to the API, including failed ones. Entries record who made the
+request, from where, the row it targeted and how that row changed.
+
+Sensitive values such as secret contents are redacted from diffs. Entries
+can't be modified or deleted, and are only visible to site admins.
+",
+    )),
+    paths(get_all, get_one),
+)]
+pub(super) struct OpenApi;
@@ -0,0 +1,148 @@
+use crate::{permissions::verify_site_admin, result::ApiResult};
+use actix_web::{HttpResponse, delete, get, post, put, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::custom_role::{CustomRole, CustomRoleFilters, NewCustomRole, UpdateCustomRole},
+};
+use uuid::Uuid;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Custom Roles",
+    operation_id = "allCustomRoles",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(CustomRoleFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<CustomRole>,
+        ),
+    ),
+)]
+#[get("/custom-roles")]
+async fn get_all(
+    _identity: ApiIdentity,
+    filters: web::Query<CustomRoleFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    Ok(HttpResponse::Ok()
+        .json(CustomRole::all_filtered(filters.into_inner(), pagination.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Custom Roles",
+    operation_id = "getCustomRole",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = CustomRole,
+        ),
+    ),
+)]
+#[get("/custom-roles/{id}")]
+async fn get_one(_identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(CustomRole::find(id.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Custom Roles",
+    operation_id = "createCustomRole",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = NewCustomRole,
+    responses(
+        (
+            status = CREATED,
+            body = CustomRole,
+        ),
+    ),
+)]
+#[post("/custom-roles")]
+async fn create(identity: ApiIdentity, new_role: web::Json<NewCustomRole>) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    Ok(HttpResponse::Created().json(new_role.into_inner().insert().await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Custom Roles",
+    operation_id = "updateCustomRole",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = UpdateCustomRole,
+    responses(
+        (
+            status = OK,
+            body = CustomRole,
+        ),
+    ),
+)]
+#[put("/custom-roles/{id}")]
+async fn update(
+    identity: ApiIdentity,
+    id: web::Path<Uuid>,
+    data: web::Json<UpdateCustomRole>,
+) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    Ok(HttpResponse::Ok().json(data.into_inner().save(id.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Custom Roles",
+    operation_id = "deleteCustomRole",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = NO_CONTENT,
+        ),
+    ),
+)]
+#[delete("/custom-roles/{id}")]
+async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    let role = CustomRole::find(id.into_inner()).await?;
+    role.delete().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Custom Roles",
+        description = "\
+This collection contains custom roles, which are named sets of permissions.
+Custom roles are bound to users and bots in env and deployment permissions by
+setting the role to Custom and custom_role_id to the custom role's ID.
+
+The built-in roles are:
+
+* Env Admin: all permissions, including managing the env's permissions
+* Env User: read
+* Deployment Owner: read, create, update-config, upgrade-chart,
+  invoke-action, restart and delete
+* Deployment Maintainer: read, update-config, upgrade-chart, invoke-action
+  and restart
+* Deployment Viewer: read
+        ",
+    )),
+    paths(get_all, get_one, create, update, delete),
+)]
+pub(super) struct OpenApi;
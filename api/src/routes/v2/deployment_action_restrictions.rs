@@ -0,0 +1,158 @@
+use crate::{permissions::verify_env_admin, result::ApiResult};
+use actix_web::{HttpResponse, delete, get, post, put, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    AccessScope,
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::deployment_action_restriction::{
+        DeploymentActionRestriction, DeploymentActionRestrictionFilters,
+        NewDeploymentActionRestriction, UpdateDeploymentActionRestriction,
+    },
+};
+use uuid::Uuid;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Action Restrictions",
+    operation_id = "allDeploymentActionRestrictions",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(DeploymentActionRestrictionFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<DeploymentActionRestriction>,
+        ),
+    ),
+)]
+#[get("/deployment-action-restrictions")]
+async fn get_all(
+    identity: ApiIdentity,
+    filters: web::Query<DeploymentActionRestrictionFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        DeploymentActionRestriction::all_filtered(
+            filters.into_inner(),
+            pagination.into_inner(),
+            &scope,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Action Restrictions",
+    operation_id = "getDeploymentActionRestriction",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = DeploymentActionRestriction,
+        ),
+    ),
+)]
+#[get("/deployment-action-restrictions/{id}")]
+async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok()
+        .json(DeploymentActionRestriction::find_scoped(id.into_inner(), &scope).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Action Restrictions",
+    operation_id = "createDeploymentActionRestriction",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = NewDeploymentActionRestriction,
+    responses(
+        (
+            status = CREATED,
+            body = DeploymentActionRestriction,
+        ),
+    ),
+)]
+#[post("/deployment-action-restrictions")]
+async fn create(
+    identity: ApiIdentity,
+    new_restriction: web::Json<NewDeploymentActionRestriction>,
+) -> ApiResult {
+    let new_restriction = new_restriction.into_inner();
+    verify_env_admin(new_restriction.env_id, &identity).await?;
+    Ok(HttpResponse::Created().json(new_restriction.insert().await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Action Restrictions",
+    operation_id = "updateDeploymentActionRestriction",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = UpdateDeploymentActionRestriction,
+    responses(
+        (
+            status = OK,
+            body = DeploymentActionRestriction,
+        ),
+    ),
+)]
+#[put("/deployment-action-restrictions/{id}")]
+async fn update(
+    identity: ApiIdentity,
+    id: web::Path<Uuid>,
+    data: web::Json<UpdateDeploymentActionRestriction>,
+) -> ApiResult {
+    let restriction = DeploymentActionRestriction::find(id.into_inner()).await?;
+    verify_env_admin(restriction.env_id, &identity).await?;
+    Ok(HttpResponse::Ok().json(data.into_inner().save(restriction.id).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Action Restrictions",
+    operation_id = "deleteDeploymentActionRestriction",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = NO_CONTENT,
+        ),
+    ),
+)]
+#[delete("/deployment-action-restrictions/{id}")]
+async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let restriction = DeploymentActionRestriction::find(id.into_inner()).await?;
+    verify_env_admin(restriction.env_id, &identity).await?;
+    restriction.delete().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Deployment Action Restrictions",
+        description = "\
+Action restrictions limit who may invoke a chart action on deployments of a
+kind in an env. Invoking any action requires the invoke-action permission.
+When an action is restricted, the invoking user or bot must also be listed
+in the restriction, or have one of its roles. Env admins may always invoke
+actions.
+        ",
+    )),
+    paths(get_all, get_one, create, update, delete),
+)]
+pub(super) struct OpenApi;
@@ -0,0 +1,154 @@
+use crate::permissions::verify_env_admin;
+use crate::result::ApiResult;
+use actix_web::{HttpResponse, delete, get, post, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    AccessScope,
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::{
+        deployment::Deployment,
+        deployment_bot_permission::{
+            DeploymentBotPermission, DeploymentBotPermissionFilters, NewDeploymentBotPermission,
+        },
+        deployment_permission::UserDeploymentRole,
+        k8s_cluster::K8sCluster,
+    },
+};
+use serde_json::json;
+use uuid::Uuid;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Bot Permissions",
+    operation_id = "allDeploymentBotPermissions",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(DeploymentBotPermissionFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<DeploymentBotPermission>,
+        ),
+    ),
+)]
+#[get("/deployment-bot-permissions")]
+async fn get_all(
+    identity: ApiIdentity,
+    filters: web::Query<DeploymentBotPermissionFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        DeploymentBotPermission::all_filtered(
+            filters.into_inner(),
+            pagination.into_inner(),
+            &scope,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Bot Permissions",
+    operation_id = "getDeploymentBotPermission",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = DeploymentBotPermission,
+        ),
+    ),
+)]
+#[get("/deployment-bot-permissions/{id}")]
+async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok()
+        .json(DeploymentBotPermission::find_scoped(id.into_inner(), &scope).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Bot Permissions",
+    operation_id = "createDeploymentBotPermission",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = NewDeploymentBotPermission,
+    responses(
+        (
+            status = CREATED,
+            body = DeploymentBotPermission,
+        ),
+    ),
+)]
+#[post("/deployment-bot-permissions")]
+async fn create(
+    identity: ApiIdentity,
+    new_permission: web::Json<NewDeploymentBotPermission>,
+) -> ApiResult {
+    let new_permission = new_permission.into_inner();
+    verify_env_admin(new_permission.env_id, &identity).await?;
+    if (new_permission.role == UserDeploymentRole::Custom)
+        != new_permission.custom_role_id.is_some()
+    {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "custom_role_id must be set for the Custom role, and only for it",
+        })));
+    }
+    if let Some(deployment_id) = new_permission.deployment_id {
+        let deployment = Deployment::find(deployment_id).await?;
+        let cluster = K8sCluster::find(deployment.cluster_id).await?;
+        if deployment.kind_id != new_permission.kind_id
+            || cluster.env_id != Some(new_permission.env_id)
+        {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "The deployment must be of the permission's kind and in its env",
+            })));
+        }
+    }
+    Ok(HttpResponse::Created().json(new_permission.insert().await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Bot Permissions",
+    operation_id = "deleteDeploymentBotPermission",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = NO_CONTENT,
+        ),
+    ),
+)]
+#[delete("/deployment-bot-permissions/{id}")]
+async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let permission = DeploymentBotPermission::find(id.into_inner()).await?;
+    verify_env_admin(permission.env_id, &identity).await?;
+    permission.delete().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Deployment Bot Permissions",
+        description = "\
+APIs for setting deployment permissions per bot, for example limiting a CI
+bot to maintaining a single kind in a staging env.
+
+See UserDeploymentRole for more information.
+        ",
+    )),
+    paths(get_all, get_one, create, delete),
+)]
+pub(super) struct OpenApi;
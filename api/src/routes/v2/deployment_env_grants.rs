@@ -0,0 +1,129 @@
+use crate::permissions::verify_env_admin;
+use crate::result::ApiResult;
+use actix_web::{HttpResponse, delete, get, post, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    AccessScope,
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::deployment_env_grant::{
+        DeploymentEnvGrant, DeploymentEnvGrantFilters, NewDeploymentEnvGrant,
+    },
+};
+use serde_json::json;
+use uuid::Uuid;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Env Grants",
+    operation_id = "allDeploymentEnvGrants",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(DeploymentEnvGrantFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<DeploymentEnvGrant>,
+        ),
+    ),
+)]
+#[get("/deployment-env-grants")]
+async fn get_all(
+    identity: ApiIdentity,
+    filters: web::Query<DeploymentEnvGrantFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        DeploymentEnvGrant::all_filtered(filters.into_inner(), pagination.into_inner(), &scope)
+            .await?,
+    ))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Env Grants",
+    operation_id = "getDeploymentEnvGrant",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = DeploymentEnvGrant,
+        ),
+    ),
+)]
+#[get("/deployment-env-grants/{id}")]
+async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(DeploymentEnvGrant::find_scoped(id.into_inner(), &scope).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Env Grants",
+    operation_id = "createDeploymentEnvGrant",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = NewDeploymentEnvGrant,
+    responses(
+        (
+            status = CREATED,
+            body = DeploymentEnvGrant,
+        ),
+    ),
+)]
+#[post("/deployment-env-grants")]
+async fn create(identity: ApiIdentity, new_grant: web::Json<NewDeploymentEnvGrant>) -> ApiResult {
+    let new_grant = new_grant.into_inner();
+    verify_env_admin(new_grant.target_env_id, &identity).await?;
+    if new_grant.source_env_id == new_grant.target_env_id {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Deployments always have access to their own env",
+        })));
+    }
+    Ok(HttpResponse::Created().json(new_grant.insert().await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Deployment Env Grants",
+    operation_id = "deleteDeploymentEnvGrant",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = NO_CONTENT,
+        ),
+    ),
+)]
+#[delete("/deployment-env-grants/{id}")]
+async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let grant = DeploymentEnvGrant::find(id.into_inner()).await?;
+    verify_env_admin(grant.target_env_id, &identity).await?;
+    grant.delete().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Deployment Env Grants",
+        description = "\
+Deployments authenticating with their own credentials can only access
+their own env. Env grants allow deployments of a kind in one env to also
+access another env, for example a shared service reaching a staging env.
+
+Grants are managed by admins of the target env.
+        ",
+    )),
+    paths(get_all, get_one, create, delete),
+)]
+pub(super) struct OpenApi;
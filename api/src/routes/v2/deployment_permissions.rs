@@ -5,10 +5,16 @@ use platz_auth::ApiIdentity;
 use platz_db::{
     AccessScope,
     diesel_pagination::{Paginated, PaginationParams},
-    schema::deployment_permission::{
-        DeploymentPermission, DeploymentPermissionFilters, NewDeploymentPermission,
+    schema::{
+        deployment::Deployment,
+        deployment_permission::{
+            DeploymentPermission, DeploymentPermissionFilters, NewDeploymentPermission,
+            UserDeploymentRole,
+        },
+        k8s_cluster::K8sCluster,
     },
 };
+use serde_json::json;
 use uuid::Uuid;
 
 #[utoipa::path(
@@ -84,6 +90,24 @@ async fn create(
 ) -> ApiResult {
     let new_permission = new_permission.into_inner();
     verify_env_admin(new_permission.env_id, &identity).await?;
+    if (new_permission.role == UserDeploymentRole::Custom)
+        != new_permission.custom_role_id.is_some()
+    {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "custom_role_id must be set for the Custom role, and only for it",
+        })));
+    }
+    if let Some(deployment_id) = new_permission.deployment_id {
+        let deployment = Deployment::find(deployment_id).await?;
+        let cluster = K8sCluster::find(deployment.cluster_id).await?;
+        if deployment.kind_id != new_permission.kind_id
+            || cluster.env_id != Some(new_permission.env_id)
+        {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "The deployment must be of the permission's kind and in its env",
+            })));
+        }
+    }
     Ok(HttpResponse::Created().json(new_permission.insert().await?))
 }
 
@@ -1,5 +1,5 @@
 use super::utils::ensure_user;
-use crate::permissions::verify_deployment_maintainer;
+use crate::permissions::{verify_action_allowed, verify_deployment_permission};
 use crate::result::ApiResult;
 use actix_web::{HttpResponse, delete, get, post, web};
 use chrono::prelude::*;
@@ -8,6 +8,7 @@ use platz_db::{
     AccessScope, DbError, DbTableOrDeploymentResource, Json,
     diesel_pagination::{Paginated, PaginationParams},
     schema::{
+        custom_role::RolePermission,
         deployment::Deployment,
         deployment_task::{
             DeploymentTask, DeploymentTaskExtraFilters, DeploymentTaskFilters,
@@ -166,9 +167,17 @@ pub struct CreateDeploymentTask {
 async fn create(identity: ApiIdentity, task: web::Json<CreateDeploymentTask>) -> ApiResult {
     let task = task.into_inner();
 
-    let deployment = Deployment::find(task.deployment_id).await?;
+    // Tasks can only be created for deployments the identity can see, which
+    // keeps bots and deployments within the envs they were given access to
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    let deployment = Deployment::find_scoped(task.deployment_id, &scope).await?;
 
     let cluster = K8sCluster::find(deployment.cluster_id).await?;
+    if cluster.missing_since.is_some() {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "Can't create a deployment task for a deployment in a cluster missing from cluster discovery",
+        })));
+    }
     let env_id = match cluster.env_id {
         Some(env_id) => env_id,
         None => return Ok(HttpResponse::InternalServerError().json(json!({
@@ -188,6 +197,14 @@ async fn create(identity: ApiIdentity, task: web::Json<CreateDeploymentTask>) ->
 
     Ok(match &task.operation {
         Json(DeploymentTaskOperation::InvokeAction(params)) => {
+            verify_action_allowed(
+                deployment.cluster_id,
+                deployment.kind_id,
+                Some(deployment.id),
+                &params.action_id,
+                &identity,
+            )
+            .await?;
             if params.helm_chart_id == deployment.helm_chart_id {
                 let chart = HelmChart::find(params.helm_chart_id).await?;
                 let actions_schema = chart.actions_schema()?;
@@ -210,8 +227,14 @@ async fn create(identity: ApiIdentity, task: web::Json<CreateDeploymentTask>) ->
             }
         }
         Json(DeploymentTaskOperation::RestartK8sResource(params)) => {
-            verify_deployment_maintainer(deployment.cluster_id, deployment.kind_id, &identity)
-                .await?;
+            verify_deployment_permission(
+                deployment.cluster_id,
+                deployment.kind_id,
+                Some(deployment.id),
+                RolePermission::Restart,
+                &identity,
+            )
+            .await?;
             match K8sResource::find(params.resource_id).await? {
                 None => HttpResponse::NotFound().json(json!({
                     "message": format!("Unknown resource with id={}", params.resource_id)
@@ -1,7 +1,4 @@
-use crate::{
-    permissions::{verify_deployment_maintainer, verify_deployment_owner},
-    result::ApiResult,
-};
+use crate::{permissions::verify_deployment_permission, result::ApiResult};
 use actix_web::{HttpResponse, delete, get, post, put, web};
 use platz_auth::ApiIdentity;
 use platz_chart_ext::ChartExtCardinality;
@@ -9,12 +6,14 @@ use platz_db::{
     AccessScope, DbTable, DbTableOrDeploymentResource,
     diesel_pagination::{Paginated, PaginationParams},
     schema::{
+        custom_role::RolePermission,
         deployment::{
             Deployment, DeploymentExtraFilters, DeploymentFilters, DeploymentStatus, NewDeployment,
             UpdateDeployment,
         },
         deployment_task::DeploymentTask,
         helm_chart::HelmChart,
+        k8s_cluster::K8sCluster,
     },
 };
 use serde_json::json;
@@ -95,7 +94,26 @@ async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
 #[post("/deployments")]
 async fn create(identity: ApiIdentity, new_deployment: web::Json<NewDeployment>) -> ApiResult {
     let new_deployment = new_deployment.into_inner();
-    verify_deployment_owner(new_deployment.cluster_id, new_deployment.kind_id, &identity).await?;
+    verify_deployment_permission(
+        new_deployment.cluster_id,
+        new_deployment.kind_id,
+        None,
+        RolePermission::Create,
+        &identity,
+    )
+    .await?;
+
+    let cluster = K8sCluster::find(new_deployment.cluster_id).await?;
+    if cluster.cordoned {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "This cluster is cordoned and doesn't accept new deployments",
+        })));
+    }
+    if cluster.missing_since.is_some() {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "This cluster is missing from cluster discovery",
+        })));
+    }
 
     let chart = HelmChart::find(new_deployment.helm_chart_id).await?;
     match chart.features()?.cardinality() {
@@ -157,13 +175,73 @@ async fn update(
     let updates = data.into_inner();
     let old_deployment = Deployment::find(id.into_inner()).await?;
 
-    verify_deployment_maintainer(old_deployment.cluster_id, old_deployment.kind_id, &identity)
+    // Changing the chart requires the upgrade-chart permission, any other
+    // change (or no change at all) requires the update-config permission
+    let upgrades_chart = updates
+        .helm_chart_id
+        .is_some_and(|helm_chart_id| helm_chart_id != old_deployment.helm_chart_id);
+    let updates_config = !upgrades_chart
+        || updates
+            .name
+            .as_ref()
+            .is_some_and(|name| *name != old_deployment.name)
+        || updates
+            .cluster_id
+            .is_some_and(|cluster_id| cluster_id != old_deployment.cluster_id)
+        || updates
+            .config
+            .as_ref()
+            .is_some_and(|config| *config != old_deployment.config)
+        || updates
+            .values_override
+            .as_ref()
+            .is_some_and(|values_override| *values_override != old_deployment.values_override)
+        || updates
+            .enabled
+            .is_some_and(|enabled| enabled != old_deployment.enabled)
+        || updates
+            .description_md
+            .as_ref()
+            .is_some_and(|description_md| {
+                Some(description_md) != old_deployment.description_md.as_ref()
+            });
+    if upgrades_chart {
+        verify_deployment_permission(
+            old_deployment.cluster_id,
+            old_deployment.kind_id,
+            Some(old_deployment.id),
+            RolePermission::UpgradeChart,
+            &identity,
+        )
         .await?;
+    }
+    if updates_config {
+        verify_deployment_permission(
+            old_deployment.cluster_id,
+            old_deployment.kind_id,
+            Some(old_deployment.id),
+            RolePermission::UpdateConfig,
+            &identity,
+        )
+        .await?;
+    }
 
     if let Some(new_cluster_id) = updates.cluster_id
         && new_cluster_id != old_deployment.cluster_id
     {
-        verify_deployment_maintainer(new_cluster_id, old_deployment.kind_id, &identity).await?;
+        verify_deployment_permission(
+            new_cluster_id,
+            old_deployment.kind_id,
+            Some(old_deployment.id),
+            RolePermission::UpdateConfig,
+            &identity,
+        )
+        .await?;
+        if K8sCluster::find(new_cluster_id).await?.cordoned {
+            return Ok(HttpResponse::Conflict().json(json!({
+                "error": "The new cluster is cordoned and doesn't accept new deployments",
+            })));
+        }
     }
 
     if old_deployment.enabled && updates.enabled == Some(false) {
@@ -242,7 +320,14 @@ async fn update(
 #[delete("/deployments/{id}")]
 async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
     let deployment = Deployment::find(id.into_inner()).await?;
-    verify_deployment_owner(deployment.cluster_id, deployment.kind_id, &identity).await?;
+    verify_deployment_permission(
+        deployment.cluster_id,
+        deployment.kind_id,
+        Some(deployment.id),
+        RolePermission::Delete,
+        &identity,
+    )
+    .await?;
 
     let dependents = Deployment::find_using(
         &DbTableOrDeploymentResource::DbTable(DbTable::Deployments),
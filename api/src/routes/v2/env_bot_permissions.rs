@@ -0,0 +1,129 @@
+use crate::{permissions::verify_env_admin, result::ApiResult};
+use actix_web::{HttpResponse, delete, get, post, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    AccessScope,
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::{
+        env_bot_permission::{EnvBotPermission, EnvBotPermissionFilters, NewEnvBotPermission},
+        env_user_permission::EnvUserRole,
+    },
+};
+use serde_json::json;
+use uuid::Uuid;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Env Bot Permissions",
+    operation_id = "allEnvBotPermissions",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(EnvBotPermissionFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<EnvBotPermission>,
+        ),
+    ),
+)]
+#[get("/env-bot-permissions")]
+async fn get_all(
+    identity: ApiIdentity,
+    filters: web::Query<EnvBotPermissionFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        EnvBotPermission::all_filtered(filters.into_inner(), pagination.into_inner(), &scope)
+            .await?,
+    ))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Env Bot Permissions",
+    operation_id = "getEnvBotPermission",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = EnvBotPermission,
+        ),
+    ),
+)]
+#[get("/env-bot-permissions/{id}")]
+async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(EnvBotPermission::find_scoped(id.into_inner(), &scope).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Env Bot Permissions",
+    operation_id = "createEnvBotPermission",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = NewEnvBotPermission,
+    responses(
+        (
+            status = CREATED,
+            body = EnvBotPermission,
+        ),
+    ),
+)]
+#[post("/env-bot-permissions")]
+async fn create(
+    identity: ApiIdentity,
+    new_permission: web::Json<NewEnvBotPermission>,
+) -> ApiResult {
+    let new_permission = new_permission.into_inner();
+    verify_env_admin(new_permission.env_id, &identity).await?;
+    if (new_permission.role == EnvUserRole::Custom) != new_permission.custom_role_id.is_some() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "custom_role_id must be set for the Custom role, and only for it",
+        })));
+    }
+    Ok(HttpResponse::Created().json(new_permission.insert().await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Env Bot Permissions",
+    operation_id = "deleteEnvBotPermission",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = NO_CONTENT,
+        ),
+    ),
+)]
+#[delete("/env-bot-permissions/{id}")]
+async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let permission = EnvBotPermission::find(id.into_inner()).await?;
+    verify_env_admin(permission.env_id, &identity).await?;
+    permission.delete().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Env Bot Permissions",
+        description = "\
+Env roles of bots. Bots that aren't unrestricted can only see envs they
+have a role in, and act as env admins where they have the Admin role.
+        ",
+    )),
+    paths(get_all, get_one, create, delete),
+)]
+pub(super) struct OpenApi;
@@ -5,7 +5,7 @@ use platz_db::{
     AccessScope,
     diesel_pagination::{Paginated, PaginationParams},
     schema::env_user_permission::{
-        EnvUserPermission, EnvUserPermissionFilters, NewEnvUserPermission,
+        EnvUserPermission, EnvUserPermissionFilters, EnvUserRole, NewEnvUserPermission,
     },
 };
 use serde_json::json;
@@ -84,6 +84,11 @@ async fn create(
 ) -> ApiResult {
     let new_permission = new_permission.into_inner();
     verify_env_admin(new_permission.env_id, &identity).await?;
+    if (new_permission.role == EnvUserRole::Custom) != new_permission.custom_role_id.is_some() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "custom_role_id must be set for the Custom role, and only for it",
+        })));
+    }
     Ok(HttpResponse::Created().json(new_permission.insert().await?))
 }
 
@@ -90,6 +90,8 @@ async fn create(identity: ApiIdentity, new_env: web::Json<NewEnv>) -> ApiResult
             .user_id()
             .expect("Site admin must be a user"),
         role: EnvUserRole::Admin,
+        custom_role_id: None,
+        expires_at: None,
     }
     .insert()
     .await?;
@@ -0,0 +1,209 @@
+use super::utils::ensure_user;
+use crate::{permissions::verify_site_admin, result::ApiResult};
+use actix_web::{HttpResponse, get, post, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::{
+        deployment::Deployment,
+        k8s_cluster::{K8sCluster, UpdateK8sCluster},
+        k8s_cluster_drain::{
+            K8sClusterDrain, K8sClusterDrainFilters, K8sClusterDrainStatus, NewK8sClusterDrain,
+        },
+    },
+};
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const DEFAULT_DRAIN_BATCH_SIZE: i32 = 5;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Drains",
+    operation_id = "allK8sClusterDrains",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(K8sClusterDrainFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<K8sClusterDrain>,
+        ),
+    ),
+)]
+#[get("/k8s-cluster-drains")]
+async fn get_all(
+    _identity: ApiIdentity,
+    filters: web::Query<K8sClusterDrainFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    Ok(HttpResponse::Ok()
+        .json(K8sClusterDrain::all_filtered(filters.into_inner(), pagination.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Drains",
+    operation_id = "getK8sClusterDrain",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = K8sClusterDrain,
+        ),
+    ),
+)]
+#[get("/k8s-cluster-drains/{id}")]
+async fn get_one(_identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(K8sClusterDrain::find(id.into_inner()).await?))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateK8sClusterDrain {
+    /// The cluster to drain
+    pub cluster_id: Uuid,
+    /// The cluster to move deployments to, must be in the same env
+    pub target_cluster_id: Uuid,
+    /// How many deployments to move at a time, defaults to 5
+    #[schema(required)]
+    pub batch_size: Option<i32>,
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Drains",
+    operation_id = "createK8sClusterDrain",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = CreateK8sClusterDrain,
+    responses(
+        (
+            status = CREATED,
+            body = K8sClusterDrain,
+        ),
+    ),
+)]
+#[post("/k8s-cluster-drains")]
+async fn create(identity: ApiIdentity, body: web::Json<CreateK8sClusterDrain>) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    let user = ensure_user(&identity).await?;
+    let body = body.into_inner();
+
+    let batch_size = body.batch_size.unwrap_or(DEFAULT_DRAIN_BATCH_SIZE);
+    if batch_size < 1 {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Batch size must be at least 1",
+        })));
+    }
+    if body.cluster_id == body.target_cluster_id {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Can't drain a cluster into itself",
+        })));
+    }
+
+    let cluster = K8sCluster::find(body.cluster_id).await?;
+    let target_cluster = K8sCluster::find(body.target_cluster_id).await?;
+    if cluster.env_id.is_none() || cluster.env_id != target_cluster.env_id {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "The target cluster must be in the same env as the drained cluster",
+        })));
+    }
+    if target_cluster.ignore || target_cluster.cordoned {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "The target cluster is ignored or cordoned",
+        })));
+    }
+    if K8sClusterDrain::find_running_for_cluster(cluster.id)
+        .await?
+        .is_some()
+    {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "This cluster is already being drained",
+        })));
+    }
+
+    UpdateK8sCluster {
+        env_id: None,
+        ignore: None,
+        ingress_domain: None,
+        ingress_class: None,
+        ingress_tls_secret_name: None,
+        grafana_url: None,
+        grafana_datasource_name: None,
+        cordoned: Some(true),
+    }
+    .save(cluster.id)
+    .await?;
+
+    let drain = NewK8sClusterDrain {
+        created_by_user_id: user.id,
+        cluster_id: cluster.id,
+        target_cluster_id: target_cluster.id,
+        batch_size,
+        total_deployments: Deployment::find_by_cluster_id(cluster.id).await?.len() as i32,
+    }
+    .insert()
+    .await?;
+
+    Ok(HttpResponse::Created().json(drain))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Drains",
+    operation_id = "cancelK8sClusterDrain",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = K8sClusterDrain,
+        ),
+    ),
+)]
+#[post("/k8s-cluster-drains/{id}/cancel")]
+async fn cancel(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    let drain = K8sClusterDrain::find(id.into_inner()).await?;
+    match drain
+        .finish(
+            K8sClusterDrainStatus::Canceled,
+            Some("Canceled by user".to_owned()),
+        )
+        .await?
+    {
+        Some(drain) => Ok(HttpResponse::Ok().json(drain)),
+        None => Ok(HttpResponse::Conflict().json(json!({
+            "error": "This drain has already finished",
+        }))),
+    }
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Kubernetes Cluster Drains",
+        description = "\
+This collection contains cluster drains, which move all deployments of a
+cluster to another cluster in the same env.
+
+Creating a drain cordons the cluster so it doesn't accept new deployments.
+Deployments are then moved in batches, waiting for each batch's tasks to
+finish before starting the next one. Clusters stay cordoned after a drain
+finishes or is canceled, until uncordoned by updating the cluster.
+",
+    )),
+    paths(get_all, get_one, create, cancel),
+)]
+pub(super) struct OpenApi;
@@ -0,0 +1,162 @@
+use crate::{permissions::verify_site_admin, result::ApiResult};
+use actix_web::{HttpResponse, delete, get, post, put, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::k8s_cluster_rule::{
+        K8sClusterRule, K8sClusterRuleFilters, K8sClusterRuleMatches, NewK8sClusterRule,
+        UpdateK8sClusterRule,
+    },
+};
+use uuid::Uuid;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Rules",
+    operation_id = "allK8sClusterRules",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(K8sClusterRuleFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<K8sClusterRule>,
+        ),
+    ),
+)]
+#[get("/k8s-cluster-rules")]
+async fn get_all(
+    _identity: ApiIdentity,
+    filters: web::Query<K8sClusterRuleFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    Ok(HttpResponse::Ok()
+        .json(K8sClusterRule::all_filtered(filters.into_inner(), pagination.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Rules",
+    operation_id = "dryRunK8sClusterRules",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = Vec<K8sClusterRuleMatches>,
+        ),
+    ),
+)]
+#[get("/k8s-cluster-rules/dry-run")]
+async fn dry_run(identity: ApiIdentity) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    Ok(HttpResponse::Ok().json(K8sClusterRule::dry_run().await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Rules",
+    operation_id = "getK8sClusterRule",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = K8sClusterRule,
+        ),
+    ),
+)]
+#[get("/k8s-cluster-rules/{id}")]
+async fn get_one(_identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    Ok(HttpResponse::Ok().json(K8sClusterRule::find(id.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Rules",
+    operation_id = "createK8sClusterRule",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = NewK8sClusterRule,
+    responses(
+        (
+            status = CREATED,
+            body = K8sClusterRule,
+        ),
+    ),
+)]
+#[post("/k8s-cluster-rules")]
+async fn create(identity: ApiIdentity, new_rule: web::Json<NewK8sClusterRule>) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    Ok(HttpResponse::Created().json(new_rule.into_inner().insert().await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Rules",
+    operation_id = "updateK8sClusterRule",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = UpdateK8sClusterRule,
+    responses(
+        (
+            status = OK,
+            body = K8sClusterRule,
+        ),
+    ),
+)]
+#[put("/k8s-cluster-rules/{id}")]
+async fn update(
+    identity: ApiIdentity,
+    id: web::Path<Uuid>,
+    data: web::Json<UpdateK8sClusterRule>,
+) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    Ok(HttpResponse::Ok().json(data.into_inner().save(id.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Cluster Rules",
+    operation_id = "deleteK8sClusterRule",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = NO_CONTENT,
+        ),
+    ),
+)]
+#[delete("/k8s-cluster-rules/{id}")]
+async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    let rule = K8sClusterRule::find(id.into_inner()).await?;
+    rule.delete().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Kubernetes Cluster Rules",
+        description = "\
+This collection contains rules for attaching newly discovered Kubernetes
+clusters to envs and setting their ingress configuration. The first rule
+matching a new cluster, ordered by priority, is applied to it.
+        ",
+    )),
+    paths(get_all, dry_run, get_one, create, update, delete),
+)]
+pub(super) struct OpenApi;
@@ -1,11 +1,12 @@
 use crate::{permissions::verify_site_admin, result::ApiResult};
-use actix_web::{HttpResponse, delete, get, put, web};
+use actix_web::{HttpResponse, delete, get, post, put, web};
 use platz_auth::ApiIdentity;
 use platz_db::{
     diesel_pagination::{Paginated, PaginationParams},
     schema::{
         deployment::Deployment,
         k8s_cluster::{K8sCluster, K8sClusterFilters, UpdateK8sCluster},
+        k8s_cluster_health::{K8sClusterHealth, K8sClusterHealthExtraFilters},
     },
 };
 use serde_json::json;
@@ -57,6 +58,65 @@ async fn get_one(_identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
     Ok(HttpResponse::Ok().json(K8sCluster::find(id.into_inner()).await?))
 }
 
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Clusters",
+    operation_id = "getK8sClusterHealth",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = K8sClusterHealth,
+        ),
+    ),
+)]
+#[get("/k8s-clusters/{id}/health")]
+async fn get_health(_identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let cluster = K8sCluster::find(id.into_inner()).await?;
+    match K8sClusterHealth::latest(cluster.id).await? {
+        Some(health) => Ok(HttpResponse::Ok().json(health)),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "Health of this cluster hasn't been collected yet",
+        }))),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Clusters",
+    operation_id = "getK8sClusterHealthHistory",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = Paginated<K8sClusterHealth>,
+        ),
+    ),
+)]
+#[get("/k8s-clusters/{id}/health/history")]
+async fn get_health_history(
+    _identity: ApiIdentity,
+    id: web::Path<Uuid>,
+    extra_filters: web::Query<K8sClusterHealthExtraFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let cluster = K8sCluster::find(id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        K8sClusterHealth::history(
+            cluster.id,
+            extra_filters.into_inner(),
+            pagination.into_inner(),
+        )
+        .await?,
+    ))
+}
+
 #[utoipa::path(
     context_path = "/api/v2",
     tag = "Kubernetes Clusters",
@@ -113,14 +173,47 @@ async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
     }
 }
 
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Kubernetes Clusters",
+    operation_id = "forgetK8sCluster",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = NO_CONTENT,
+        ),
+    ),
+)]
+#[post("/k8s-clusters/{id}/forget")]
+async fn forget(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    verify_site_admin(&identity).await?;
+    let cluster = K8sCluster::find(id.into_inner()).await?;
+    if cluster.missing_since.is_none() {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "Only clusters missing from cluster discovery can be forgotten",
+        })));
+    }
+    cluster.forget().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
 #[derive(utoipa::OpenApi)]
 #[openapi(
     tags((
         name = "Kubernetes Clusters",
         description = "\
-This collection contains Kubernetes clusters detected by Plaz.
+This collection contains Kubernetes clusters detected by Plaz, along with
+their periodically collected health and capacity.
+
+Clusters that stop showing up in cluster discovery are marked as missing,
+their deployments are marked as orphaned and their pending tasks are failed.
+Missing clusters can be forgotten, deleting them along with all of their
+deployments.
         ",
     )),
-    paths(get_all, get_one, update, delete),
+    paths(get_all, get_one, get_health, get_health_history, update, delete, forget),
 )]
 pub(super) struct OpenApi;
@@ -3,7 +3,7 @@ use actix_web::{HttpResponse, get, web};
 use platz_auth::ApiIdentity;
 use platz_db::{
     diesel_pagination::{Paginated, PaginationParams},
-    schema::k8s_resource::{K8sResource, K8sResourceFilters},
+    schema::k8s_resource::{K8sResource, K8sResourceExtraFilters, K8sResourceFilters},
 };
 use uuid::Uuid;
 
@@ -27,10 +27,17 @@ use uuid::Uuid;
 async fn get_all(
     _identity: ApiIdentity,
     filters: web::Query<K8sResourceFilters>,
+    extra_filters: web::Query<K8sResourceExtraFilters>,
     pagination: web::Query<PaginationParams>,
 ) -> ApiResult {
-    Ok(HttpResponse::Ok()
-        .json(K8sResource::all_filtered(filters.into_inner(), pagination.into_inner()).await?))
+    Ok(HttpResponse::Ok().json(
+        K8sResource::all_filtered(
+            filters.into_inner(),
+            extra_filters.into_inner(),
+            pagination.into_inner(),
+        )
+        .await?,
+    ))
 }
 
 #[utoipa::path(
@@ -1,17 +1,26 @@
+mod access_requests;
+mod audit_log;
 mod auth;
 mod bot_tokens;
 mod bots;
+mod custom_roles;
+mod deployment_action_restrictions;
+mod deployment_bot_permissions;
+mod deployment_env_grants;
 mod deployment_kinds;
 mod deployment_permissions;
 mod deployment_resource_types;
 mod deployment_resources;
 mod deployment_tasks;
 mod deployments;
+mod env_bot_permissions;
 mod env_user_permissions;
 mod envs;
 mod helm_charts;
 mod helm_registries;
 mod helm_tag_formats;
+mod k8s_cluster_drains;
+mod k8s_cluster_rules;
 mod k8s_clusters;
 mod k8s_resources;
 mod secrets;
@@ -19,6 +28,8 @@ mod server;
 mod user_tokens;
 mod users;
 mod utils;
+mod webhook_deliveries;
+mod webhooks;
 mod ws;
 
 use actix_web::web;
@@ -26,6 +37,14 @@ use platz_db::{DbTable, DbTableOrDeploymentResource};
 use utoipa::OpenApi;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(access_requests::get_all);
+    cfg.service(access_requests::get_one);
+    cfg.service(access_requests::create);
+    cfg.service(access_requests::approve);
+    cfg.service(access_requests::reject);
+    cfg.service(access_requests::cancel);
+    cfg.service(audit_log::get_all);
+    cfg.service(audit_log::get_one);
     cfg.service(auth::me);
     cfg.service(auth::start_google_login);
     cfg.service(auth::finish_google_login);
@@ -38,6 +57,24 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(bots::create);
     cfg.service(bots::update);
     cfg.service(bots::delete);
+    cfg.service(custom_roles::get_all);
+    cfg.service(custom_roles::get_one);
+    cfg.service(custom_roles::create);
+    cfg.service(custom_roles::update);
+    cfg.service(custom_roles::delete);
+    cfg.service(deployment_action_restrictions::get_all);
+    cfg.service(deployment_action_restrictions::get_one);
+    cfg.service(deployment_action_restrictions::create);
+    cfg.service(deployment_action_restrictions::update);
+    cfg.service(deployment_action_restrictions::delete);
+    cfg.service(deployment_bot_permissions::get_all);
+    cfg.service(deployment_bot_permissions::get_one);
+    cfg.service(deployment_bot_permissions::create);
+    cfg.service(deployment_bot_permissions::delete);
+    cfg.service(deployment_env_grants::get_all);
+    cfg.service(deployment_env_grants::get_one);
+    cfg.service(deployment_env_grants::create);
+    cfg.service(deployment_env_grants::delete);
     cfg.service(deployment_kinds::get_all);
     cfg.service(deployment_kinds::get_one);
     cfg.service(deployment_kinds::update);
@@ -61,6 +98,10 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(deployments::create);
     cfg.service(deployments::update);
     cfg.service(deployments::delete);
+    cfg.service(env_bot_permissions::get_all);
+    cfg.service(env_bot_permissions::get_one);
+    cfg.service(env_bot_permissions::create);
+    cfg.service(env_bot_permissions::delete);
     cfg.service(env_user_permissions::get_all);
     cfg.service(env_user_permissions::get_one);
     cfg.service(env_user_permissions::create);
@@ -79,16 +120,31 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(helm_tag_formats::get_one);
     cfg.service(helm_tag_formats::create);
     cfg.service(helm_tag_formats::delete);
+    cfg.service(k8s_cluster_drains::get_all);
+    cfg.service(k8s_cluster_drains::get_one);
+    cfg.service(k8s_cluster_drains::create);
+    cfg.service(k8s_cluster_drains::cancel);
+    cfg.service(k8s_cluster_rules::get_all);
+    cfg.service(k8s_cluster_rules::dry_run);
+    cfg.service(k8s_cluster_rules::get_one);
+    cfg.service(k8s_cluster_rules::create);
+    cfg.service(k8s_cluster_rules::update);
+    cfg.service(k8s_cluster_rules::delete);
     cfg.service(k8s_clusters::get_all);
     cfg.service(k8s_clusters::get_one);
+    cfg.service(k8s_clusters::get_health);
+    cfg.service(k8s_clusters::get_health_history);
     cfg.service(k8s_clusters::update);
     cfg.service(k8s_clusters::delete);
+    cfg.service(k8s_clusters::forget);
     cfg.service(k8s_resources::get_all);
     cfg.service(k8s_resources::get_one);
     cfg.service(secrets::get_all);
     cfg.service(secrets::get_one);
     cfg.service(secrets::create);
     cfg.service(secrets::update);
+    cfg.service(secrets::get_versions);
+    cfg.service(secrets::restore_version);
     cfg.service(secrets::delete);
     cfg.service(server::get_one);
     cfg.service(user_tokens::get_all);
@@ -98,6 +154,14 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(users::get_all);
     cfg.service(users::get_one);
     cfg.service(users::update);
+    cfg.service(webhook_deliveries::get_all);
+    cfg.service(webhook_deliveries::get_one);
+    cfg.service(webhook_deliveries::redeliver);
+    cfg.service(webhooks::get_all);
+    cfg.service(webhooks::get_one);
+    cfg.service(webhooks::create);
+    cfg.service(webhooks::update);
+    cfg.service(webhooks::delete);
     cfg.service(web::scope("/ws").configure(ws::config));
 }
 
@@ -119,24 +183,35 @@ pub(super) struct ApiV2;
 impl ApiV2 {
     pub fn openapi() -> utoipa::openapi::OpenApi {
         let mut openapi = <ApiV2 as OpenApi>::openapi();
+        openapi.merge(access_requests::OpenApi::openapi());
+        openapi.merge(audit_log::OpenApi::openapi());
         openapi.merge(auth::OpenApi::openapi());
+        openapi.merge(custom_roles::OpenApi::openapi());
+        openapi.merge(deployment_action_restrictions::OpenApi::openapi());
+        openapi.merge(deployment_bot_permissions::OpenApi::openapi());
+        openapi.merge(deployment_env_grants::OpenApi::openapi());
         openapi.merge(deployment_kinds::OpenApi::openapi());
         openapi.merge(deployment_permissions::OpenApi::openapi());
         openapi.merge(deployment_resource_types::OpenApi::openapi());
         openapi.merge(deployment_resources::OpenApi::openapi());
         openapi.merge(deployment_tasks::OpenApi::openapi());
         openapi.merge(deployments::OpenApi::openapi());
+        openapi.merge(env_bot_permissions::OpenApi::openapi());
         openapi.merge(env_user_permissions::OpenApi::openapi());
         openapi.merge(envs::OpenApi::openapi());
         openapi.merge(helm_charts::OpenApi::openapi());
         openapi.merge(helm_registries::OpenApi::openapi());
         openapi.merge(helm_tag_formats::OpenApi::openapi());
+        openapi.merge(k8s_cluster_drains::OpenApi::openapi());
+        openapi.merge(k8s_cluster_rules::OpenApi::openapi());
         openapi.merge(k8s_clusters::OpenApi::openapi());
         openapi.merge(k8s_resources::OpenApi::openapi());
         openapi.merge(secrets::OpenApi::openapi());
         openapi.merge(server::OpenApi::openapi());
         openapi.merge(user_tokens::OpenApi::openapi());
         openapi.merge(users::OpenApi::openapi());
+        openapi.merge(webhook_deliveries::OpenApi::openapi());
+        openapi.merge(webhooks::OpenApi::openapi());
         openapi.merge(bots::OpenApi::openapi());
         openapi.merge(bot_tokens::OpenApi::openapi());
         openapi.merge(ws::OpenApi::openapi());
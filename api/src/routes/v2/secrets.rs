@@ -1,14 +1,17 @@
 use super::deployments::using_error;
-use crate::{permissions::verify_env_admin, result::ApiResult};
+use crate::{permissions::verify_env_permission, result::ApiResult};
 use actix_web::{HttpResponse, delete, get, post, put, web};
 use platz_auth::ApiIdentity;
 use platz_db::{
     AccessScope, DbTable, DbTableOrDeploymentResource,
     diesel_pagination::{Paginated, PaginationParams},
     schema::{
+        custom_role::RolePermission,
         deployment::Deployment,
-        secret::{NewSecret, Secret, SecretFilters, UpdateSecret},
+        secret::{NewSecret, Secret, SecretFilters, SecretStoreKind, UpdateSecret},
+        secret_version::SecretVersion,
     },
+    secret_store::validate_reference,
 };
 use serde_json::json;
 use uuid::Uuid;
@@ -80,8 +83,33 @@ async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
 #[post("/secrets")]
 async fn create(identity: ApiIdentity, new_secret: web::Json<NewSecret>) -> ApiResult {
     let new_secret = new_secret.into_inner();
-    verify_env_admin(new_secret.env_id, &identity).await?;
-    Ok(HttpResponse::Created().json(new_secret.insert().await?))
+    verify_env_permission(new_secret.env_id, RolePermission::ManageSecrets, &identity).await?;
+    let reference_error = match (
+        new_secret.store,
+        new_secret.store_path.as_deref(),
+        new_secret.store_key.as_deref(),
+    ) {
+        (SecretStoreKind::Postgres, None, None) => None,
+        (SecretStoreKind::Postgres, _, _) => {
+            Some("store_path and store_key are only used for external stores".to_owned())
+        }
+        (_, Some(path), Some(key)) if new_secret.contents.is_empty() => {
+            validate_reference(new_secret.env_id, path, key)
+                .err()
+                .map(|err| err.to_string())
+        }
+        (_, Some(_), Some(_)) => Some("Secrets in external stores can't have contents".to_owned()),
+        _ => Some("store_path and store_key are required for external stores".to_owned()),
+    };
+    if let Some(error) = reference_error {
+        return Ok(HttpResponse::BadRequest().json(json!({ "error": error })));
+    }
+    if new_secret.store == SecretStoreKind::Postgres
+        && let Err(error) = new_secret.secret_type.validate(&new_secret.contents)
+    {
+        return Ok(HttpResponse::BadRequest().json(json!({ "error": error })));
+    }
+    Ok(HttpResponse::Created().json(new_secret.insert(identity.inner()).await?))
 }
 
 #[utoipa::path(
@@ -110,14 +138,120 @@ async fn update(
     let update = update.into_inner();
 
     let old = Secret::find(id).await?;
-    verify_env_admin(old.env_id, &identity).await?;
-    let new = update.save(id).await?;
+    verify_env_permission(old.env_id, RolePermission::ManageSecrets, &identity).await?;
+    if old.write_only && update.write_only == Some(false) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Write-only can't be turned off",
+        })));
+    }
+    if old.store == SecretStoreKind::Postgres {
+        if update.store_path.is_some() || update.store_key.is_some() {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "store_path and store_key are only used for external stores",
+            })));
+        }
+        if let Some(contents) = update.contents.as_deref()
+            && let Err(error) = old.secret_type.validate(contents)
+        {
+            return Ok(HttpResponse::BadRequest().json(json!({ "error": error })));
+        }
+    } else {
+        if update.contents.is_some() {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Secrets in external stores can't have contents, update store_path and store_key instead",
+            })));
+        }
+        if let Err(err) = validate_reference(
+            old.env_id,
+            update
+                .store_path
+                .as_deref()
+                .or(old.store_path.as_deref())
+                .unwrap_or_default(),
+            update
+                .store_key
+                .as_deref()
+                .or(old.store_key.as_deref())
+                .unwrap_or_default(),
+        ) {
+            return Ok(HttpResponse::BadRequest().json(json!({ "error": err.to_string() })));
+        }
+    }
+    // Names are resolved into chart values, so only value changes can take
+    // the faster path of refreshing Kubernetes secrets
+    let changes_value_only = update.name.as_ref().is_none_or(|name| *name == old.name)
+        && (update.contents.is_some() || update.store_path.is_some() || update.store_key.is_some());
+    let new = update.save(id, identity.inner()).await?;
+
+    let collection = DbTableOrDeploymentResource::DbTable(DbTable::Secrets);
+    let reason = format!("{} secret has been updated", new.collection);
+    if changes_value_only {
+        Deployment::refresh_secrets_all_using(&collection, id, &identity, reason).await?;
+    } else {
+        Deployment::reinstall_all_using(&collection, id, &identity, reason).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(new))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Secrets",
+    operation_id = "allSecretVersions",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = Paginated<SecretVersion>,
+        ),
+    ),
+)]
+#[get("/secrets/{id}/versions")]
+async fn get_versions(
+    identity: ApiIdentity,
+    id: web::Path<Uuid>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    let secret = Secret::find_scoped(id.into_inner(), &scope).await?;
+    Ok(HttpResponse::Ok()
+        .json(SecretVersion::all_for_secret(secret.id, pagination.into_inner()).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Secrets",
+    operation_id = "restoreSecretVersion",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = Secret,
+        ),
+    ),
+)]
+#[post("/secrets/{id}/versions/{version_id}/restore")]
+async fn restore_version(identity: ApiIdentity, path: web::Path<(Uuid, Uuid)>) -> ApiResult {
+    let (id, version_id) = path.into_inner();
+    let old = Secret::find(id).await?;
+    verify_env_permission(old.env_id, RolePermission::ManageSecrets, &identity).await?;
+    let version = SecretVersion::find(version_id).await?;
+    if version.secret_id != old.id {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let new = old.restore(&version, identity.inner()).await?;
 
-    Deployment::reinstall_all_using(
+    Deployment::refresh_secrets_all_using(
         &DbTableOrDeploymentResource::DbTable(DbTable::Secrets),
         id,
         &identity,
-        format!("{} secret has been updated", new.collection),
+        format!("{} secret has been restored", new.collection),
     )
     .await?;
 
@@ -143,7 +277,7 @@ async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
     let id = id.into_inner();
     let secret = Secret::find(id).await?;
 
-    verify_env_admin(secret.env_id, &identity).await?;
+    verify_env_permission(secret.env_id, RolePermission::ManageSecrets, &identity).await?;
 
     let dependents =
         Deployment::find_using(&DbTableOrDeploymentResource::DbTable(DbTable::Secrets), id).await?;
@@ -167,8 +301,42 @@ Secrets are stored in envs and can be referenced by chart inputs by using the
 
 Kubernetes secrets are created during deployment as defined in the chart
 extensions. See chart extensions documentation for more information.
+
+Instead of holding its contents, a secret can reference a value in an external
+store by setting `store` to `Vault` or `Files` along with `store_path` and
+`store_key`. The value is read when deployments using the secret are
+installed, and failing to read it fails the deployment task. `store_path` must
+be under `envs/<env id>`, so secrets can only reference their own env's values.
+
+Contents are never returned by the API, only a fingerprint of secrets stored
+in Postgres and when they were last updated. Secrets can be marked write-only
+to record that they're never meant to be read, which can't be turned off.
+
+Secrets of type `DockerConfigJson` hold registry credentials in
+`.dockerconfigjson` format. They are created as image pull secrets in every
+deployment namespace of the env and kept up to date, and their names are
+passed to charts in `platz.image_pull_secrets`, ready to be used as
+`imagePullSecrets`.
+
+Every value a secret had is kept as a version, recording who set it and when.
+Restoring a version sets the secret's value back to it as a new version, and
+refreshes deployments using the secret like an update does.
+
+Updating a secret's value re-applies only the changed Kubernetes secrets of
+deployments using it and restarts the workloads mounting them, falling back
+to reinstalling the deployment when that fails. Deployments whose chart values
+use the secret, and all deployments using it when its name changes, are
+reinstalled instead.
         ",
     )),
-    paths(get_all, get_one, create, update, delete),
+    paths(
+        get_all,
+        get_one,
+        create,
+        update,
+        get_versions,
+        restore_version,
+        delete,
+    ),
 )]
 pub(super) struct OpenApi;
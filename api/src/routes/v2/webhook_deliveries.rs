@@ -0,0 +1,110 @@
+use crate::{permissions::verify_env_admin, result::ApiResult};
+use actix_web::{HttpResponse, get, post, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    AccessScope,
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::{
+        webhook::Webhook,
+        webhook_delivery::{WebhookDelivery, WebhookDeliveryFilters, WebhookDeliveryStatus},
+    },
+};
+use serde_json::json;
+use uuid::Uuid;
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Webhook Deliveries",
+    operation_id = "allWebhookDeliveries",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(WebhookDeliveryFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<WebhookDelivery>,
+        ),
+    ),
+)]
+#[get("/webhook-deliveries")]
+async fn get_all(
+    identity: ApiIdentity,
+    filters: web::Query<WebhookDeliveryFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        WebhookDelivery::all_filtered(filters.into_inner(), pagination.into_inner(), &scope)
+            .await?,
+    ))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Webhook Deliveries",
+    operation_id = "getWebhookDelivery",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = WebhookDelivery,
+        ),
+    ),
+)]
+#[get("/webhook-deliveries/{id}")]
+async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    let delivery = WebhookDelivery::find(id.into_inner()).await?;
+    Webhook::find_scoped(delivery.webhook_id, &scope).await?;
+    Ok(HttpResponse::Ok().json(delivery))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Webhook Deliveries",
+    operation_id = "redeliverWebhookDelivery",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = WebhookDelivery,
+        ),
+    ),
+)]
+#[post("/webhook-deliveries/{id}/redeliver")]
+async fn redeliver(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let delivery = WebhookDelivery::find(id.into_inner()).await?;
+    let webhook = Webhook::find(delivery.webhook_id).await?;
+    verify_env_admin(webhook.env_id, &identity).await?;
+    if delivery.status == WebhookDeliveryStatus::Pending {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "This delivery is still pending",
+        })));
+    }
+    Ok(HttpResponse::Ok().json(delivery.redeliver().await?))
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Webhook Deliveries",
+        description = "\
+This collection contains the events sent to webhooks and the outcome of their
+last attempt.
+
+Failed deliveries are retried with exponential backoff. Deliveries that run
+out of attempts, or whose webhook was disabled, become dead letters and are
+kept until redelivered.
+",
+    )),
+    paths(get_all, get_one, redeliver),
+)]
+pub(super) struct OpenApi;
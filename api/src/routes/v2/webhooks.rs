@@ -0,0 +1,199 @@
+use crate::{permissions::verify_env_admin, result::ApiResult, webhooks::is_public_ip};
+use actix_web::{HttpResponse, delete, get, post, put, web};
+use platz_auth::ApiIdentity;
+use platz_db::{
+    AccessScope,
+    diesel_pagination::{Paginated, PaginationParams},
+    schema::webhook::{NewWebhook, UpdateWebhook, Webhook, WebhookFilters},
+};
+use serde_json::json;
+use std::net::IpAddr;
+use url::{Host, Url};
+use uuid::Uuid;
+
+const MIN_SIGNING_SECRET_LEN: usize = 16;
+
+fn validate(url: Option<&str>, signing_secret: Option<&str>) -> Result<(), String> {
+    if let Some(url) = url {
+        match Url::parse(url) {
+            Ok(url) if !matches!(url.scheme(), "http" | "https") => {
+                return Err("Webhook URLs must be http or https".to_owned());
+            }
+            // Hostnames are checked again after resolving them when delivering
+            Ok(url) => {
+                let public = match url.host() {
+                    Some(Host::Ipv4(ip)) => is_public_ip(IpAddr::V4(ip)),
+                    Some(Host::Ipv6(ip)) => is_public_ip(IpAddr::V6(ip)),
+                    Some(Host::Domain(domain)) => {
+                        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+                        domain != "localhost" && !domain.ends_with(".localhost")
+                    }
+                    None => false,
+                };
+                if !public {
+                    return Err("Webhook URLs must point to a public address".to_owned());
+                }
+            }
+            Err(err) => return Err(format!("Invalid webhook URL: {err}")),
+        }
+    }
+    if signing_secret.is_some_and(|secret| secret.len() < MIN_SIGNING_SECRET_LEN) {
+        return Err(format!(
+            "Signing secrets must be at least {MIN_SIGNING_SECRET_LEN} characters long"
+        ));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Webhooks",
+    operation_id = "allWebhooks",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    params(WebhookFilters),
+    responses(
+        (
+            status = OK,
+            body = Paginated<Webhook>,
+        ),
+    ),
+)]
+#[get("/webhooks")]
+async fn get_all(
+    identity: ApiIdentity,
+    filters: web::Query<WebhookFilters>,
+    pagination: web::Query<PaginationParams>,
+) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok()
+        .json(Webhook::all_filtered(filters.into_inner(), pagination.into_inner(), &scope).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Webhooks",
+    operation_id = "getWebhook",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = OK,
+            body = Webhook,
+        ),
+    ),
+)]
+#[get("/webhooks/{id}")]
+async fn get_one(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let scope = AccessScope::for_identity(identity.inner()).await?;
+    Ok(HttpResponse::Ok().json(Webhook::find_scoped(id.into_inner(), &scope).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Webhooks",
+    operation_id = "createWebhook",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = NewWebhook,
+    responses(
+        (
+            status = CREATED,
+            body = Webhook,
+        ),
+    ),
+)]
+#[post("/webhooks")]
+async fn create(identity: ApiIdentity, new_webhook: web::Json<NewWebhook>) -> ApiResult {
+    let new_webhook = new_webhook.into_inner();
+    verify_env_admin(new_webhook.env_id, &identity).await?;
+    if let Err(error) = validate(Some(&new_webhook.url), Some(&new_webhook.signing_secret)) {
+        return Ok(HttpResponse::BadRequest().json(json!({ "error": error })));
+    }
+    Ok(HttpResponse::Created().json(new_webhook.insert().await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Webhooks",
+    operation_id = "updateWebhook",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    request_body = UpdateWebhook,
+    responses(
+        (
+            status = OK,
+            body = Webhook,
+        ),
+    ),
+)]
+#[put("/webhooks/{id}")]
+async fn update(
+    identity: ApiIdentity,
+    id: web::Path<Uuid>,
+    update: web::Json<UpdateWebhook>,
+) -> ApiResult {
+    let id = id.into_inner();
+    let update = update.into_inner();
+    let old = Webhook::find(id).await?;
+    verify_env_admin(old.env_id, &identity).await?;
+    if let Err(error) = validate(update.url.as_deref(), update.signing_secret.as_deref()) {
+        return Ok(HttpResponse::BadRequest().json(json!({ "error": error })));
+    }
+    Ok(HttpResponse::Ok().json(update.save(id).await?))
+}
+
+#[utoipa::path(
+    context_path = "/api/v2",
+    tag = "Webhooks",
+    operation_id = "deleteWebhook",
+    security(
+        ("access_token" = []),
+        ("user_token" = []),
+    ),
+    responses(
+        (
+            status = NO_CONTENT,
+        ),
+    ),
+)]
+#[delete("/webhooks/{id}")]
+async fn delete(identity: ApiIdentity, id: web::Path<Uuid>) -> ApiResult {
+    let webhook = Webhook::find(id.into_inner()).await?;
+    verify_env_admin(webhook.env_id, &identity).await?;
+    webhook.delete().await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((
+        name = "Webhooks",
+        description = "\
+This collection contains webhooks, HTTP endpoints notified of task and
+deployment events in an env, optionally limited to a deployment kind and to
+some event types.
+
+Events are POSTed as JSON with these headers:
+
+* `X-Platz-Event`: the event type.
+* `X-Platz-Delivery`: the delivery ID, the same across retries.
+* `X-Platz-Timestamp`: when the request was sent, in seconds since the epoch.
+* `X-Platz-Signature`: `sha256=` followed by the hex encoded HMAC-SHA256 of
+  `{timestamp}.{body}` with the webhook's signing secret.
+
+Failed deliveries are retried with exponential backoff, see the webhook
+deliveries collection.
+",
+    )),
+    paths(get_all, get_one, create, update, delete),
+)]
+pub(super) struct OpenApi;
@@ -1,8 +1,11 @@
-use actix_web::middleware::Logger;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::{Logger, Next, from_fn};
 use actix_web::{App, HttpResponse, HttpServer, error::InternalError, web};
 use anyhow::Result;
 use prometheus::Encoder;
 use serde_json::json;
+use tracing::Instrument;
 
 #[derive(clap::Args)]
 #[group(skip)]
@@ -37,7 +40,12 @@ pub async fn serve(config: Config) -> Result<()> {
             .app_data(oidc_login.clone())
             .route("/status", web::get().to(status))
             .route("/metrics", web::get().to(metrics))
-            .service(web::scope("/api/v2").configure(crate::routes::v2::config))
+            .service(
+                web::scope("/api/v2")
+                    .wrap(from_fn(crate::audit::audit_mutations))
+                    .wrap(from_fn(trace_requests))
+                    .configure(crate::routes::v2::config),
+            )
     });
 
     Ok(server
@@ -46,6 +54,31 @@ pub async fn serve(config: Config) -> Result<()> {
         .await?)
 }
 
+/// Runs each request in its own span, continuing the caller's trace if it sent
+/// a `traceparent` header. Tasks created by the request carry its trace on to
+/// the agent.
+async fn trace_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        path = %req.path(),
+        status = tracing::field::Empty,
+    );
+    if let Some(traceparent) = req
+        .headers()
+        .get("traceparent")
+        .and_then(|value| value.to_str().ok())
+    {
+        platz_otel::set_traceparent(&span, traceparent);
+    }
+    let res = next.call(req).instrument(span.clone()).await?;
+    span.record("status", res.status().as_u16());
+    Ok(res)
+}
+
 async fn status() -> crate::result::ApiResult {
     Ok(HttpResponse::Ok().json("ok"))
 }
@@ -0,0 +1,424 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::prelude::*;
+use futures::future::join_all;
+use platz_db::{
+    DbEvent, DbEventOperation, DbEventReceiver, DbTable,
+    schema::{
+        deployment::Deployment,
+        deployment_task::{DeploymentTask, DeploymentTaskStatus},
+        k8s_cluster::K8sCluster,
+        webhook::{Webhook, WebhookEventType},
+        webhook_delivery::WebhookDelivery,
+        webhook_deployment_state::WebhookDeploymentState,
+    },
+};
+use reqwest::{header::CONTENT_TYPE, redirect};
+use serde::Serialize;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::{
+    net::lookup_host,
+    select,
+    sync::{Notify, broadcast::error::RecvError},
+    time,
+};
+use tracing::{debug, error, info, warn};
+use url::{Host, Url};
+use uuid::Uuid;
+
+const DELIVERY_BATCH_SIZE: i64 = 50;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_BASE_DELAY_SECS: i64 = 30;
+const RETRY_MAX_DELAY_SECS: i64 = 60 * 60;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The body sent to webhooks
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    event_type: WebhookEventType,
+    timestamp: DateTime<Utc>,
+    env_id: Uuid,
+    deployment: &'a Deployment,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task: Option<&'a DeploymentTask>,
+}
+
+/// Turns task and deployment DB events into webhook deliveries, and delivers
+/// them. Deliveries are queued in the database, so every API replica can run
+/// this without sending the same event twice. Finished deliveries are deleted
+/// once they're older than `retention`.
+#[tracing::instrument(err, skip_all, name = "webhooks")]
+pub async fn deliver_webhooks_task(
+    events_rx: DbEventReceiver,
+    delivery_interval: Duration,
+    max_attempts: i32,
+    retention: Duration,
+) -> Result<()> {
+    if max_attempts < 1 {
+        bail!("Webhook max attempts must be at least 1");
+    }
+    let retention = chrono::Duration::from_std(retention)
+        .context("Webhook delivery retention is out of range")?;
+    let enqueued = Notify::new();
+    select! {
+        result = enqueue_events(events_rx, &enqueued) => result,
+        result = deliver_due_loop(&enqueued, delivery_interval, max_attempts) => result,
+        result = prune_loop(retention) => result,
+    }
+}
+
+async fn enqueue_events(mut events_rx: DbEventReceiver, enqueued: &Notify) -> Result<()> {
+    loop {
+        let event = match events_rx.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(count)) => {
+                warn!("Missed {count} DB events, their webhooks won't be sent");
+                continue;
+            }
+            Err(RecvError::Closed) => bail!("DB events channel closed"),
+        };
+        match handle_event(event).await {
+            Ok(true) => enqueued.notify_one(),
+            Ok(false) => (),
+            Err(err) => error!("Failed enqueuing webhooks for {event:?}: {err:?}"),
+        }
+    }
+}
+
+/// Returns whether any deliveries were enqueued
+async fn handle_event(event: DbEvent) -> Result<bool> {
+    match (event.table, event.operation) {
+        (_, DbEventOperation::Delete) => Ok(false),
+        (DbTable::DeploymentTasks, _) => task_changed(event.data.id).await,
+        (DbTable::Deployments, operation) => {
+            deployment_changed(event.data.id, operation == DbEventOperation::Insert).await
+        }
+        _ => Ok(false),
+    }
+}
+
+async fn task_changed(task_id: Uuid) -> Result<bool> {
+    let task = DeploymentTask::find(task_id).await?;
+    let event_type = match task.status {
+        DeploymentTaskStatus::Pending => return Ok(false),
+        DeploymentTaskStatus::Started => WebhookEventType::TaskStarted,
+        DeploymentTaskStatus::Failed => WebhookEventType::TaskFailed,
+        DeploymentTaskStatus::Canceled => WebhookEventType::TaskCanceled,
+        DeploymentTaskStatus::Done => WebhookEventType::TaskDone,
+    };
+    let Some(deployment) = Deployment::find_optional(task.deployment_id).await? else {
+        return Ok(false);
+    };
+    // Tasks reach each status once, so their events are identified by it
+    let event_key = format!("task:{}:{}", task.id, task.status);
+    enqueue(&deployment, Some(&task), event_type, event_key).await
+}
+
+async fn deployment_changed(deployment_id: Uuid, inserted: bool) -> Result<bool> {
+    let Some(deployment) = Deployment::find_optional(deployment_id).await? else {
+        return Ok(false);
+    };
+    // Only one observer gets the change, so it's the only one enqueuing it
+    let Some(state) = WebhookDeploymentState::observe(&deployment).await? else {
+        return Ok(false);
+    };
+    let mut enqueued = false;
+    if inserted || state.status_changed(&deployment) {
+        let event_key = format!("deployment:{}:status:{}", deployment.id, Uuid::new_v4());
+        enqueued |= enqueue(
+            &deployment,
+            None,
+            WebhookEventType::DeploymentStatusChanged,
+            event_key,
+        )
+        .await?;
+    }
+    if state.turned_danger(&deployment) {
+        let event_key = format!("deployment:{}:danger:{}", deployment.id, Uuid::new_v4());
+        enqueued |= enqueue(
+            &deployment,
+            None,
+            WebhookEventType::DeploymentReportedDanger,
+            event_key,
+        )
+        .await?;
+    }
+    Ok(enqueued)
+}
+
+async fn enqueue(
+    deployment: &Deployment,
+    task: Option<&DeploymentTask>,
+    event_type: WebhookEventType,
+    event_key: String,
+) -> Result<bool> {
+    let Some(env_id) = K8sCluster::find(deployment.cluster_id).await?.env_id else {
+        return Ok(false);
+    };
+    let payload = serde_json::to_value(WebhookPayload {
+        event_type,
+        timestamp: Utc::now(),
+        env_id,
+        deployment,
+        task,
+    })?;
+    let mut enqueued = false;
+    for webhook in Webhook::all_subscribed(env_id, deployment.kind_id).await? {
+        if !webhook.wants(event_type) {
+            continue;
+        }
+        if let Some(delivery) =
+            WebhookDelivery::enqueue(webhook.id, event_type, event_key.clone(), payload.clone())
+                .await?
+        {
+            debug!(
+                "Enqueued {event_type} delivery {} to {}",
+                delivery.id, webhook.name
+            );
+            enqueued = true;
+        }
+    }
+    Ok(enqueued)
+}
+
+async fn deliver_due_loop(
+    enqueued: &Notify,
+    delivery_interval: Duration,
+    max_attempts: i32,
+) -> Result<()> {
+    let mut interval = time::interval(delivery_interval);
+    loop {
+        select! {
+            _ = interval.tick() => (),
+            _ = enqueued.notified() => (),
+        }
+        if let Err(err) = deliver_due(max_attempts).await {
+            error!("Failed delivering webhooks: {err:?}");
+        }
+    }
+}
+
+async fn prune_loop(retention: chrono::Duration) -> Result<()> {
+    let mut interval = time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match WebhookDelivery::delete_older_than(Utc::now() - retention).await {
+            Ok(0) => (),
+            Ok(deleted) => info!("Deleted {deleted} old webhook deliveries"),
+            Err(err) => error!("Failed deleting old webhook deliveries: {err:?}"),
+        }
+    }
+}
+
+async fn deliver_due(max_attempts: i32) -> Result<()> {
+    let lease_until = Utc::now() + chrono::Duration::from_std(REQUEST_TIMEOUT * 2)?;
+    let mut claimed = Vec::new();
+    for delivery in WebhookDelivery::all_due(DELIVERY_BATCH_SIZE).await? {
+        if let Some(delivery) = delivery.claim(lease_until).await? {
+            claimed.push(delivery);
+        }
+    }
+    for result in join_all(
+        claimed
+            .into_iter()
+            .map(|delivery| deliver(delivery, max_attempts)),
+    )
+    .await
+    {
+        if let Err(err) = result {
+            error!("{err:?}");
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument(err, skip_all, fields(delivery=%delivery.id))]
+async fn deliver(delivery: WebhookDelivery, max_attempts: i32) -> Result<()> {
+    let webhook = Webhook::find(delivery.webhook_id).await?;
+    let (response_status, error) = if !webhook.enabled {
+        (None, "Webhook is disabled".to_owned())
+    } else {
+        match public_client(&webhook.url).await {
+            Err(err) => (None, format!("{err:#}")),
+            Ok(client) => {
+                let body = serde_json::to_vec(&delivery.payload)?;
+                let timestamp = Utc::now().timestamp();
+                let signature = webhook.signature(timestamp, &body);
+                match client
+                    .post(&webhook.url)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header("X-Platz-Event", &delivery.event_type)
+                    .header("X-Platz-Delivery", delivery.id.to_string())
+                    .header("X-Platz-Timestamp", timestamp.to_string())
+                    .header("X-Platz-Signature", signature)
+                    .body(body)
+                    .send()
+                    .await
+                {
+                    Ok(response) if response.status().is_success() => {
+                        delivery
+                            .set_delivered(response.status().as_u16().into())
+                            .await?;
+                        return Ok(());
+                    }
+                    Ok(response) => (
+                        Some(response.status().as_u16().into()),
+                        format!("Webhook responded with {}", response.status()),
+                    ),
+                    Err(err) => (None, err.to_string()),
+                }
+            }
+        }
+    };
+
+    let attempts = delivery.attempts + 1;
+    let retry_at = (webhook.enabled && attempts < max_attempts)
+        .then(|| Utc::now() + chrono::Duration::seconds(retry_delay_secs(attempts)));
+    if retry_at.is_none() {
+        warn!("Giving up on delivery after {attempts} attempts: {error}");
+    }
+    delivery
+        .set_failed(response_status, error, retry_at)
+        .await?;
+    Ok(())
+}
+
+/// Builds a client for delivering to a webhook URL, pinned to the addresses
+/// its host resolves to right now, after checking they're all public. This
+/// keeps webhooks from reaching internal services, including through DNS
+/// records changing between the check and the request, or through redirects.
+async fn public_client(url: &str) -> Result<reqwest::Client> {
+    let url = Url::parse(url)?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("Webhook URL has no port"))?;
+    let builder = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(redirect::Policy::none());
+    let builder = match url.host() {
+        Some(Host::Ipv4(ip)) => {
+            check_public(IpAddr::V4(ip))?;
+            builder
+        }
+        Some(Host::Ipv6(ip)) => {
+            check_public(IpAddr::V6(ip))?;
+            builder
+        }
+        Some(Host::Domain(domain)) => {
+            let addrs = lookup_host((domain, port))
+                .await
+                .with_context(|| format!("Failed resolving {domain}"))?
+                .collect::<Vec<SocketAddr>>();
+            if addrs.is_empty() {
+                bail!("{domain} doesn't resolve to any address");
+            }
+            for addr in addrs.iter() {
+                check_public(addr.ip())?;
+            }
+            builder.resolve_to_addrs(domain, &addrs)
+        }
+        None => bail!("Webhook URL has no host"),
+    };
+    Ok(builder.build()?)
+}
+
+fn check_public(ip: IpAddr) -> Result<()> {
+    if is_public_ip(ip) {
+        Ok(())
+    } else {
+        bail!("{ip} is not a public address")
+    }
+}
+
+/// Whether an address is routable on the internet, as opposed to loopback,
+/// private, link-local and other special-purpose ranges
+pub(crate) fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "This network", shared address space (carrier-grade NAT),
+        // benchmarking and reserved ranges
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        || (a == 198 && (b & 0xfe) == 18)
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let [first, second, ..] = ip.segments();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, link-local and deprecated site-local ranges
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0
+        // Documentation
+        || (first == 0x2001 && second == 0x0db8)
+        // NAT64 and IPv4-compatible addresses embed an IPv4 address
+        || (first == 0x0064 && second == 0xff9b)
+        || ip.segments()[..6] == [0; 6])
+}
+
+/// Exponential backoff, doubling the delay after each failed attempt
+fn retry_delay_secs(attempts: i32) -> i64 {
+    RETRY_BASE_DELAY_SECS
+        .saturating_mul(1 << (attempts - 1).clamp(0, 20))
+        .min(RETRY_MAX_DELAY_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in ["93.184.215.14", "2606:4700::1111", "::ffff:93.184.215.14"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_retry_delay_secs() {
+        assert_eq!(retry_delay_secs(1), 30);
+        assert_eq!(retry_delay_secs(2), 60);
+        assert_eq!(retry_delay_secs(4), 240);
+        assert_eq!(retry_delay_secs(8), RETRY_MAX_DELAY_SECS);
+        assert_eq!(retry_delay_secs(100), RETRY_MAX_DELAY_SECS);
+    }
+}
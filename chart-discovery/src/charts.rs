@@ -1,4 +1,5 @@
 use crate::ecr_events::{EcrEvent, EcrEventDetail};
+use crate::metrics::CHARTS_DISCOVERED;
 use crate::tag_parser::parse_image_tag;
 use anyhow::{Result, anyhow};
 use aws_smithy_types_convert::date_time::DateTimeExt;
@@ -117,6 +118,7 @@ pub async fn record_helm_chart(
     .await?;
 
     info!("Added helm chart {:?}", chart);
+    CHARTS_DISCOVERED.inc();
     Ok(())
 }
 
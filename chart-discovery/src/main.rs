@@ -10,6 +10,7 @@ use tracing::{info, warn};
 mod charts;
 mod ecr_events;
 mod kind;
+mod metrics;
 mod oci_poll;
 mod registries;
 mod sqs;
@@ -50,7 +51,7 @@ async fn main() -> Result<()> {
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .expect("Failed installing default crypto provider");
-    platz_otel::init()?;
+    let _otel = platz_otel::init(env!("CARGO_PKG_NAME"))?;
     let config = Config::parse();
     let mut sigterm = signal(SignalKind::terminate())?;
     let mut sigint = signal(SignalKind::interrupt())?;
@@ -98,6 +99,11 @@ async fn main() -> Result<()> {
             result.map_err(Into::into)
         }
 
+        result = platz_otel::server::serve() => {
+            warn!("Health and metrics server finished: {result:?}");
+            result
+        }
+
         result = provider_fut => {
             result
         }
@@ -0,0 +1,10 @@
+use lazy_static::lazy_static;
+use prometheus::{IntCounter, register_int_counter};
+
+lazy_static! {
+    pub static ref CHARTS_DISCOVERED: IntCounter = register_int_counter!(
+        "platz_charts_discovered_total",
+        "Number of Helm charts discovered and added"
+    )
+    .unwrap();
+}
@@ -6,13 +6,18 @@ use clap::Parser;
 use platz_chart_ext::ChartExt;
 use platz_db::schema::helm_chart::HelmChart;
 use platz_db::schema::helm_registry::{HelmRegistry, HelmRegistryProvider, NewHelmRegistry};
+use platz_otel::health;
 use serde::Deserialize;
 use std::collections::HashSet;
+use std::time::Duration;
 use tokio::time;
 use tracing::{debug, info, warn};
 use url::Url;
 use uuid::Uuid;
 
+/// How long a poll may take before the poll loop is considered wedged
+const POLL_HEARTBEAT_GRACE: Duration = Duration::from_secs(10 * 60);
+
 #[derive(Debug, Parser)]
 #[group(skip)]
 pub struct Config {
@@ -35,14 +40,20 @@ pub async fn run(config: &Config) -> Result<()> {
     let client = reqwest::Client::builder().build()?;
 
     info!("Polling OCI registry at {url}");
+    health::set_ready("oci_poll", Err("Registry not polled yet".to_owned()));
 
     let mut interval = time::interval(*config.oci_poll_interval);
     let mut seen_pairs: HashSet<(String, String)> = HashSet::new();
 
     loop {
         interval.tick().await;
-        if let Err(err) = poll_once(&client, &url, &domain, &mut seen_pairs).await {
-            warn!("OCI poll iteration failed: {err:?}");
+        health::heartbeat("oci_poll", *config.oci_poll_interval + POLL_HEARTBEAT_GRACE);
+        match poll_once(&client, &url, &domain, &mut seen_pairs).await {
+            Ok(()) => health::set_ready("oci_poll", Ok(())),
+            Err(err) => {
+                warn!("OCI poll iteration failed: {err:?}");
+                health::set_ready("oci_poll", Err(err.to_string()));
+            }
         }
     }
 }
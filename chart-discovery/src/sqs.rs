@@ -1,9 +1,14 @@
 use anyhow::{Result, anyhow};
 use aws_types::region::Region;
+use platz_otel::health;
 use serde::de::DeserializeOwned;
-use std::future::Future;
+use std::{future::Future, time::Duration};
 use tracing::debug;
 
+/// How long receiving and handling a batch of messages may take before the
+/// receive loop is considered wedged
+const RECEIVE_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
 /// Listen for messages from an SQS queue, invoking the given
 /// function for every message. If the function returns a successful
 /// Result, the message is deleted from the queue.
@@ -22,15 +27,18 @@ where
         .region(queue_region)
         .build();
     let sqs = aws_sdk_sqs::Client::from_conf(sqs_config);
+    health::set_ready("sqs", Err("Queue not polled yet".to_owned()));
     let queue_url = get_queue_url(&sqs, queue_name).await?;
 
     loop {
+        health::heartbeat("sqs", RECEIVE_HEARTBEAT_TIMEOUT);
         let res = sqs
             .receive_message()
             .queue_url(queue_url.clone())
             .wait_time_seconds(20)
             .send()
             .await?;
+        health::set_ready("sqs", Ok(()));
         if let Some(messages) = res.messages {
             for message in messages.into_iter() {
                 debug!("Handling message {}", message.message_id.as_ref().unwrap());
@@ -0,0 +1 @@
+alter table k8s_resources drop column status;
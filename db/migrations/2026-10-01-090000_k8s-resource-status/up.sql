@@ -0,0 +1,3 @@
+-- Structured status of tracked Kubernetes resources: conditions, container
+-- images, replica counts and pod details. Filled in by the k8s-agent tracker.
+alter table k8s_resources add column status jsonb not null default '{}';
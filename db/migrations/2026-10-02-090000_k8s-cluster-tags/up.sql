@@ -0,0 +1,2 @@
+-- Tags reported by the cluster provider (e.g. EKS cluster tags)
+alter table k8s_clusters add column tags jsonb not null default '{}';
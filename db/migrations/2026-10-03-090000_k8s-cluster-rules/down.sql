@@ -0,0 +1,3 @@
+drop table k8s_cluster_rules;
+
+alter table k8s_clusters drop column provider;
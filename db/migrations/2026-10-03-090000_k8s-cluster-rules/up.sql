@@ -0,0 +1,30 @@
+-- The provider that discovered each cluster, so rules can match on it
+alter table k8s_clusters add column provider varchar not null default '';
+
+update k8s_clusters set provider = case
+  when provider_id like 'arn:aws:eks:%' then 'eks'
+  when provider_id like 'local:%' then 'local'
+  else 'static'
+end;
+
+-- Admin-defined rules applied to newly discovered clusters. The first
+-- matching rule (by priority, then creation time) sets the cluster's env,
+-- ingress settings and ignore flag.
+create table k8s_cluster_rules(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  name varchar not null,
+  priority integer not null default 0,
+  match_name_pattern varchar,
+  match_region_name varchar,
+  match_provider varchar,
+  match_tags jsonb not null default '{}',
+  env_id uuid references envs(id) on delete cascade,
+  ingress_domain varchar,
+  ingress_class varchar,
+  ingress_tls_secret_name varchar,
+  ignore boolean
+);
+
+create trigger notify_changes after insert or update or delete on k8s_cluster_rules
+for each row execute procedure notify_trigger('id');
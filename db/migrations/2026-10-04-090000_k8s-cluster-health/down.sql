@@ -0,0 +1 @@
+drop table k8s_cluster_health;
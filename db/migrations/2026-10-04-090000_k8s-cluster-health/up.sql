@@ -0,0 +1,22 @@
+-- Periodic health and capacity samples collected by the k8s-agent for each
+-- tracked cluster. Old samples are pruned by the agent.
+create table k8s_cluster_health(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  cluster_id uuid not null references k8s_clusters(id) on delete cascade,
+  k8s_version varchar,
+  node_count integer,
+  ready_node_count integer,
+  allocatable_cpu_millis bigint,
+  allocatable_memory_bytes bigint,
+  requested_cpu_millis bigint,
+  requested_memory_bytes bigint,
+  missing_permissions varchar[] not null default '{}',
+  errors varchar[] not null default '{}'
+);
+
+create index k8s_cluster_health_cluster_id_created_at
+  on k8s_cluster_health(cluster_id, created_at desc);
+
+create trigger notify_changes after insert or update or delete on k8s_cluster_health
+for each row execute procedure notify_trigger('id');
@@ -0,0 +1,3 @@
+drop table k8s_cluster_drains;
+
+alter table k8s_clusters drop column cordoned;
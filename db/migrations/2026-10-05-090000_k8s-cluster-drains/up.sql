@@ -0,0 +1,26 @@
+-- Cordoned clusters don't accept new deployments (or deployments moved into them)
+alter table k8s_clusters add column cordoned boolean not null default false;
+
+-- Migrations of all deployments of a cluster into another cluster in the
+-- same env, run by the k8s-agent in batches.
+create table k8s_cluster_drains(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  finished_at timestamptz,
+  created_by_user_id uuid not null references users(id),
+  cluster_id uuid not null references k8s_clusters(id) on delete cascade,
+  target_cluster_id uuid not null references k8s_clusters(id) on delete cascade,
+  batch_size integer not null,
+  status varchar not null default 'Running',
+  reason varchar,
+  total_deployments integer not null,
+  migrated_deployment_ids uuid[] not null default '{}'
+);
+
+-- Only one drain can run on a cluster at a time
+create unique index k8s_cluster_drains_running_cluster_id
+  on k8s_cluster_drains(cluster_id)
+  where status = 'Running';
+
+create trigger notify_changes after insert or update or delete on k8s_cluster_drains
+for each row execute procedure notify_trigger('id');
@@ -0,0 +1 @@
+alter table k8s_clusters drop column missing_since;
@@ -0,0 +1,3 @@
+-- Set when a cluster stops showing up in cluster discovery, cleared when it's
+-- discovered again
+alter table k8s_clusters add column missing_since timestamptz;
@@ -0,0 +1 @@
+drop table deployment_env_grants;
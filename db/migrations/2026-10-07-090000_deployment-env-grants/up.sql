@@ -0,0 +1,15 @@
+-- Deployment identities may only access their own env. Each row allows the
+-- deployments of a kind in the source env to also access the target env.
+create table deployment_env_grants(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  kind_id uuid not null references deployment_kinds(id) on delete cascade,
+  source_env_id uuid not null references envs(id) on delete cascade,
+  target_env_id uuid not null references envs(id) on delete cascade
+);
+
+create unique index deployment_env_grants_kind_source_target
+  on deployment_env_grants(kind_id, source_env_id, target_env_id);
+
+create trigger notify_changes after insert or update or delete on deployment_env_grants
+for each row execute procedure notify_trigger('id');
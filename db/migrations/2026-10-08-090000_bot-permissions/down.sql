@@ -0,0 +1,4 @@
+drop table deployment_bot_permissions;
+drop table env_bot_permissions;
+
+alter table bots drop column unrestricted;
@@ -0,0 +1,35 @@
+-- Unrestricted bots can see every env and maintain every deployment. Bots
+-- created before role bindings existed keep that access.
+alter table bots add column unrestricted boolean not null default false;
+update bots set unrestricted = true;
+
+-- Env roles of bots, like env_user_permissions
+create table env_bot_permissions(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  env_id uuid not null references envs(id) on delete cascade,
+  bot_id uuid not null references bots(id) on delete cascade,
+  role varchar not null
+);
+
+create unique index env_bot_permissions_env_bot
+  on env_bot_permissions(env_id, bot_id);
+
+create trigger notify_changes after insert or update or delete on env_bot_permissions
+for each row execute procedure notify_trigger('id');
+
+-- Per-kind deployment roles of bots, like deployment_permissions
+create table deployment_bot_permissions(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  env_id uuid not null references envs(id) on delete cascade,
+  bot_id uuid not null references bots(id) on delete cascade,
+  kind_id uuid not null references deployment_kinds(id) on delete cascade,
+  role varchar not null
+);
+
+create unique index deployment_bot_permissions_env_bot_kind
+  on deployment_bot_permissions(env_id, bot_id, kind_id);
+
+create trigger notify_changes after insert or update or delete on deployment_bot_permissions
+for each row execute procedure notify_trigger('id');
@@ -0,0 +1,13 @@
+delete from deployment_bot_permissions where role in ('Custom', 'Viewer');
+alter table deployment_bot_permissions drop column custom_role_id;
+
+delete from deployment_permissions where role in ('Custom', 'Viewer');
+alter table deployment_permissions drop column custom_role_id;
+
+delete from env_bot_permissions where role = 'Custom';
+alter table env_bot_permissions drop column custom_role_id;
+
+delete from env_user_permissions where role = 'Custom';
+alter table env_user_permissions drop column custom_role_id;
+
+drop table custom_roles;
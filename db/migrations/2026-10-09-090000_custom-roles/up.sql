@@ -0,0 +1,31 @@
+-- Named roles made of explicit permissions, bound like the built-in roles by
+-- setting role = 'Custom' and custom_role_id on a permission row.
+create table custom_roles(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  name varchar not null unique,
+  permissions jsonb not null default '[]'
+);
+
+create trigger notify_changes after insert or update or delete on custom_roles
+for each row execute procedure notify_trigger('id');
+
+alter table env_user_permissions
+  add column custom_role_id uuid references custom_roles(id) on delete cascade,
+  add constraint env_user_permissions_custom_role
+    check ((role = 'Custom') = (custom_role_id is not null));
+
+alter table env_bot_permissions
+  add column custom_role_id uuid references custom_roles(id) on delete cascade,
+  add constraint env_bot_permissions_custom_role
+    check ((role = 'Custom') = (custom_role_id is not null));
+
+alter table deployment_permissions
+  add column custom_role_id uuid references custom_roles(id) on delete cascade,
+  add constraint deployment_permissions_custom_role
+    check ((role = 'Custom') = (custom_role_id is not null));
+
+alter table deployment_bot_permissions
+  add column custom_role_id uuid references custom_roles(id) on delete cascade,
+  add constraint deployment_bot_permissions_custom_role
+    check ((role = 'Custom') = (custom_role_id is not null));
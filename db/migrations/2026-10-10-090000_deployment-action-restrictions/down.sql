@@ -0,0 +1 @@
+drop table deployment_action_restrictions;
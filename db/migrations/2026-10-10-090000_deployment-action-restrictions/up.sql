@@ -0,0 +1,20 @@
+-- Restricts invoking a chart action on deployments of a kind in an env to
+-- the listed roles, users and bots. Actions without a restriction can be
+-- invoked by anyone with the invoke-action permission.
+create table deployment_action_restrictions(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  env_id uuid not null references envs(id) on delete cascade,
+  kind_id uuid not null references deployment_kinds(id) on delete cascade,
+  action_id varchar not null,
+  allowed_roles jsonb not null default '[]',
+  allowed_custom_role_ids uuid[] not null default '{}',
+  allowed_user_ids uuid[] not null default '{}',
+  allowed_bot_ids uuid[] not null default '{}'
+);
+
+create unique index deployment_action_restrictions_env_kind_action
+  on deployment_action_restrictions(env_id, kind_id, action_id);
+
+create trigger notify_changes after insert or update or delete on deployment_action_restrictions
+for each row execute procedure notify_trigger('id');
@@ -0,0 +1,11 @@
+drop index deployment_bot_permissions_bot_deployment;
+drop index deployment_bot_permissions_env_bot_kind;
+
+delete from deployment_bot_permissions where deployment_id is not null;
+alter table deployment_bot_permissions drop column deployment_id;
+
+create unique index deployment_bot_permissions_env_bot_kind
+  on deployment_bot_permissions(env_id, bot_id, kind_id);
+
+delete from deployment_permissions where deployment_id is not null;
+alter table deployment_permissions drop column deployment_id;
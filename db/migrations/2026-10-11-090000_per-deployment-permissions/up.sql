@@ -0,0 +1,18 @@
+-- Deployment permissions with a deployment_id only apply to that deployment,
+-- instead of all deployments of the kind in the env.
+alter table deployment_permissions
+  add column deployment_id uuid references deployments(id) on delete cascade;
+
+alter table deployment_bot_permissions
+  add column deployment_id uuid references deployments(id) on delete cascade;
+
+-- A bot may now have both a kind-wide role and per-deployment roles
+drop index deployment_bot_permissions_env_bot_kind;
+
+create unique index deployment_bot_permissions_env_bot_kind
+  on deployment_bot_permissions(env_id, bot_id, kind_id)
+  where deployment_id is null;
+
+create unique index deployment_bot_permissions_bot_deployment
+  on deployment_bot_permissions(bot_id, deployment_id)
+  where deployment_id is not null;
@@ -0,0 +1,11 @@
+drop table access_requests;
+
+drop index env_user_role_env_user;
+delete from env_user_permissions where expires_at is not null;
+
+create unique index env_user_role_env_user
+  on env_user_permissions(env_id, user_id);
+
+delete from deployment_permissions where expires_at is not null;
+alter table deployment_permissions drop column expires_at;
+alter table env_user_permissions drop column expires_at;
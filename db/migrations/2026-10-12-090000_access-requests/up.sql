@@ -0,0 +1,44 @@
+-- Temporary grants: permissions with an expires_at are ignored once expired,
+-- and deleted by the API's expiry job.
+alter table env_user_permissions add column expires_at timestamptz;
+alter table deployment_permissions add column expires_at timestamptz;
+
+-- A user may hold temporary env roles on top of their permanent one
+drop index env_user_role_env_user;
+
+create unique index env_user_role_env_user
+  on env_user_permissions(env_id, user_id)
+  where expires_at is null;
+
+-- Requests by users for temporary elevated access. Approving a request creates
+-- the permission with an expires_at, and the request is kept as an audit
+-- record of who asked, who reviewed and when the grant expired.
+create table access_requests(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  user_id uuid not null references users(id) on delete cascade,
+  env_id uuid not null references envs(id) on delete cascade,
+  kind_id uuid references deployment_kinds(id) on delete cascade,
+  deployment_id uuid references deployments(id) on delete cascade,
+  env_role varchar,
+  deployment_role varchar,
+  custom_role_id uuid references custom_roles(id) on delete cascade,
+  duration_secs integer not null,
+  justification varchar not null,
+  status varchar not null default 'Pending',
+  reviewed_by_user_id uuid references users(id) on delete set null,
+  reviewed_at timestamptz,
+  review_comment varchar,
+  expires_at timestamptz,
+  revoked_at timestamptz,
+  constraint access_requests_role
+    check ((kind_id is null) = (env_role is not null)
+      and (kind_id is null) = (deployment_role is null)
+      and (deployment_id is null or kind_id is not null))
+);
+
+create index access_requests_status_expires_at
+  on access_requests(status, expires_at);
+
+create trigger notify_changes after insert or update or delete on access_requests
+for each row execute procedure notify_trigger('id');
@@ -0,0 +1,10 @@
+-- Dropping the data keys makes encrypted values unreadable, only downgrade
+-- if encryption was never enabled.
+alter table deployment_resources
+  drop column key_id,
+  drop column data_key,
+  drop column sensitive_props;
+
+alter table secrets
+  drop column key_id,
+  drop column data_key;
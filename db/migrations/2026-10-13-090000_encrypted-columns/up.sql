@@ -0,0 +1,13 @@
+-- Envelope encryption: when key_id is set, the value is encrypted with a data
+-- key, stored in data_key wrapped with the master key identified by key_id.
+-- Rows without a key_id are plaintext.
+alter table secrets
+  add column data_key varchar,
+  add column key_id varchar;
+
+-- Sensitive props of deployment resources are moved out of props into
+-- sensitive_props, encrypted as a single JSON object.
+alter table deployment_resources
+  add column sensitive_props varchar,
+  add column data_key varchar,
+  add column key_id varchar;
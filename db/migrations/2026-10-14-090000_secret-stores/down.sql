@@ -0,0 +1,5 @@
+alter table secrets
+  drop constraint secrets_store_reference,
+  drop column store,
+  drop column store_path,
+  drop column store_key;
@@ -0,0 +1,10 @@
+-- Secrets can reference a value kept in an external secret store instead of
+-- holding their contents. Postgres secrets keep their contents inline, other
+-- stores only keep the path and key of the value, which is read at install time.
+alter table secrets
+  add column store varchar not null default 'Postgres',
+  add column store_path varchar,
+  add column store_key varchar,
+  add constraint secrets_store_reference check (
+    (store = 'Postgres') = (store_path is null and store_key is null)
+  );
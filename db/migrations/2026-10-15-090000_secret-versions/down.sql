@@ -0,0 +1,5 @@
+drop table secret_versions;
+
+alter table secrets
+  drop column write_only,
+  drop column fingerprint;
@@ -0,0 +1,30 @@
+-- The fingerprint is an HMAC of a secret's contents keyed with the master key,
+-- letting users tell values apart without reading them. It's computed by the
+-- API, existing secrets get theirs when loaded.
+alter table secrets
+  add column write_only boolean not null default false,
+  add column fingerprint varchar;
+
+-- Every value a secret had, including the current one, along with who set it.
+-- Values are encrypted at rest the same way secrets are.
+create table secret_versions(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  secret_id uuid not null references secrets(id) on delete cascade,
+  contents varchar not null,
+  data_key varchar,
+  key_id varchar,
+  fingerprint varchar,
+  store_path varchar,
+  store_key varchar,
+  acting_user_id uuid references users(id) on delete set null,
+  acting_bot_id uuid references bots(id) on delete set null,
+  acting_deployment_id uuid references deployments(id) on delete set null,
+  restored_from_version_id uuid references secret_versions(id) on delete set null
+);
+
+create index secret_versions_secret_id_created_at
+  on secret_versions(secret_id, created_at);
+
+create trigger notify_changes after insert or update or delete on secret_versions
+for each row execute procedure notify_trigger('id');
@@ -0,0 +1,2 @@
+alter table secrets
+  drop column secret_type;
@@ -0,0 +1,4 @@
+-- DockerConfigJson secrets hold registry credentials, created as image pull
+-- secrets in every deployment namespace of their env.
+alter table secrets
+  add column secret_type varchar not null default 'Opaque';
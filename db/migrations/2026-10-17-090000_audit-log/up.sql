@@ -0,0 +1,31 @@
+-- A record of every mutating API request. Identities and targets aren't
+-- foreign keys so entries outlive what they refer to.
+create table audit_log(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  acting_user_id uuid,
+  acting_bot_id uuid,
+  acting_deployment_id uuid,
+  method varchar not null,
+  action varchar not null,
+  path varchar not null,
+  status_code integer not null,
+  target_table varchar,
+  target_id uuid,
+  diff jsonb,
+  source_ip varchar,
+  user_agent varchar
+);
+
+create index audit_log_created_at on audit_log(created_at);
+create index audit_log_target on audit_log(target_table, target_id);
+create index audit_log_acting_user_id on audit_log(acting_user_id);
+
+create function audit_log_append_only() returns trigger as $$
+begin
+  raise exception 'audit_log is append-only';
+end;
+$$ language plpgsql;
+
+create trigger audit_log_append_only before update or delete on audit_log
+for each row execute procedure audit_log_append_only();
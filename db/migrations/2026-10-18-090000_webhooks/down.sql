@@ -0,0 +1,3 @@
+drop table webhook_deployment_states;
+drop table webhook_deliveries;
+drop table webhooks;
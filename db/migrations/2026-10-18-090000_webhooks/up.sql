@@ -0,0 +1,57 @@
+-- Outbound webhooks, notified of task and deployment events in an env,
+-- optionally limited to a deployment kind. An empty event_types means all
+-- events. The signing secret is encrypted at rest the same way secrets are.
+create table webhooks(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  env_id uuid not null references envs(id) on delete cascade,
+  kind_id uuid references deployment_kinds(id) on delete cascade,
+  name varchar not null,
+  url varchar not null,
+  event_types varchar[] not null default '{}',
+  enabled boolean not null default true,
+  signing_secret varchar not null,
+  data_key varchar,
+  key_id varchar
+);
+
+create index webhooks_env_id on webhooks(env_id);
+
+create trigger notify_changes after insert or update or delete on webhooks
+for each row execute procedure notify_trigger('id');
+
+-- Every event sent to a webhook, along with its delivery attempts. Failed
+-- deliveries are retried until they run out of attempts, and are then kept
+-- as dead letters. The event key makes enqueuing the same event twice a no-op.
+create table webhook_deliveries(
+  id uuid primary key default uuid_generate_v4(),
+  created_at timestamptz not null default now(),
+  webhook_id uuid not null references webhooks(id) on delete cascade,
+  event_type varchar not null,
+  event_key varchar not null,
+  payload jsonb not null,
+  status varchar not null default 'Pending',
+  attempts integer not null default 0,
+  next_attempt_at timestamptz not null default now(),
+  last_attempted_at timestamptz,
+  delivered_at timestamptz,
+  response_status integer,
+  error varchar
+);
+
+create unique index webhook_deliveries_webhook_id_event_key
+  on webhook_deliveries(webhook_id, event_key);
+create index webhook_deliveries_status_next_attempt_at
+  on webhook_deliveries(status, next_attempt_at);
+
+create trigger notify_changes after insert or update or delete on webhook_deliveries
+for each row execute procedure notify_trigger('id');
+
+-- The last deployment state webhooks were notified of, so that status changes
+-- are only sent once no matter how many API replicas see them.
+create table webhook_deployment_states(
+  deployment_id uuid primary key references deployments(id) on delete cascade,
+  updated_at timestamptz not null default now(),
+  status varchar not null,
+  reported_danger boolean not null
+);
@@ -0,0 +1,2 @@
+alter table deployment_tasks
+  drop column traceparent;
@@ -0,0 +1,4 @@
+-- The W3C trace context of the request that created the task, continued by
+-- the agent running it.
+alter table deployment_tasks
+  add column traceparent varchar;
@@ -0,0 +1 @@
+alter table deployments drop column status_before_orphaned;
@@ -0,0 +1,3 @@
+-- The status a deployment had before its cluster went missing, restored when
+-- the cluster is discovered again.
+alter table deployments add column status_before_orphaned varchar;
@@ -9,7 +9,18 @@
 
 use crate::{
     DbEvent, DbResult, DbTable, Identity, db_conn,
-    schema::{env_user_permission::env_user_permissions, user::users},
+    schema::{
+        bot::bots,
+        custom_role::{CustomRole, RolePermission},
+        deployment::deployments,
+        deployment_bot_permission::deployment_bot_permissions,
+        deployment_env_grant::DeploymentEnvGrant,
+        deployment_permission::{UserDeploymentRole, deployment_permissions},
+        env_bot_permission::env_bot_permissions,
+        env_user_permission::{EnvUserRole, env_user_permissions},
+        k8s_cluster::k8s_clusters,
+        user::users,
+    },
 };
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
@@ -19,16 +30,22 @@ use uuid::Uuid;
 /// The set of environments an identity is allowed to access.
 #[derive(Debug, Clone)]
 pub enum AccessScope {
-    /// Unrestricted access. Site admins and the service identities (bots and
-    /// in-cluster deployments) see everything.
+    /// Unrestricted access. Site admins and unrestricted bots see everything.
     All,
-    /// A regular user, restricted to the environments they have any permission
-    /// in. May be empty, in which case the identity sees nothing.
-    Envs(Vec<Uuid>),
+    /// A regular user or bot, restricted to the environments they have a role
+    /// with the read permission in, or an in-cluster deployment, restricted to
+    /// its own environment and the ones granted to its kind. May be empty, in
+    /// which case the identity sees nothing.
+    Envs {
+        env_ids: Vec<Uuid>,
+        /// Deployments (and their tasks and resources) visible in addition to
+        /// everything in `env_ids`, granted through per-deployment permissions
+        deployment_ids: Vec<Uuid>,
+    },
 }
 
 impl AccessScope {
-    /// Resolve the scope for an identity with a single small, indexed query.
+    /// Resolve the scope for an identity with a few small, indexed queries.
     pub async fn for_identity(identity: &Identity) -> DbResult<Self> {
         match identity {
             Identity::User(user_id) => {
@@ -42,16 +59,144 @@ impl AccessScope {
                 if is_admin {
                     return Ok(Self::All);
                 }
-                let env_ids = env_user_permissions::table
+                // Users see envs where they have an env role or a deployment
+                // role, as long as the role includes the read permission
+                let read_role_ids = CustomRole::ids_with_permission(RolePermission::Read).await?;
+                let mut env_ids = env_user_permissions::table
                     .filter(env_user_permissions::user_id.eq(user_id))
+                    .filter(
+                        env_user_permissions::role
+                            .ne(EnvUserRole::Custom)
+                            .or(env_user_permissions::custom_role_id.eq_any(read_role_ids.clone())),
+                    )
+                    .filter(
+                        env_user_permissions::expires_at
+                            .is_null()
+                            .or(env_user_permissions::expires_at.gt(diesel::dsl::now)),
+                    )
                     .select(env_user_permissions::env_id)
                     .get_results::<Uuid>(db_conn().await?.deref_mut())
                     .await?;
-                Ok(Self::Envs(env_ids))
+                // Kind-wide deployment roles make the whole env visible, while
+                // per-deployment roles only make their deployment visible
+                let mut deployment_ids = Vec::new();
+                for (env_id, deployment_id) in deployment_permissions::table
+                    .filter(deployment_permissions::user_id.eq(user_id))
+                    .filter(
+                        deployment_permissions::role
+                            .ne(UserDeploymentRole::Custom)
+                            .or(deployment_permissions::custom_role_id.eq_any(read_role_ids)),
+                    )
+                    .filter(
+                        deployment_permissions::expires_at
+                            .is_null()
+                            .or(deployment_permissions::expires_at.gt(diesel::dsl::now)),
+                    )
+                    .select((
+                        deployment_permissions::env_id,
+                        deployment_permissions::deployment_id,
+                    ))
+                    .get_results::<(Uuid, Option<Uuid>)>(db_conn().await?.deref_mut())
+                    .await?
+                {
+                    match deployment_id {
+                        Some(deployment_id) => deployment_ids.push(deployment_id),
+                        None => env_ids.push(env_id),
+                    }
+                }
+                Ok(Self::from_ids(env_ids, deployment_ids))
+            }
+            // Deployments are limited to the env of their cluster, plus any
+            // envs explicitly granted to their kind from that env. A deployment
+            // whose cluster isn't attached to an env sees nothing.
+            Identity::Deployment(deployment_id) => {
+                let Some((cluster_id, kind_id)) = deployments::table
+                    .find(deployment_id)
+                    .select((deployments::cluster_id, deployments::kind_id))
+                    .get_result::<(Uuid, Uuid)>(db_conn().await?.deref_mut())
+                    .await
+                    .optional()?
+                else {
+                    return Ok(Self::envs(Vec::new()));
+                };
+                let Some(env_id) = k8s_clusters::table
+                    .find(cluster_id)
+                    .select(k8s_clusters::env_id)
+                    .get_result::<Option<Uuid>>(db_conn().await?.deref_mut())
+                    .await
+                    .optional()?
+                    .flatten()
+                else {
+                    return Ok(Self::envs(Vec::new()));
+                };
+                let mut env_ids = DeploymentEnvGrant::target_env_ids(kind_id, env_id).await?;
+                env_ids.push(env_id);
+                Ok(Self::envs(env_ids))
+            }
+            // Bots are restricted to the envs they have an env role or a
+            // deployment role in, same as users, unless marked unrestricted.
+            Identity::Bot(bot_id) => {
+                let unrestricted = bots::table
+                    .find(bot_id)
+                    .select(bots::unrestricted)
+                    .get_result::<bool>(db_conn().await?.deref_mut())
+                    .await
+                    .optional()?
+                    .unwrap_or(false);
+                if unrestricted {
+                    return Ok(Self::All);
+                }
+                let read_role_ids = CustomRole::ids_with_permission(RolePermission::Read).await?;
+                let mut env_ids = env_bot_permissions::table
+                    .filter(env_bot_permissions::bot_id.eq(bot_id))
+                    .filter(
+                        env_bot_permissions::role
+                            .ne(EnvUserRole::Custom)
+                            .or(env_bot_permissions::custom_role_id.eq_any(read_role_ids.clone())),
+                    )
+                    .select(env_bot_permissions::env_id)
+                    .get_results::<Uuid>(db_conn().await?.deref_mut())
+                    .await?;
+                let mut deployment_ids = Vec::new();
+                for (env_id, deployment_id) in deployment_bot_permissions::table
+                    .filter(deployment_bot_permissions::bot_id.eq(bot_id))
+                    .filter(
+                        deployment_bot_permissions::role
+                            .ne(UserDeploymentRole::Custom)
+                            .or(deployment_bot_permissions::custom_role_id.eq_any(read_role_ids)),
+                    )
+                    .select((
+                        deployment_bot_permissions::env_id,
+                        deployment_bot_permissions::deployment_id,
+                    ))
+                    .get_results::<(Uuid, Option<Uuid>)>(db_conn().await?.deref_mut())
+                    .await?
+                {
+                    match deployment_id {
+                        Some(deployment_id) => deployment_ids.push(deployment_id),
+                        None => env_ids.push(env_id),
+                    }
+                }
+                Ok(Self::from_ids(env_ids, deployment_ids))
             }
-            // Service identities are trusted: bots and in-cluster deployments
-            // authenticate with their own tokens and operate across envs.
-            Identity::Bot(_) | Identity::Deployment(_) => Ok(Self::All),
+        }
+    }
+
+    fn envs(env_ids: Vec<Uuid>) -> Self {
+        Self::Envs {
+            env_ids,
+            deployment_ids: Vec::new(),
+        }
+    }
+
+    fn from_ids(mut env_ids: Vec<Uuid>, mut deployment_ids: Vec<Uuid>) -> Self {
+        env_ids.sort();
+        env_ids.dedup();
+        deployment_ids.sort();
+        deployment_ids.dedup();
+        Self::Envs {
+            env_ids,
+            deployment_ids,
         }
     }
 
@@ -65,7 +210,7 @@ impl AccessScope {
     pub fn allows_env(&self, env_id: Option<Uuid>) -> bool {
         match self {
             Self::All => true,
-            Self::Envs(env_ids) => env_id.is_some_and(|env_id| env_ids.contains(&env_id)),
+            Self::Envs { env_ids, .. } => env_id.is_some_and(|env_id| env_ids.contains(&env_id)),
         }
     }
 
@@ -80,7 +225,10 @@ impl AccessScope {
     pub fn can_receive_event(&self, event: &DbEvent) -> bool {
         match self {
             Self::All => true,
-            Self::Envs(env_ids) => match event.table {
+            Self::Envs {
+                env_ids,
+                deployment_ids,
+            } => match event.table {
                 // Global catalog / infrastructure tables are not env-scoped and
                 // are visible to every authenticated identity.
                 DbTable::HelmTagFormats
@@ -89,10 +237,23 @@ impl AccessScope {
                 | DbTable::DeploymentKinds
                 | DbTable::DeploymentResourceTypes
                 | DbTable::K8sClusters
+                | DbTable::K8sClusterDrains
+                | DbTable::K8sClusterHealth
+                | DbTable::K8sClusterRules
                 | DbTable::K8sResources
                 | DbTable::Users
                 | DbTable::Bots
+                | DbTable::CustomRoles
                 | DbTable::Settings => true,
+                // Deployments granted individually, along with their tasks and
+                // resources, are visible even when their environment isn't.
+                DbTable::Deployments | DbTable::DeploymentTasks | DbTable::DeploymentResources
+                    if event
+                        .deployment_id
+                        .is_some_and(|deployment_id| deployment_ids.contains(&deployment_id)) =>
+                {
+                    true
+                }
                 // Everything else is env-scoped: forward only when the event's
                 // resolved environment is one the identity may access.
                 _ => event.env_id.is_some_and(|env_id| env_ids.contains(&env_id)),
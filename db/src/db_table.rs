@@ -7,7 +7,13 @@ use uuid::Uuid;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum DbTable {
+    AccessRequests,
     Bots,
+    BotTokens,
+    CustomRoles,
+    DeploymentActionRestrictions,
+    DeploymentBotPermissions,
+    DeploymentEnvGrants,
     DeploymentKinds,
     DeploymentResources,
     DeploymentResourceTypes,
@@ -15,15 +21,23 @@ pub enum DbTable {
     DeploymentTasks,
     DeploymentPermissions,
     Envs,
+    EnvBotPermissions,
     EnvUserPermissions,
     HelmRegistries,
     HelmCharts,
     HelmTagFormats,
     K8sClusters,
+    K8sClusterDrains,
+    K8sClusterHealth,
+    K8sClusterRules,
     K8sResources,
     Secrets,
+    SecretVersions,
     Settings,
     Users,
+    UserTokens,
+    Webhooks,
+    WebhookDeliveries,
 }
 
 impl UiSchemaCollections for DbTable {
@@ -69,7 +83,7 @@ impl UiSchemaCollections for DbTable {
                         "env_id" => Ok(secret.env_id.to_string().into()),
                         "collection" => Ok(secret.collection.into()),
                         "name" => Ok(secret.name.into()),
-                        "contents" => Ok(secret.contents.into()),
+                        "contents" => Ok(secret.resolve_contents().await?.into()),
                         _ => Err(UiSchemaInputError::UnknownProperty(
                             property.to_owned(),
                             self.to_string(),
@@ -0,0 +1,432 @@
+//! Envelope encryption of sensitive columns.
+//!
+//! Every encrypted value gets its own random data key. The value is encrypted
+//! with the data key, and the data key is encrypted ("wrapped") with the master
+//! key. Rows store the ciphertext, the wrapped data key and the ID of the
+//! master key that wrapped it. Both use AES-256-GCM, stored as base64 of the
+//! nonce followed by the ciphertext and tag.
+//!
+//! The master key is 32 random bytes, base64 encoded, read from
+//! `PLATZ_ENCRYPTION_KEY` or from the file pointed to by
+//! `PLATZ_ENCRYPTION_KEY_FILE`. Without a master key, values are stored in
+//! plaintext as before.
+//!
+//! # Rotation
+//!
+//! Previous master keys are listed in `PLATZ_ENCRYPTION_OLD_KEYS` (comma
+//! separated) or in the file pointed to by `PLATZ_ENCRYPTION_OLD_KEYS_FILE`
+//! (one per line). They are only used for decrypting. To rotate:
+//!
+//! 1. Deploy all workers with the new key as the master key and the current
+//!    key as an old key. New writes use the new key, existing rows keep
+//!    working with the old one.
+//! 2. Run `platz-api encryption rotate`, which re-wraps the data key of every
+//!    row with the new key. Values themselves aren't re-encrypted. Rows stored
+//!    before encryption was enabled are encrypted.
+//! 3. Remove the old key.
+//!
+//! # Fingerprints
+//!
+//! Secret fingerprints are an HMAC-SHA256 of the value, keyed with a key
+//! derived from the current master key, so they can't be brute-forced without
+//! it. Without a master key they're a plain SHA-256, values are stored in
+//! plaintext anyway. Rotating recomputes the fingerprints with the new key,
+//! until then values written before and after the new key was deployed have
+//! different fingerprints.
+
+use aws_lc_rs::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    digest, hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use lazy_static::lazy_static;
+use std::{env, fmt::Write};
+
+const PLATZ_ENCRYPTION_KEY: &str = "PLATZ_ENCRYPTION_KEY";
+const PLATZ_ENCRYPTION_KEY_FILE: &str = "PLATZ_ENCRYPTION_KEY_FILE";
+const PLATZ_ENCRYPTION_OLD_KEYS: &str = "PLATZ_ENCRYPTION_OLD_KEYS";
+const PLATZ_ENCRYPTION_OLD_KEYS_FILE: &str = "PLATZ_ENCRYPTION_OLD_KEYS_FILE";
+
+const KEY_LEN: usize = 32;
+
+const FINGERPRINT_KEY_LABEL: &[u8] = b"platz secret fingerprints";
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum EncryptionError {
+    #[error("Invalid master key in {0}: {1}")]
+    InvalidKey(&'static str, String),
+
+    #[error("Failed reading {0}: {1}")]
+    KeyFileError(String, String),
+
+    #[error("Encryption is not configured, but found a value encrypted with master key {0}")]
+    NotConfigured(String),
+
+    #[error("Unknown master key {0}, was it removed before rotating?")]
+    UnknownKeyId(String),
+
+    #[error("Invalid encrypted value")]
+    InvalidCiphertext,
+
+    #[error("Failed encrypting value")]
+    EncryptFailed,
+
+    #[error("Failed decrypting value, it may have been tampered with")]
+    DecryptFailed,
+}
+
+type Result<T> = std::result::Result<T, EncryptionError>;
+
+struct MasterKey {
+    id: String,
+    key: LessSafeKey,
+    fingerprint_key: hmac::Key,
+}
+
+impl MasterKey {
+    fn new(source: &'static str, encoded: &str) -> Result<Self> {
+        let bytes = BASE64_STANDARD
+            .decode(encoded.trim())
+            .map_err(|err| EncryptionError::InvalidKey(source, err.to_string()))?;
+        if bytes.len() != KEY_LEN {
+            return Err(EncryptionError::InvalidKey(
+                source,
+                format!("expected {KEY_LEN} bytes, got {}", bytes.len()),
+            ));
+        }
+        Ok(Self {
+            id: key_id(&bytes),
+            key: new_key(&bytes).map_err(|_| {
+                EncryptionError::InvalidKey(source, "not a valid AES-256 key".to_owned())
+            })?,
+            fingerprint_key: fingerprint_key(&bytes),
+        })
+    }
+}
+
+/// Master keys IDs are derived from the key itself, so the same key always
+/// gets the same ID without having to configure one.
+fn key_id(key: &[u8]) -> String {
+    hex(&digest::digest(&digest::SHA256, key).as_ref()[..8])
+}
+
+fn fingerprint_key(key: &[u8]) -> hmac::Key {
+    let derived = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, key),
+        FINGERPRINT_KEY_LABEL,
+    );
+    hmac::Key::new(hmac::HMAC_SHA256, derived.as_ref())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn new_key(bytes: &[u8]) -> Result<LessSafeKey> {
+    Ok(LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, bytes).map_err(|_| EncryptionError::EncryptFailed)?,
+    ))
+}
+
+fn seal_with(key: &LessSafeKey, plaintext: &[u8]) -> Result<String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| EncryptionError::EncryptFailed)?;
+    let mut in_out = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut in_out,
+    )
+    .map_err(|_| EncryptionError::EncryptFailed)?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(in_out);
+    Ok(BASE64_STANDARD.encode(sealed))
+}
+
+fn open_with(key: &LessSafeKey, sealed: &str) -> Result<Vec<u8>> {
+    let sealed = BASE64_STANDARD
+        .decode(sealed)
+        .map_err(|_| EncryptionError::InvalidCiphertext)?;
+    if sealed.len() < NONCE_LEN {
+        return Err(EncryptionError::InvalidCiphertext);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let nonce =
+        Nonce::try_assume_unique_for_key(nonce).map_err(|_| EncryptionError::InvalidCiphertext)?;
+    let mut in_out = ciphertext.to_vec();
+    let plaintext = key
+        .open_in_place(nonce, Aad::empty(), &mut in_out)
+        .map_err(|_| EncryptionError::DecryptFailed)?;
+    Ok(plaintext.to_vec())
+}
+
+/// A value as stored in the database, along with its wrapped data key and the
+/// ID of the master key that wrapped it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encrypted {
+    pub ciphertext: String,
+    pub data_key: String,
+    pub key_id: String,
+}
+
+pub struct MasterKeys {
+    current: MasterKey,
+    old: Vec<MasterKey>,
+}
+
+impl MasterKeys {
+    /// Reads the master keys from the environment, returning `None` when
+    /// encryption isn't configured.
+    fn from_env() -> Result<Option<Self>> {
+        let current = match (
+            env::var(PLATZ_ENCRYPTION_KEY).ok(),
+            env::var(PLATZ_ENCRYPTION_KEY_FILE).ok(),
+        ) {
+            (Some(key), _) => MasterKey::new(PLATZ_ENCRYPTION_KEY, &key)?,
+            (None, Some(path)) => MasterKey::new(PLATZ_ENCRYPTION_KEY_FILE, &read_file(&path)?)?,
+            (None, None) => return Ok(None),
+        };
+        let old_keys = match (
+            env::var(PLATZ_ENCRYPTION_OLD_KEYS).ok(),
+            env::var(PLATZ_ENCRYPTION_OLD_KEYS_FILE).ok(),
+        ) {
+            (Some(keys), _) => keys
+                .split(',')
+                .map(|key| MasterKey::new(PLATZ_ENCRYPTION_OLD_KEYS, key))
+                .collect::<Result<Vec<_>>>()?,
+            (None, Some(path)) => read_file(&path)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|key| MasterKey::new(PLATZ_ENCRYPTION_OLD_KEYS_FILE, key))
+                .collect::<Result<Vec<_>>>()?,
+            (None, None) => Vec::new(),
+        };
+        Ok(Some(Self {
+            current,
+            old: old_keys,
+        }))
+    }
+
+    pub fn current_key_id(&self) -> &str {
+        &self.current.id
+    }
+
+    fn find(&self, key_id: &str) -> Result<&MasterKey> {
+        std::iter::once(&self.current)
+            .chain(self.old.iter())
+            .find(|key| key.id == key_id)
+            .ok_or_else(|| EncryptionError::UnknownKeyId(key_id.to_owned()))
+    }
+
+    fn unwrap_data_key(&self, data_key: &str, key_id: &str) -> Result<LessSafeKey> {
+        let data_key = open_with(&self.find(key_id)?.key, data_key)?;
+        new_key(&data_key).map_err(|_| EncryptionError::DecryptFailed)
+    }
+
+    /// Encrypts a value with a new data key, wrapped with the current master
+    /// key.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Encrypted> {
+        let mut data_key = [0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut data_key)
+            .map_err(|_| EncryptionError::EncryptFailed)?;
+        Ok(Encrypted {
+            ciphertext: seal_with(&new_key(&data_key)?, plaintext)?,
+            data_key: seal_with(&self.current.key, &data_key)?,
+            key_id: self.current.id.clone(),
+        })
+    }
+
+    pub fn decrypt(&self, ciphertext: &str, data_key: &str, key_id: &str) -> Result<Vec<u8>> {
+        open_with(&self.unwrap_data_key(data_key, key_id)?, ciphertext)
+    }
+
+    /// Wraps the data key of an encrypted value with the current master key,
+    /// returning the new wrapped data key.
+    pub fn rewrap(&self, data_key: &str, key_id: &str) -> Result<String> {
+        let data_key = open_with(&self.find(key_id)?.key, data_key)?;
+        seal_with(&self.current.key, &data_key)
+    }
+
+    /// Hex encoded fingerprint of a value, keyed with the current master key
+    pub fn fingerprint(&self, plaintext: &[u8]) -> String {
+        hex(hmac::sign(&self.current.fingerprint_key, plaintext).as_ref())
+    }
+
+    /// Brings a stored value to the current master key: re-wraps its data key
+    /// if it's encrypted, or encrypts it if it was stored in plaintext. Returns
+    /// the stored value and its wrapped data key.
+    pub fn reseal(
+        &self,
+        value: String,
+        data_key: Option<&str>,
+        key_id: Option<&str>,
+    ) -> Result<(String, String)> {
+        match (data_key, key_id) {
+            (Some(data_key), Some(key_id)) => Ok((value, self.rewrap(data_key, key_id)?)),
+            _ => {
+                let encrypted = self.encrypt(value.as_bytes())?;
+                Ok((encrypted.ciphertext, encrypted.data_key))
+            }
+        }
+    }
+}
+
+fn read_file(path: &str) -> Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|err| EncryptionError::KeyFileError(path.to_owned(), err.to_string()))
+}
+
+lazy_static! {
+    static ref MASTER_KEYS: Result<Option<MasterKeys>> = MasterKeys::from_env();
+}
+
+/// The configured master keys, or `None` if encryption isn't configured
+pub fn master_keys() -> Result<Option<&'static MasterKeys>> {
+    MASTER_KEYS
+        .as_ref()
+        .map(Option::as_ref)
+        .map_err(Clone::clone)
+}
+
+/// A value ready to be stored: encrypted when encryption is configured, or the
+/// plaintext with no key otherwise.
+pub(crate) struct Sealed {
+    pub value: String,
+    pub data_key: Option<String>,
+    pub key_id: Option<String>,
+}
+
+pub(crate) fn seal(plaintext: String) -> Result<Sealed> {
+    Ok(match master_keys()? {
+        Some(keys) => {
+            let encrypted = keys.encrypt(plaintext.as_bytes())?;
+            Sealed {
+                value: encrypted.ciphertext,
+                data_key: Some(encrypted.data_key),
+                key_id: Some(encrypted.key_id),
+            }
+        }
+        None => Sealed {
+            value: plaintext,
+            data_key: None,
+            key_id: None,
+        },
+    })
+}
+
+/// Fingerprint of a value with the current master key, or its SHA-256 if
+/// encryption isn't configured
+pub(crate) fn fingerprint(plaintext: &str) -> Result<String> {
+    Ok(match master_keys()? {
+        Some(keys) => keys.fingerprint(plaintext.as_bytes()),
+        None => hex(digest::digest(&digest::SHA256, plaintext.as_bytes()).as_ref()),
+    })
+}
+
+/// Reverses [`seal`]. Values stored without a key are returned as is.
+pub(crate) fn open(value: &str, data_key: Option<&str>, key_id: Option<&str>) -> Result<String> {
+    match (data_key, key_id) {
+        (Some(data_key), Some(key_id)) => {
+            let keys =
+                master_keys()?.ok_or_else(|| EncryptionError::NotConfigured(key_id.to_owned()))?;
+            String::from_utf8(keys.decrypt(value, data_key, key_id)?)
+                .map_err(|_| EncryptionError::DecryptFailed)
+        }
+        _ => Ok(value.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(current: u8, old: &[u8]) -> MasterKeys {
+        let key =
+            |byte: u8| MasterKey::new("test", &BASE64_STANDARD.encode([byte; KEY_LEN])).unwrap();
+        MasterKeys {
+            current: key(current),
+            old: old.iter().copied().map(key).collect(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let keys = keys(1, &[]);
+        let encrypted = keys.encrypt(b"hunter2").unwrap();
+        assert_ne!(encrypted.ciphertext, "hunter2");
+        assert_eq!(encrypted.key_id, keys.current_key_id());
+        assert_eq!(
+            keys.decrypt(
+                &encrypted.ciphertext,
+                &encrypted.data_key,
+                &encrypted.key_id
+            )
+            .unwrap(),
+            b"hunter2"
+        );
+    }
+
+    #[test]
+    fn test_rotation() {
+        let encrypted = keys(1, &[]).encrypt(b"hunter2").unwrap();
+        let rotated = keys(2, &[1]);
+        assert_ne!(rotated.current_key_id(), encrypted.key_id);
+        let data_key = rotated
+            .rewrap(&encrypted.data_key, &encrypted.key_id)
+            .unwrap();
+        assert_eq!(
+            keys(2, &[])
+                .decrypt(&encrypted.ciphertext, &data_key, rotated.current_key_id())
+                .unwrap(),
+            b"hunter2"
+        );
+    }
+
+    #[test]
+    fn test_unknown_or_wrong_key() {
+        let encrypted = keys(1, &[]).encrypt(b"hunter2").unwrap();
+        assert!(matches!(
+            keys(2, &[]).decrypt(
+                &encrypted.ciphertext,
+                &encrypted.data_key,
+                &encrypted.key_id
+            ),
+            Err(EncryptionError::UnknownKeyId(_))
+        ));
+        let mut tampered = BASE64_STANDARD.decode(&encrypted.ciphertext).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            keys(1, &[]).decrypt(
+                &BASE64_STANDARD.encode(tampered),
+                &encrypted.data_key,
+                &encrypted.key_id
+            ),
+            Err(EncryptionError::DecryptFailed)
+        ));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = keys(1, &[]).fingerprint(b"hunter2");
+        assert_eq!(fingerprint, keys(1, &[2]).fingerprint(b"hunter2"));
+        assert_ne!(fingerprint, keys(1, &[]).fingerprint(b"hunter3"));
+        assert_ne!(fingerprint, keys(2, &[1]).fingerprint(b"hunter2"));
+        assert_ne!(
+            fingerprint,
+            hex(digest::digest(&digest::SHA256, b"hunter2").as_ref())
+        );
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(MasterKey::new("test", &BASE64_STANDARD.encode([1u8; 16])).is_err());
+        assert!(MasterKey::new("test", "not base64!").is_err());
+    }
+}
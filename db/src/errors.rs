@@ -18,6 +18,12 @@ pub enum DbError {
     #[error("Invalid database TLS configuration: {0}")]
     SslConfigError(String),
 
+    #[error("Encryption error: {0}")]
+    EncryptionError(#[from] crate::encryption::EncryptionError),
+
+    #[error("Failed resolving secret {0}: {1}")]
+    SecretStoreError(String, crate::secret_store::SecretStoreError),
+
     #[error("Database was not initialized")]
     DbNotInitialized,
 
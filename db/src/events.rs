@@ -1,5 +1,6 @@
 use crate::DbTable;
 use crate::config::SslSettings;
+use platz_otel::health;
 use serde::{Deserialize, Serialize};
 use std::{future::poll_fn, task::ready};
 use tokio::{
@@ -40,6 +41,12 @@ pub struct DbEvent {
     #[serde(default)]
     #[schema(required)]
     pub env_id: Option<Uuid>,
+    /// Deployment the changed row belongs to (the row itself for deployments),
+    /// resolved by the database trigger. Used to forward events to clients
+    /// permitted to see only that deployment.
+    #[serde(default)]
+    #[schema(required)]
+    pub deployment_id: Option<Uuid>,
     pub data: DbEventData,
 }
 
@@ -117,10 +124,12 @@ impl DbEventBroadcast {
         loop {
             let listen_to = channel_name.clone();
             debug!("Listening for {}", &listen_to);
+            health::set_ready("db_events", Err("Connecting".to_owned()));
             match self.listen_for_notifications(&listen_to).await {
                 Ok(()) => continue,
                 Err(err) if err.retryable() => {
                     error!("Retryable error while listening for notifications: {err:?}");
+                    health::set_ready("db_events", Err(err.to_string()));
                     time::sleep(time::Duration::from_secs(3)).await;
                 }
                 Err(err) => break Err(err),
@@ -160,6 +169,7 @@ impl DbEventBroadcast {
             .execute(&format!("LISTEN {channel_name}"), &[])
             .await
             .map_err(DbEventsError::ListenQueryFailed)?;
+        health::set_ready("db_events", Ok(()));
 
         events_task.await?
     }
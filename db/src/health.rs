@@ -0,0 +1,20 @@
+use crate::DbPool;
+use platz_otel::health;
+use std::time::Duration;
+use tokio::time;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Reports the pool as ready while connections can be taken from it. The
+/// check loop also beats, so a starved runtime fails liveness.
+pub(crate) async fn start(pool: DbPool, connection_timeout: Duration) {
+    let mut interval = time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        health::set_ready(
+            "db_pool",
+            pool.get().await.map(drop).map_err(|err| err.to_string()),
+        );
+        health::heartbeat("db_pool", CHECK_INTERVAL * 4 + connection_timeout);
+    }
+}
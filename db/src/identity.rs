@@ -17,6 +17,13 @@ impl Identity {
         }
     }
 
+    pub fn bot_id(&self) -> Option<Uuid> {
+        match self {
+            Self::Bot(bot_id) => Some(bot_id.to_owned()),
+            _ => None,
+        }
+    }
+
     pub fn deployment_id(&self) -> Option<Uuid> {
         match self {
             Self::Deployment(deployment_id) => Some(deployment_id.to_owned()),
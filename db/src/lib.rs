@@ -1,11 +1,14 @@
 mod access;
 mod config;
 mod db_table;
+pub mod encryption;
 mod errors;
 mod events;
+mod health;
 mod identity;
 pub mod json_diff;
 pub mod schema;
+pub mod secret_store;
 mod stats;
 pub mod tls;
 mod ui_collection;
@@ -46,6 +49,7 @@ pub struct Db {
     pool: DbPool,
     events: DbEventBroadcast,
     _stats_task: JoinHandle<()>,
+    _health_task: JoinHandle<()>,
 }
 
 impl Db {
@@ -53,6 +57,13 @@ impl Db {
         let connection_url = database_url();
         let ssl = SslSettings::from_env().map_err(errors::DbError::SslConfigError)?;
         info!("Connecting to {connection_url} (sslmode={:?})", ssl.mode);
+        match encryption::master_keys()? {
+            Some(keys) => info!(
+                "Encrypting sensitive values with master key {}",
+                keys.current_key_id()
+            ),
+            None => info!("No encryption key configured, sensitive values are stored in plaintext"),
+        }
 
         // Wire the TLS connector into every pooled connection via a custom
         // setup callback, so the pool negotiates TLS exactly like the
@@ -80,10 +91,12 @@ impl Db {
         info!("Pool configuration: {:?}", pool_options);
         let events = Default::default();
         let stats_task = spawn(stats::start(pool.clone()));
+        let health_task = spawn(health::start(pool.clone(), pool_options.connection_timeout));
         Ok(Self {
             pool,
             events,
             _stats_task: stats_task,
+            _health_task: health_task,
         })
     }
 
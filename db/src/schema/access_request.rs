@@ -0,0 +1,318 @@
+use super::{
+    deployment_permission::{NewDeploymentPermission, UserDeploymentRole, deployment_permissions},
+    env_user_permission::{EnvUserRole, NewEnvUserPermission, env_user_permissions},
+};
+use crate::{AccessScope, DbError, DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::{
+    AsyncConnection, AsyncPgConnection, RunQueryDsl, scoped_futures::ScopedFutureExt,
+};
+use diesel_enum_derive::DieselEnum;
+use diesel_filter::DieselFilter;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use strum::{AsRefStr, Display, EnumIter, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    access_requests(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        user_id -> Uuid,
+        env_id -> Uuid,
+        kind_id -> Nullable<Uuid>,
+        deployment_id -> Nullable<Uuid>,
+        env_role -> Nullable<Varchar>,
+        deployment_role -> Nullable<Varchar>,
+        custom_role_id -> Nullable<Uuid>,
+        duration_secs -> Integer,
+        justification -> Varchar,
+        status -> Varchar,
+        reviewed_by_user_id -> Nullable<Uuid>,
+        reviewed_at -> Nullable<Timestamptz>,
+        review_comment -> Nullable<Varchar>,
+        expires_at -> Nullable<Timestamptz>,
+        revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    AsRefStr,
+    Display,
+    DieselEnum,
+    ToSchema,
+)]
+pub enum AccessRequestStatus {
+    /// Waiting for an env admin to review
+    Pending,
+    /// Approved, the temporary permission is in effect until `expires_at`
+    Approved,
+    Rejected,
+    /// Canceled by the requesting user before being reviewed
+    Canceled,
+    /// The temporary permission expired and was revoked
+    Expired,
+}
+
+/// A user's request for temporary elevated access. Requests without a
+/// `kind_id` ask for `env_role` in the env, requests with a `kind_id` ask for
+/// `deployment_role` on deployments of that kind, or only on `deployment_id`
+/// if set. Requests are kept after the grant expires, for auditing.
+#[derive(Debug, Clone, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = access_requests)]
+pub struct AccessRequest {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter]
+    pub user_id: Uuid,
+    #[filter]
+    pub env_id: Uuid,
+    #[schema(required)]
+    pub kind_id: Option<Uuid>,
+    #[schema(required)]
+    pub deployment_id: Option<Uuid>,
+    #[schema(required)]
+    pub env_role: Option<EnvUserRole>,
+    #[schema(required)]
+    pub deployment_role: Option<UserDeploymentRole>,
+    #[schema(required)]
+    pub custom_role_id: Option<Uuid>,
+    /// How long the access is granted for once approved
+    pub duration_secs: i32,
+    pub justification: String,
+    #[filter]
+    pub status: AccessRequestStatus,
+    #[schema(required)]
+    pub reviewed_by_user_id: Option<Uuid>,
+    #[schema(required)]
+    pub reviewed_at: Option<DateTime<Utc>>,
+    #[schema(required)]
+    pub review_comment: Option<String>,
+    /// When the granted access expires, set on approval
+    #[schema(required)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// When the granted access was revoked after expiring
+    #[schema(required)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl AccessRequest {
+    /// Requests in the identity's scope, along with the user's own requests
+    /// in envs outside it.
+    pub async fn all_filtered(
+        filters: AccessRequestFilters,
+        pagination: PaginationParams,
+        scope: &AccessScope,
+        user_id: Option<Uuid>,
+    ) -> DbResult<Paginated<Self>> {
+        let mut filtered = Self::filter(filters);
+        if let AccessScope::Envs { env_ids, .. } = scope {
+            filtered = filtered.filter(
+                access_requests::env_id
+                    .eq_any(env_ids.clone())
+                    .or(access_requests::user_id.nullable().eq(user_id)),
+            );
+        }
+        Ok(filtered
+            .order_by(access_requests::created_at.desc())
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(access_requests::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Like [`Self::find`] but only returns the request if its environment is
+    /// within the identity's [`AccessScope`], or if it's the user's own.
+    pub async fn find_scoped(
+        id: Uuid,
+        scope: &AccessScope,
+        user_id: Option<Uuid>,
+    ) -> DbResult<Self> {
+        match scope {
+            AccessScope::All => Self::find(id).await,
+            AccessScope::Envs { env_ids, .. } => Ok(access_requests::table
+                .find(id)
+                .filter(
+                    access_requests::env_id
+                        .eq_any(env_ids.clone())
+                        .or(access_requests::user_id.nullable().eq(user_id)),
+                )
+                .get_result(db_conn().await?.deref_mut())
+                .await?),
+        }
+    }
+
+    /// Updates the status of a pending request. Returns `None` if the request
+    /// is no longer pending, so concurrent reviews don't override each other.
+    async fn review(
+        &self,
+        conn: &mut AsyncPgConnection,
+        status: AccessRequestStatus,
+        reviewed_by_user_id: Option<Uuid>,
+        review_comment: Option<String>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> DbResult<Option<Self>> {
+        Ok(diesel::update(
+            access_requests::table
+                .find(self.id)
+                .filter(access_requests::status.eq(AccessRequestStatus::Pending)),
+        )
+        .set((
+            access_requests::status.eq(status),
+            access_requests::reviewed_by_user_id.eq(reviewed_by_user_id),
+            access_requests::reviewed_at.eq(diesel::dsl::now),
+            access_requests::review_comment.eq(review_comment),
+            access_requests::expires_at.eq(expires_at),
+        ))
+        .get_result(conn)
+        .await
+        .optional()?)
+    }
+
+    /// Approves a pending request and grants the requested access until it
+    /// expires. Returns `None` if the request is no longer pending. Both are
+    /// done in one transaction, so a request is never approved without its
+    /// permission.
+    pub async fn approve(
+        &self,
+        reviewed_by_user_id: Uuid,
+        review_comment: Option<String>,
+    ) -> DbResult<Option<Self>> {
+        let expires_at = Utc::now() + chrono::Duration::seconds(self.duration_secs.into());
+        db_conn()
+            .await?
+            .deref_mut()
+            .transaction::<_, DbError, _>(|conn| {
+                async move {
+                    let Some(request) = self
+                        .review(
+                            conn,
+                            AccessRequestStatus::Approved,
+                            Some(reviewed_by_user_id),
+                            review_comment,
+                            Some(expires_at),
+                        )
+                        .await?
+                    else {
+                        return Ok(None);
+                    };
+                    match (request.kind_id, request.env_role, request.deployment_role) {
+                        (None, Some(role), _) => {
+                            diesel::insert_into(env_user_permissions::table)
+                                .values(NewEnvUserPermission {
+                                    env_id: request.env_id,
+                                    user_id: request.user_id,
+                                    role,
+                                    custom_role_id: request.custom_role_id,
+                                    expires_at: Some(expires_at),
+                                })
+                                .execute(conn)
+                                .await?;
+                        }
+                        (Some(kind_id), _, Some(role)) => {
+                            diesel::insert_into(deployment_permissions::table)
+                                .values(NewDeploymentPermission {
+                                    env_id: request.env_id,
+                                    user_id: request.user_id,
+                                    kind_id,
+                                    role,
+                                    custom_role_id: request.custom_role_id,
+                                    deployment_id: request.deployment_id,
+                                    expires_at: Some(expires_at),
+                                })
+                                .execute(conn)
+                                .await?;
+                        }
+                        // Ruled out by the access_requests_role check constraint
+                        _ => unreachable!("Access request {} has no role", request.id),
+                    }
+                    Ok(Some(request))
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn reject(
+        &self,
+        reviewed_by_user_id: Uuid,
+        review_comment: Option<String>,
+    ) -> DbResult<Option<Self>> {
+        self.review(
+            db_conn().await?.deref_mut(),
+            AccessRequestStatus::Rejected,
+            Some(reviewed_by_user_id),
+            review_comment,
+            None,
+        )
+        .await
+    }
+
+    pub async fn cancel(&self) -> DbResult<Option<Self>> {
+        self.review(
+            db_conn().await?.deref_mut(),
+            AccessRequestStatus::Canceled,
+            None,
+            None,
+            None,
+        )
+        .await
+    }
+
+    /// Marks approved requests whose access expired as expired, returning
+    /// them. Called after the expired permissions themselves were deleted.
+    pub async fn expire_approved() -> DbResult<Vec<Self>> {
+        Ok(diesel::update(
+            access_requests::table
+                .filter(access_requests::status.eq(AccessRequestStatus::Approved))
+                .filter(access_requests::expires_at.le(diesel::dsl::now)),
+        )
+        .set((
+            access_requests::status.eq(AccessRequestStatus::Expired),
+            access_requests::revoked_at.eq(diesel::dsl::now),
+        ))
+        .get_results(db_conn().await?.deref_mut())
+        .await?)
+    }
+}
+
+#[derive(Debug, Insertable, Deserialize, ToSchema)]
+#[diesel(table_name = access_requests)]
+pub struct NewAccessRequest {
+    pub user_id: Uuid,
+    pub env_id: Uuid,
+    pub kind_id: Option<Uuid>,
+    pub deployment_id: Option<Uuid>,
+    pub env_role: Option<EnvUserRole>,
+    pub deployment_role: Option<UserDeploymentRole>,
+    pub custom_role_id: Option<Uuid>,
+    pub duration_secs: i32,
+    pub justification: String,
+}
+
+impl NewAccessRequest {
+    pub async fn insert(self) -> DbResult<AccessRequest> {
+        Ok(diesel::insert_into(access_requests::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
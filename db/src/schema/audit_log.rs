@@ -0,0 +1,206 @@
+use crate::{DbResult, DbTable, db_conn, json_diff::JsonDiff};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_json::Json;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ops::DerefMut;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+table! {
+    audit_log(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        acting_user_id -> Nullable<Uuid>,
+        acting_bot_id -> Nullable<Uuid>,
+        acting_deployment_id -> Nullable<Uuid>,
+        method -> Varchar,
+        action -> Varchar,
+        path -> Varchar,
+        status_code -> Integer,
+        target_table -> Nullable<Varchar>,
+        target_id -> Nullable<Uuid>,
+        diff -> Nullable<Jsonb>,
+        source_ip -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+    }
+}
+
+/// Columns never written to the audit log, replaced with a placeholder when
+/// they change
+const REDACTED_COLUMNS: &[&str] = &[
+    "contents",
+    "data_key",
+    "secret_hash",
+    "sensitive_props",
+    "signing_secret",
+];
+const REDACTED: &str = "<redacted>";
+
+/// A mutating API request: who made it, from where, and how it changed its
+/// target row.
+#[derive(Debug, Identifiable, Queryable, Serialize, ToSchema)]
+#[diesel(table_name = audit_log)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[schema(required)]
+    pub acting_user_id: Option<Uuid>,
+    #[schema(required)]
+    pub acting_bot_id: Option<Uuid>,
+    #[schema(required)]
+    pub acting_deployment_id: Option<Uuid>,
+    pub method: String,
+    /// The method and route, e.g. `PUT /api/v2/deployments/{id}`
+    pub action: String,
+    pub path: String,
+    pub status_code: i32,
+    #[schema(required)]
+    pub target_table: Option<String>,
+    #[schema(required)]
+    pub target_id: Option<Uuid>,
+    /// Changes to the target row, `None` if the request didn't change it
+    #[schema(required, value_type = Option<JsonDiff>)]
+    pub diff: Option<Json<JsonDiff>>,
+    #[schema(required)]
+    pub source_ip: Option<String>,
+    #[schema(required)]
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogFilters {
+    pub acting_user_id: Option<Uuid>,
+    pub acting_bot_id: Option<Uuid>,
+    pub acting_deployment_id: Option<Uuid>,
+    pub method: Option<String>,
+    pub target_table: Option<String>,
+    pub target_id: Option<Uuid>,
+    /// Only entries created at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries created before this time
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(QueryableByName)]
+struct RowJson {
+    #[diesel(sql_type = diesel::sql_types::Json)]
+    row: Value,
+}
+
+fn redact(mut row: Value) -> Value {
+    if let Value::Object(columns) = &mut row {
+        for column in REDACTED_COLUMNS {
+            if let Some(value) = columns.get_mut(*column)
+                && !value.is_null()
+            {
+                *value = Value::String(REDACTED.to_owned());
+            }
+        }
+    }
+    row
+}
+
+impl AuditLogEntry {
+    pub async fn all_filtered(
+        filters: AuditLogFilters,
+        pagination: PaginationParams,
+    ) -> DbResult<Paginated<Self>> {
+        let mut query = audit_log::table.into_boxed();
+        if let Some(acting_user_id) = filters.acting_user_id {
+            query = query.filter(audit_log::acting_user_id.eq(acting_user_id));
+        }
+        if let Some(acting_bot_id) = filters.acting_bot_id {
+            query = query.filter(audit_log::acting_bot_id.eq(acting_bot_id));
+        }
+        if let Some(acting_deployment_id) = filters.acting_deployment_id {
+            query = query.filter(audit_log::acting_deployment_id.eq(acting_deployment_id));
+        }
+        if let Some(method) = filters.method {
+            query = query.filter(audit_log::method.eq(method.to_uppercase()));
+        }
+        if let Some(target_table) = filters.target_table {
+            query = query.filter(audit_log::target_table.eq(target_table));
+        }
+        if let Some(target_id) = filters.target_id {
+            query = query.filter(audit_log::target_id.eq(target_id));
+        }
+        if let Some(since) = filters.since {
+            query = query.filter(audit_log::created_at.ge(since));
+        }
+        if let Some(until) = filters.until {
+            query = query.filter(audit_log::created_at.lt(until));
+        }
+        Ok(query
+            .order_by(audit_log::created_at.desc())
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(audit_log::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// A row as JSON, with sensitive columns redacted, or `None` if it doesn't
+    /// exist. Used for diffing the target of a request before and after it.
+    pub async fn snapshot(table: DbTable, id: Uuid) -> DbResult<Option<Value>> {
+        // Table names come from DbTable, never from user input
+        Ok(diesel::sql_query(format!(
+            "select row_to_json(t) as row from {table} t where t.id = $1"
+        ))
+        .bind::<diesel::sql_types::Uuid, _>(id)
+        .get_result::<RowJson>(db_conn().await?.deref_mut())
+        .await
+        .optional()?
+        .map(|row_json| redact(row_json.row)))
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditLogEntry {
+    pub acting_user_id: Option<Uuid>,
+    pub acting_bot_id: Option<Uuid>,
+    pub acting_deployment_id: Option<Uuid>,
+    pub method: String,
+    pub action: String,
+    pub path: String,
+    pub status_code: i32,
+    pub target_table: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub diff: Option<Json<JsonDiff>>,
+    pub source_ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl NewAuditLogEntry {
+    pub async fn insert(self) -> DbResult<AuditLogEntry> {
+        Ok(diesel::insert_into(audit_log::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact(json!({"id": 1, "contents": "hunter2", "data_key": null})),
+            json!({"id": 1, "contents": REDACTED, "data_key": null})
+        );
+        assert_eq!(redact(json!(null)), json!(null));
+    }
+}
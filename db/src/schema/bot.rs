@@ -14,6 +14,7 @@ table! {
         id -> Uuid,
         created_at -> Timestamptz,
         display_name -> Varchar,
+        unrestricted -> Bool,
     }
 }
 
@@ -24,6 +25,10 @@ pub struct Bot {
     pub created_at: DateTime<Utc>,
     #[filter(insensitive, substring)]
     pub display_name: String,
+    /// Unrestricted bots see every env and may maintain any deployment.
+    /// Other bots only get the roles bound to them in each env.
+    #[filter]
+    pub unrestricted: bool,
 }
 
 impl Bot {
@@ -63,6 +68,8 @@ impl Bot {
 #[diesel(table_name = bots)]
 pub struct NewBot {
     pub display_name: String,
+    #[serde(default)]
+    pub unrestricted: bool,
 }
 
 impl NewBot {
@@ -79,6 +86,7 @@ impl NewBot {
 #[diesel(table_name = bots)]
 pub struct UpdateBot {
     pub display_name: Option<String>,
+    pub unrestricted: Option<bool>,
 }
 
 impl UpdateBot {
@@ -0,0 +1,152 @@
+use crate::{DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_filter::DieselFilter;
+use diesel_json::Json;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use strum::{AsRefStr, Display, EnumIter, EnumString, IntoEnumIterator};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    custom_roles(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        name -> Varchar,
+        permissions -> Jsonb,
+    }
+}
+
+/// A single operation a role may allow. Roles bound in an env apply to
+/// deployments of all kinds in the env, roles bound per kind only apply to
+/// deployments of that kind.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    AsRefStr,
+    Display,
+    ToSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum RolePermission {
+    /// See the env and its deployments
+    Read,
+    /// Create deployments
+    Create,
+    /// Change the config, values, name or cluster of deployments, or
+    /// enable and disable them
+    UpdateConfig,
+    /// Change the Helm chart of deployments
+    UpgradeChart,
+    /// Invoke chart actions on deployments
+    InvokeAction,
+    /// Restart Kubernetes resources of deployments
+    Restart,
+    /// Delete deployments
+    Delete,
+    /// Create, update and delete env secrets. Only meaningful for roles bound
+    /// in an env.
+    ManageSecrets,
+}
+
+impl RolePermission {
+    pub fn all() -> Vec<Self> {
+        Self::iter().collect()
+    }
+}
+
+/// A named role made of explicit permissions. Custom roles are bound to users
+/// and bots like the built-in roles, using the `Custom` role and the custom
+/// role's ID.
+#[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = custom_roles)]
+pub struct CustomRole {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter(insensitive, substring)]
+    pub name: String,
+    #[schema(value_type = Vec<RolePermission>)]
+    pub permissions: Json<Vec<RolePermission>>,
+}
+
+impl CustomRole {
+    pub async fn all_filtered(
+        filters: CustomRoleFilters,
+        pagination: PaginationParams,
+    ) -> DbResult<Paginated<Self>> {
+        Ok(Self::filter(filters)
+            .order_by(custom_roles::name)
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(custom_roles::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// IDs of all custom roles including the given permission
+    pub async fn ids_with_permission(permission: RolePermission) -> DbResult<Vec<Uuid>> {
+        Ok(custom_roles::table
+            .filter(custom_roles::permissions.contains(serde_json::json!([permission])))
+            .select(custom_roles::id)
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn delete(&self) -> DbResult<()> {
+        diesel::delete(custom_roles::table.find(self.id))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable, Deserialize, ToSchema)]
+#[diesel(table_name = custom_roles)]
+pub struct NewCustomRole {
+    pub name: String,
+    #[schema(value_type = Vec<RolePermission>)]
+    pub permissions: Json<Vec<RolePermission>>,
+}
+
+impl NewCustomRole {
+    pub async fn insert(self) -> DbResult<CustomRole> {
+        Ok(diesel::insert_into(custom_roles::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
+
+#[derive(Debug, AsChangeset, Deserialize, ToSchema)]
+#[diesel(table_name = custom_roles)]
+pub struct UpdateCustomRole {
+    pub name: Option<String>,
+    #[schema(value_type = Option<Vec<RolePermission>>)]
+    pub permissions: Option<Json<Vec<RolePermission>>>,
+}
+
+impl UpdateCustomRole {
+    pub async fn save(self, id: Uuid) -> DbResult<CustomRole> {
+        Ok(diesel::update(custom_roles::table.find(id))
+            .set(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
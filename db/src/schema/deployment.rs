@@ -40,6 +40,7 @@ table! {
         helm_chart_id -> Uuid,
         config -> Jsonb,
         values_override -> Nullable<Jsonb>,
+        status_before_orphaned -> Nullable<Varchar>,
     }
 }
 
@@ -68,6 +69,8 @@ pub enum DeploymentStatus {
     Uninstalling,
     Uninstalled,
     Deleting,
+    /// The deployment's cluster is missing from cluster discovery
+    Orphaned,
 }
 
 #[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema, Clone)]
@@ -96,6 +99,8 @@ pub struct Deployment {
     pub config: serde_json::Value,
     #[schema(required)]
     pub values_override: Option<serde_json::Value>,
+    #[serde(skip)]
+    pub status_before_orphaned: Option<DeploymentStatus>,
 }
 
 #[derive(Queryable)]
@@ -144,9 +149,17 @@ impl Deployment {
         }
         // Restrict to clusters in the environments the identity may access. The
         // database does the filtering as part of the single paginated query.
-        if let AccessScope::Envs(env_ids) = scope {
+        if let AccessScope::Envs {
+            env_ids,
+            deployment_ids,
+        } = scope
+        {
             let cluster_ids = K8sCluster::ids_in_envs(env_ids).await?;
-            filtered = filtered.filter(deployments::cluster_id.eq_any(cluster_ids));
+            filtered = filtered.filter(
+                deployments::cluster_id
+                    .eq_any(cluster_ids)
+                    .or(deployments::id.eq_any(deployment_ids.clone())),
+            );
         }
 
         Ok(filtered
@@ -170,11 +183,18 @@ impl Deployment {
     pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
         match scope {
             AccessScope::All => Self::find(id).await,
-            AccessScope::Envs(env_ids) => {
+            AccessScope::Envs {
+                env_ids,
+                deployment_ids,
+            } => {
                 let cluster_ids = K8sCluster::ids_in_envs(env_ids).await?;
                 Ok(deployments::table
                     .find(id)
-                    .filter(deployments::cluster_id.eq_any(cluster_ids))
+                    .filter(
+                        deployments::cluster_id
+                            .eq_any(cluster_ids)
+                            .or(deployments::id.eq_any(deployment_ids.clone())),
+                    )
                     .get_result(db_conn().await?.deref_mut())
                     .await?)
             }
@@ -192,6 +212,15 @@ impl Deployment {
             .await?)
     }
 
+    /// Which of the given IDs belong to existing deployments
+    pub async fn existing_ids(ids: Vec<Uuid>) -> DbResult<Vec<Uuid>> {
+        Ok(deployments::table
+            .filter(deployments::id.eq_any(ids))
+            .select(deployments::id)
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
     pub async fn find_optional(id: Uuid) -> DbResult<Option<Self>> {
         Ok(deployments::table
             .find(id)
@@ -211,28 +240,39 @@ impl Deployment {
             .await?)
     }
 
-    async fn is_using(&self, collection: &DbTableOrDeploymentResource, id: &str) -> DbResult<bool> {
+    /// The values UI schema of the deployment's current chart and the config
+    /// it was installed with, if it has both
+    async fn values_ui_and_config(
+        &self,
+    ) -> DbResult<Option<(serde_json::Value, serde_json::Value)>> {
         let revision_id = match self.revision_id {
             Some(revision_id) => revision_id,
-            None => return Ok(false),
+            None => return Ok(None),
         };
         let task = DeploymentTask::find(revision_id).await?;
         let chart = match task.helm_chart().await {
             Ok(chart) => chart,
-            Err(DbError::InvalidDeploymentRevision) => return Ok(false),
+            Err(DbError::InvalidDeploymentRevision) => return Ok(None),
             Err(err) => return Err(err),
         };
-        let values_ui: UiSchema = match chart.values_ui {
-            None => return Ok(false),
-            Some(values_ui) => serde_json::from_value(values_ui)
-                .map_err(DbError::HelmChartValuesSchemaParseError)?,
+        let Some(values_ui) = chart.values_ui else {
+            return Ok(None);
         };
         let config = match task.get_config() {
-            Ok(config) => config,
-            Err(DbError::TaskHasNoConfig) => return Ok(false),
+            Ok(config) => config.clone(),
+            Err(DbError::TaskHasNoConfig) => return Ok(None),
             Err(err) => return Err(err),
         };
-        Ok(values_ui.is_collection_in_inputs(config, collection, id))
+        Ok(Some((values_ui, config)))
+    }
+
+    async fn is_using(&self, collection: &DbTableOrDeploymentResource, id: &str) -> DbResult<bool> {
+        let Some((values_ui, config)) = self.values_ui_and_config().await? else {
+            return Ok(false);
+        };
+        let values_ui: UiSchema =
+            serde_json::from_value(values_ui).map_err(DbError::HelmChartValuesSchemaParseError)?;
+        Ok(values_ui.is_collection_in_inputs(&config, collection, id))
     }
 
     pub async fn find_using(
@@ -268,6 +308,42 @@ impl Deployment {
         Ok(())
     }
 
+    /// Like [`Self::reinstall_all_using`], for changes to the value of a
+    /// collection item. Deployments whose chart only uses the item in the
+    /// Kubernetes secrets it creates only get those secrets refreshed, the
+    /// rest are reinstalled since their values use it as well.
+    pub async fn refresh_secrets_all_using<I>(
+        collection: &DbTableOrDeploymentResource,
+        id: Uuid,
+        identity: &I,
+        reason: String,
+    ) -> DbResult<()>
+    where
+        I: std::borrow::Borrow<Identity>,
+    {
+        for deployment in Deployment::find_using(collection, id)
+            .await?
+            .into_iter()
+            .filter(|deployment| deployment.enabled)
+        {
+            let only_in_secrets =
+                deployment
+                    .values_ui_and_config()
+                    .await?
+                    .is_some_and(|(values_ui, config)| {
+                        only_feeds_secrets(&values_ui, &config, &id.to_string())
+                    });
+            if only_in_secrets {
+                DeploymentTask::create_refresh_secrets_task(&deployment, identity, reason.clone())
+                    .await?;
+            } else {
+                DeploymentTask::create_reinstall_task(&deployment, identity, reason.clone())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn find_by_cluster_id(cluster_id: Uuid) -> DbResult<Vec<Self>> {
         Ok(deployments::table
             .filter(deployments::cluster_id.eq(cluster_id))
@@ -275,6 +351,61 @@ impl Deployment {
             .await?)
     }
 
+    /// Marks all deployments in a cluster as orphaned, remembering their
+    /// status. Deployments that are uninstalled or being removed are left
+    /// alone, so their removal completes once the namespace is gone.
+    pub async fn orphan_all_in_cluster(cluster_id: Uuid, reason: String) -> DbResult<Vec<Self>> {
+        Ok(diesel::update(
+            deployments::table
+                .filter(deployments::cluster_id.eq(cluster_id))
+                .filter(deployments::status.ne_all(vec![
+                    DeploymentStatus::Uninstalled,
+                    DeploymentStatus::Uninstalling,
+                    DeploymentStatus::Deleting,
+                    DeploymentStatus::Orphaned,
+                ])),
+        )
+        .set((
+            deployments::status_before_orphaned.eq(deployments::status.nullable()),
+            deployments::status.eq(DeploymentStatus::Orphaned),
+            deployments::reason.eq(reason),
+        ))
+        .get_results(db_conn().await?.deref_mut())
+        .await?)
+    }
+
+    /// Restores the status orphaned deployments had before their cluster went
+    /// missing, once it's discovered again. Deployments orphaned without a
+    /// remembered status are unknown until the next task.
+    pub async fn unorphan_all_in_cluster(cluster_id: Uuid) -> DbResult<Vec<Self>> {
+        let orphaned = deployments::table
+            .filter(deployments::cluster_id.eq(cluster_id))
+            .filter(deployments::status.eq(DeploymentStatus::Orphaned));
+        let mut conn = db_conn().await?;
+        let mut restored: Vec<Self> = diesel::update(
+            orphaned
+                .clone()
+                .filter(deployments::status_before_orphaned.is_not_null()),
+        )
+        .set((
+            deployments::status.eq(deployments::status_before_orphaned.assume_not_null()),
+            deployments::status_before_orphaned.eq(None::<String>),
+            deployments::reason.eq(None::<String>),
+        ))
+        .get_results(conn.deref_mut())
+        .await?;
+        restored.extend(
+            diesel::update(orphaned)
+                .set((
+                    deployments::status.eq(DeploymentStatus::Unknown),
+                    deployments::reason.eq(None::<String>),
+                ))
+                .get_results::<Self>(conn.deref_mut())
+                .await?,
+        );
+        Ok(restored)
+    }
+
     pub async fn find_by_cluster_ids(cluster_ids: Vec<Uuid>) -> DbResult<Vec<Self>> {
         Ok(deployments::table
             .filter(deployments::cluster_id.eq_any(cluster_ids))
@@ -459,6 +590,8 @@ pub struct NewDeployment {
     pub config: Option<serde_json::Value>,
     #[schema(required)]
     pub values_override: Option<serde_json::Value>,
+    #[serde(skip)]
+    pub status_before_orphaned: Option<DeploymentStatus>,
 }
 
 impl NewDeployment {
@@ -550,3 +683,76 @@ impl ChartExtActionTargetResolver for Deployment {
         ))?)
     }
 }
+
+/// Whether the inputs selecting a collection item are only referenced by the
+/// chart's secrets outputs. Anything else referencing them, such as values
+/// outputs, may turn the item into chart values.
+fn only_feeds_secrets(values_ui: &serde_json::Value, config: &serde_json::Value, id: &str) -> bool {
+    let Some(config) = config.as_object() else {
+        return false;
+    };
+    let inputs = config
+        .iter()
+        .filter(|(_, value)| match value {
+            serde_json::Value::String(value) => value == id,
+            serde_json::Value::Array(values) => values.iter().any(|value| value == id),
+            _ => false,
+        })
+        .map(|(input, _)| input.as_str())
+        .collect::<Vec<_>>();
+    let mut rest = values_ui.clone();
+    let Some(secrets) = rest
+        .pointer_mut("/outputs/secrets")
+        .map(serde_json::Value::take)
+    else {
+        return false;
+    };
+    inputs.iter().any(|input| references_input(&secrets, input))
+        && !inputs.iter().any(|input| references_input(&rest, input))
+}
+
+fn references_input(value: &serde_json::Value, input: &str) -> bool {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.get("input").and_then(serde_json::Value::as_str) == Some(input)
+                || map.values().any(|value| references_input(value, input))
+        }
+        serde_json::Value::Array(values) => {
+            values.iter().any(|value| references_input(value, input))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_only_feeds_secrets() {
+        let id = "b7a4ab3b-4c1c-4b62-9d8e-2d4b0c5d5e1a";
+        let config = json!({ "db_password": id, "replicas": 2 });
+        let secret_output = json!({
+            "db": { "password": { "FieldValue": { "input": "db_password", "property": "contents" } } },
+        });
+        let values_output = json!([{
+            "path": ["db", "password"],
+            "value": { "FieldValue": { "input": "db_password", "property": "contents" } },
+        }]);
+
+        let secrets_only = json!({ "outputs": { "values": [], "secrets": secret_output } });
+        assert!(only_feeds_secrets(&secrets_only, &config, id));
+        assert!(!only_feeds_secrets(
+            &secrets_only,
+            &json!({ "db_password": "another" }),
+            id
+        ));
+
+        let both = json!({ "outputs": { "values": values_output, "secrets": secret_output } });
+        assert!(!only_feeds_secrets(&both, &config, id));
+
+        let values_only = json!({ "outputs": { "values": values_output } });
+        assert!(!only_feeds_secrets(&values_only, &config, id));
+    }
+}
@@ -0,0 +1,162 @@
+use super::deployment_permission::UserDeploymentRole;
+use crate::{AccessScope, DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_filter::DieselFilter;
+use diesel_json::Json;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    deployment_action_restrictions(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        env_id -> Uuid,
+        kind_id -> Uuid,
+        action_id -> Varchar,
+        allowed_roles -> Jsonb,
+        allowed_custom_role_ids -> Array<Uuid>,
+        allowed_user_ids -> Array<Uuid>,
+        allowed_bot_ids -> Array<Uuid>,
+    }
+}
+
+/// Restricts who may invoke an action on deployments of a kind in an env.
+/// Env admins may always invoke actions. Anyone else needs the invoke-action
+/// permission and to either be listed, or have one of the listed roles.
+#[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = deployment_action_restrictions)]
+pub struct DeploymentActionRestriction {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter]
+    pub env_id: Uuid,
+    #[filter]
+    pub kind_id: Uuid,
+    #[filter]
+    pub action_id: String,
+    /// Built-in deployment roles allowed to invoke the action
+    #[schema(value_type = Vec<UserDeploymentRole>)]
+    pub allowed_roles: Json<Vec<UserDeploymentRole>>,
+    /// Custom roles allowed to invoke the action, when bound in the env or
+    /// for the kind
+    pub allowed_custom_role_ids: Vec<Uuid>,
+    pub allowed_user_ids: Vec<Uuid>,
+    pub allowed_bot_ids: Vec<Uuid>,
+}
+
+impl DeploymentActionRestriction {
+    pub async fn all_filtered(
+        filters: DeploymentActionRestrictionFilters,
+        pagination: PaginationParams,
+        scope: &AccessScope,
+    ) -> DbResult<Paginated<Self>> {
+        let mut filtered = Self::filter(filters);
+        if let AccessScope::Envs { env_ids, .. } = scope {
+            filtered =
+                filtered.filter(deployment_action_restrictions::env_id.eq_any(env_ids.clone()));
+        }
+        Ok(filtered
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(deployment_action_restrictions::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Like [`Self::find`] but only returns the restriction if its environment
+    /// is within the identity's [`AccessScope`].
+    pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
+        match scope {
+            AccessScope::All => Self::find(id).await,
+            AccessScope::Envs { env_ids, .. } => Ok(deployment_action_restrictions::table
+                .find(id)
+                .filter(deployment_action_restrictions::env_id.eq_any(env_ids.clone()))
+                .get_result(db_conn().await?.deref_mut())
+                .await?),
+        }
+    }
+
+    pub async fn find_for_action(
+        env_id: Uuid,
+        kind_id: Uuid,
+        action_id: &str,
+    ) -> DbResult<Option<Self>> {
+        Ok(deployment_action_restrictions::table
+            .filter(deployment_action_restrictions::env_id.eq(env_id))
+            .filter(deployment_action_restrictions::kind_id.eq(kind_id))
+            .filter(deployment_action_restrictions::action_id.eq(action_id))
+            .get_result(db_conn().await?.deref_mut())
+            .await
+            .optional()?)
+    }
+
+    /// Whether a role binding allows invoking the action
+    pub fn allows_role(&self, role: UserDeploymentRole, custom_role_id: Option<Uuid>) -> bool {
+        match role {
+            UserDeploymentRole::Custom => custom_role_id.is_some_and(|custom_role_id| {
+                self.allowed_custom_role_ids.contains(&custom_role_id)
+            }),
+            role => self.allowed_roles.0.contains(&role),
+        }
+    }
+
+    pub async fn delete(&self) -> DbResult<()> {
+        diesel::delete(deployment_action_restrictions::table.find(self.id))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable, Deserialize, ToSchema)]
+#[diesel(table_name = deployment_action_restrictions)]
+pub struct NewDeploymentActionRestriction {
+    pub env_id: Uuid,
+    pub kind_id: Uuid,
+    pub action_id: String,
+    #[schema(value_type = Vec<UserDeploymentRole>)]
+    pub allowed_roles: Json<Vec<UserDeploymentRole>>,
+    pub allowed_custom_role_ids: Vec<Uuid>,
+    pub allowed_user_ids: Vec<Uuid>,
+    pub allowed_bot_ids: Vec<Uuid>,
+}
+
+impl NewDeploymentActionRestriction {
+    pub async fn insert(self) -> DbResult<DeploymentActionRestriction> {
+        Ok(diesel::insert_into(deployment_action_restrictions::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
+
+#[derive(Debug, AsChangeset, Deserialize, ToSchema)]
+#[diesel(table_name = deployment_action_restrictions)]
+pub struct UpdateDeploymentActionRestriction {
+    #[schema(value_type = Option<Vec<UserDeploymentRole>>)]
+    pub allowed_roles: Option<Json<Vec<UserDeploymentRole>>>,
+    pub allowed_custom_role_ids: Option<Vec<Uuid>>,
+    pub allowed_user_ids: Option<Vec<Uuid>>,
+    pub allowed_bot_ids: Option<Vec<Uuid>>,
+}
+
+impl UpdateDeploymentActionRestriction {
+    pub async fn save(self, id: Uuid) -> DbResult<DeploymentActionRestriction> {
+        Ok(
+            diesel::update(deployment_action_restrictions::table.find(id))
+                .set(self)
+                .get_result(db_conn().await?.deref_mut())
+                .await?,
+        )
+    }
+}
@@ -0,0 +1,132 @@
+use super::deployment_permission::UserDeploymentRole;
+use crate::{AccessScope, DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_filter::DieselFilter;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    deployment_bot_permissions(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        env_id -> Uuid,
+        bot_id -> Uuid,
+        kind_id -> Uuid,
+        role -> Varchar,
+        custom_role_id -> Nullable<Uuid>,
+        deployment_id -> Nullable<Uuid>,
+    }
+}
+
+/// A deployment role bound to a bot for a kind in an env, see
+/// [`UserDeploymentRole`].
+#[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = deployment_bot_permissions)]
+pub struct DeploymentBotPermission {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter]
+    pub env_id: Uuid,
+    #[filter]
+    pub bot_id: Uuid,
+    pub kind_id: Uuid,
+    pub role: UserDeploymentRole,
+    #[schema(required)]
+    pub custom_role_id: Option<Uuid>,
+    /// When set, the permission only applies to this deployment instead of all
+    /// deployments of the kind in the env
+    #[schema(required)]
+    pub deployment_id: Option<Uuid>,
+}
+
+impl DeploymentBotPermission {
+    pub async fn all_filtered(
+        filters: DeploymentBotPermissionFilters,
+        pagination: PaginationParams,
+        scope: &AccessScope,
+    ) -> DbResult<Paginated<Self>> {
+        let mut filtered = Self::filter(filters);
+        if let AccessScope::Envs { env_ids, .. } = scope {
+            filtered = filtered.filter(deployment_bot_permissions::env_id.eq_any(env_ids.clone()));
+        }
+        Ok(filtered
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(deployment_bot_permissions::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Like [`Self::find`] but only returns the permission if its environment is
+    /// within the identity's [`AccessScope`].
+    pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
+        match scope {
+            AccessScope::All => Self::find(id).await,
+            AccessScope::Envs { env_ids, .. } => Ok(deployment_bot_permissions::table
+                .find(id)
+                .filter(deployment_bot_permissions::env_id.eq_any(env_ids.clone()))
+                .get_result(db_conn().await?.deref_mut())
+                .await?),
+        }
+    }
+
+    /// Permissions of the bot for the kind in the env, and for the given
+    /// deployment if any
+    pub async fn find_bot_permissions(
+        env_id: Uuid,
+        bot_id: Uuid,
+        kind_id: Uuid,
+        deployment_id: Option<Uuid>,
+    ) -> DbResult<Vec<Self>> {
+        Ok(deployment_bot_permissions::table
+            .filter(deployment_bot_permissions::env_id.eq(env_id))
+            .filter(deployment_bot_permissions::bot_id.eq(bot_id))
+            .filter(deployment_bot_permissions::kind_id.eq(kind_id))
+            .filter(
+                deployment_bot_permissions::deployment_id
+                    .is_null()
+                    .or(deployment_bot_permissions::deployment_id.eq(deployment_id)),
+            )
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn delete(&self) -> DbResult<()> {
+        diesel::delete(deployment_bot_permissions::table.find(self.id))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Insertable, Deserialize, ToSchema)]
+#[diesel(table_name = deployment_bot_permissions)]
+pub struct NewDeploymentBotPermission {
+    pub env_id: Uuid,
+    pub bot_id: Uuid,
+    pub kind_id: Uuid,
+    pub role: UserDeploymentRole,
+    #[serde(default)]
+    pub custom_role_id: Option<Uuid>,
+    #[serde(default)]
+    pub deployment_id: Option<Uuid>,
+}
+
+impl NewDeploymentBotPermission {
+    pub async fn insert(self) -> DbResult<DeploymentBotPermission> {
+        Ok(diesel::insert_into(deployment_bot_permissions::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{AccessScope, DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_filter::DieselFilter;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    deployment_env_grants(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        kind_id -> Uuid,
+        source_env_id -> Uuid,
+        target_env_id -> Uuid,
+    }
+}
+
+/// Allows deployments of a kind in the source env to access the target env
+/// using their deployment credentials. Without a grant, deployments can only
+/// access their own env.
+#[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = deployment_env_grants)]
+pub struct DeploymentEnvGrant {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter]
+    pub kind_id: Uuid,
+    #[filter]
+    pub source_env_id: Uuid,
+    #[filter]
+    pub target_env_id: Uuid,
+}
+
+impl DeploymentEnvGrant {
+    pub async fn all_filtered(
+        filters: DeploymentEnvGrantFilters,
+        pagination: PaginationParams,
+        scope: &AccessScope,
+    ) -> DbResult<Paginated<Self>> {
+        let mut filtered = Self::filter(filters);
+        if let AccessScope::Envs { env_ids, .. } = scope {
+            filtered = filtered.filter(
+                deployment_env_grants::source_env_id
+                    .eq_any(env_ids.clone())
+                    .or(deployment_env_grants::target_env_id.eq_any(env_ids.clone())),
+            );
+        }
+        Ok(filtered
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(deployment_env_grants::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Like [`Self::find`] but only returns the grant if its source or target
+    /// env is within the identity's [`AccessScope`].
+    pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
+        match scope {
+            AccessScope::All => Self::find(id).await,
+            AccessScope::Envs { env_ids, .. } => Ok(deployment_env_grants::table
+                .find(id)
+                .filter(
+                    deployment_env_grants::source_env_id
+                        .eq_any(env_ids.clone())
+                        .or(deployment_env_grants::target_env_id.eq_any(env_ids.clone())),
+                )
+                .get_result(db_conn().await?.deref_mut())
+                .await?),
+        }
+    }
+
+    /// Envs that deployments of the given kind in the source env were granted
+    /// access to, not including the source env itself.
+    pub async fn target_env_ids(kind_id: Uuid, source_env_id: Uuid) -> DbResult<Vec<Uuid>> {
+        Ok(deployment_env_grants::table
+            .filter(deployment_env_grants::kind_id.eq(kind_id))
+            .filter(deployment_env_grants::source_env_id.eq(source_env_id))
+            .select(deployment_env_grants::target_env_id)
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn delete(&self) -> DbResult<()> {
+        diesel::delete(deployment_env_grants::table.find(self.id))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Insertable, Deserialize, ToSchema)]
+#[diesel(table_name = deployment_env_grants)]
+pub struct NewDeploymentEnvGrant {
+    pub kind_id: Uuid,
+    pub source_env_id: Uuid,
+    pub target_env_id: Uuid,
+}
+
+impl NewDeploymentEnvGrant {
+    pub async fn insert(self) -> DbResult<DeploymentEnvGrant> {
+        Ok(diesel::insert_into(deployment_env_grants::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
@@ -1,3 +1,4 @@
+use super::custom_role::RolePermission;
 use crate::{AccessScope, DbResult, db_conn};
 use chrono::prelude::*;
 use diesel::prelude::*;
@@ -19,6 +20,9 @@ table! {
         user_id -> Uuid,
         kind_id -> Uuid,
         role -> Varchar,
+        custom_role_id -> Nullable<Uuid>,
+        deployment_id -> Nullable<Uuid>,
+        expires_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -41,6 +45,42 @@ pub enum UserDeploymentRole {
     Owner,
     /// Maintainers can edit deployments but not create or delete them.
     Maintainer,
+    /// Viewers can only see deployments.
+    Viewer,
+    /// The permissions of the custom role set in `custom_role_id`.
+    Custom,
+}
+
+impl UserDeploymentRole {
+    /// Permissions granted by built-in roles, or `None` for custom roles
+    pub fn built_in_permissions(&self) -> Option<Vec<RolePermission>> {
+        match self {
+            Self::Owner => Some(vec![
+                RolePermission::Read,
+                RolePermission::Create,
+                RolePermission::UpdateConfig,
+                RolePermission::UpgradeChart,
+                RolePermission::InvokeAction,
+                RolePermission::Restart,
+                RolePermission::Delete,
+            ]),
+            Self::Maintainer => Some(Self::maintainer_permissions()),
+            Self::Viewer => Some(vec![RolePermission::Read]),
+            Self::Custom => None,
+        }
+    }
+
+    /// Permissions of the built-in maintainer role, also given to
+    /// unrestricted bots and to deployments within their own env
+    pub fn maintainer_permissions() -> Vec<RolePermission> {
+        vec![
+            RolePermission::Read,
+            RolePermission::UpdateConfig,
+            RolePermission::UpgradeChart,
+            RolePermission::InvokeAction,
+            RolePermission::Restart,
+        ]
+    }
 }
 
 #[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
@@ -53,6 +93,15 @@ pub struct DeploymentPermission {
     pub user_id: Uuid,
     pub kind_id: Uuid,
     pub role: UserDeploymentRole,
+    #[schema(required)]
+    pub custom_role_id: Option<Uuid>,
+    /// When set, the permission only applies to this deployment instead of all
+    /// deployments of the kind in the env
+    #[schema(required)]
+    pub deployment_id: Option<Uuid>,
+    /// Temporary permissions are ignored once expired, and then deleted
+    #[schema(required)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 impl DeploymentPermission {
@@ -68,7 +117,7 @@ impl DeploymentPermission {
         scope: &AccessScope,
     ) -> DbResult<Paginated<Self>> {
         let mut filtered = Self::filter(filters);
-        if let AccessScope::Envs(env_ids) = scope {
+        if let AccessScope::Envs { env_ids, .. } = scope {
             filtered = filtered.filter(deployment_permissions::env_id.eq_any(env_ids.clone()));
         }
         Ok(filtered
@@ -89,7 +138,7 @@ impl DeploymentPermission {
     pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
         match scope {
             AccessScope::All => Self::find(id).await,
-            AccessScope::Envs(env_ids) => Ok(deployment_permissions::table
+            AccessScope::Envs { env_ids, .. } => Ok(deployment_permissions::table
                 .find(id)
                 .filter(deployment_permissions::env_id.eq_any(env_ids.clone()))
                 .get_result(db_conn().await?.deref_mut())
@@ -97,19 +146,40 @@ impl DeploymentPermission {
         }
     }
 
-    pub async fn find_user_role(
+    /// Unexpired permissions of the user for the kind in the env, and for the
+    /// given deployment if any
+    pub async fn find_user_permissions(
         env_id: Uuid,
         user_id: Uuid,
         kind_id: Uuid,
-    ) -> DbResult<Option<UserDeploymentRole>> {
+        deployment_id: Option<Uuid>,
+    ) -> DbResult<Vec<Self>> {
         Ok(deployment_permissions::table
             .filter(deployment_permissions::env_id.eq(env_id))
             .filter(deployment_permissions::user_id.eq(user_id))
             .filter(deployment_permissions::kind_id.eq(kind_id))
-            .get_result::<Self>(db_conn().await?.deref_mut())
-            .await
-            .optional()?
-            .map(|p| p.role))
+            .filter(
+                deployment_permissions::deployment_id
+                    .is_null()
+                    .or(deployment_permissions::deployment_id.eq(deployment_id)),
+            )
+            .filter(
+                deployment_permissions::expires_at
+                    .is_null()
+                    .or(deployment_permissions::expires_at.gt(diesel::dsl::now)),
+            )
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Deletes all expired permissions, returning them
+    pub async fn delete_expired() -> DbResult<Vec<Self>> {
+        Ok(diesel::delete(
+            deployment_permissions::table
+                .filter(deployment_permissions::expires_at.le(diesel::dsl::now)),
+        )
+        .get_results(db_conn().await?.deref_mut())
+        .await?)
     }
 
     pub async fn delete(&self) -> DbResult<()> {
@@ -127,6 +197,12 @@ pub struct NewDeploymentPermission {
     pub user_id: Uuid,
     pub kind_id: Uuid,
     pub role: UserDeploymentRole,
+    #[serde(default)]
+    pub custom_role_id: Option<Uuid>,
+    #[serde(default)]
+    pub deployment_id: Option<Uuid>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 impl NewDeploymentPermission {
@@ -1,5 +1,8 @@
 use super::{deployment::Deployment, deployment_resource_type::DeploymentResourceType};
-use crate::{AccessScope, DbError, DbResult, db_conn};
+use crate::{
+    AccessScope, DbError, DbResult, db_conn,
+    encryption::{self, EncryptionError, Sealed, master_keys},
+};
 use chrono::prelude::*;
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
@@ -25,6 +28,9 @@ table! {
         props -> Jsonb,
         sync_status -> Varchar,
         sync_reason -> Nullable<Varchar>,
+        sensitive_props -> Nullable<Varchar>,
+        data_key -> Nullable<Varchar>,
+        key_id -> Nullable<Varchar>,
     }
 }
 
@@ -60,10 +66,20 @@ pub struct DeploymentResource {
     pub deployment_id: Option<Uuid>,
     pub name: String,
     pub exists: bool,
+    /// All props, including sensitive ones which are decrypted when loaded
     pub props: serde_json::Value,
     pub sync_status: DeploymentResourceSyncStatus,
     #[schema(required)]
     pub sync_reason: Option<String>,
+    /// Sensitive props as stored, encrypted with `data_key`
+    #[serde(skip)]
+    pub sensitive_props: Option<String>,
+    #[serde(skip)]
+    pub data_key: Option<String>,
+    /// The master key sensitive props are encrypted with, or `None` if they
+    /// are stored in `props` in plaintext
+    #[schema(required)]
+    pub key_id: Option<String>,
 }
 
 #[derive(Debug, Default, Deserialize, ToSchema)]
@@ -72,11 +88,78 @@ pub struct DeploymentResourceExtraFilters {
     env_id: Option<Uuid>,
 }
 
+/// Moves sensitive props out of `props` and encrypts them, when encryption is
+/// configured. Returns the remaining props.
+async fn seal_props(
+    type_id: Uuid,
+    mut props: serde_json::Value,
+) -> DbResult<(serde_json::Value, Option<Sealed>)> {
+    if master_keys()?.is_none() {
+        return Ok((props, None));
+    }
+    let Some(map) = props.as_object_mut() else {
+        return Ok((props, None));
+    };
+    let sensitive = DeploymentResourceType::find(type_id)
+        .await?
+        .spec()?
+        .values_ui
+        .inputs
+        .into_iter()
+        .filter(|input| input.sensitive)
+        .filter_map(|input| map.remove_entry(&input.id))
+        .collect::<serde_json::Map<_, _>>();
+    if sensitive.is_empty() {
+        return Ok((props, None));
+    }
+    let sealed = encryption::seal(serde_json::Value::Object(sensitive).to_string())?;
+    Ok((props, Some(sealed)))
+}
+
+fn sealed_columns(
+    sealed: Option<Sealed>,
+) -> (
+    diesel::dsl::Eq<deployment_resources::sensitive_props, Option<String>>,
+    diesel::dsl::Eq<deployment_resources::data_key, Option<String>>,
+    diesel::dsl::Eq<deployment_resources::key_id, Option<String>>,
+) {
+    let (sensitive_props, data_key, key_id) = match sealed {
+        Some(sealed) => (Some(sealed.value), sealed.data_key, sealed.key_id),
+        None => (None, None, None),
+    };
+    (
+        deployment_resources::sensitive_props.eq(sensitive_props),
+        deployment_resources::data_key.eq(data_key),
+        deployment_resources::key_id.eq(key_id),
+    )
+}
+
 impl DeploymentResource {
+    /// Decrypts sensitive props and merges them back into `props`
+    fn decrypted(mut self) -> DbResult<Self> {
+        if let Some(sensitive_props) = self.sensitive_props.as_deref() {
+            let sensitive = encryption::open(
+                sensitive_props,
+                self.data_key.as_deref(),
+                self.key_id.as_deref(),
+            )?;
+            let sensitive = serde_json::from_str::<serde_json::Value>(&sensitive)
+                .map_err(|_| EncryptionError::DecryptFailed)?;
+            merge(&mut self.props, &sensitive);
+        }
+        Ok(self)
+    }
+
+    fn all_decrypted(resources: Vec<Self>) -> DbResult<Vec<Self>> {
+        resources.into_iter().map(Self::decrypted).collect()
+    }
+
     pub async fn all() -> DbResult<Vec<Self>> {
-        Ok(deployment_resources::table
-            .get_results(db_conn().await?.deref_mut())
-            .await?)
+        Self::all_decrypted(
+            deployment_resources::table
+                .get_results(db_conn().await?.deref_mut())
+                .await?,
+        )
     }
 
     pub async fn all_filtered(
@@ -95,21 +178,30 @@ impl DeploymentResource {
         // A resource is visible if its owning deployment is in an accessible
         // environment. Resources with no deployment are not env-scoped and are
         // hidden from restricted users.
-        if let AccessScope::Envs(env_ids) = scope {
-            let deployment_ids = Deployment::ids_in_envs(env_ids).await?;
-            filtered = filtered.filter(deployment_resources::deployment_id.eq_any(deployment_ids));
+        if let AccessScope::Envs {
+            env_ids,
+            deployment_ids,
+        } = scope
+        {
+            let mut visible_deployment_ids = Deployment::ids_in_envs(env_ids).await?;
+            visible_deployment_ids.extend(deployment_ids);
+            filtered =
+                filtered.filter(deployment_resources::deployment_id.eq_any(visible_deployment_ids));
         }
-        Ok(filtered
+        let mut paginated: Paginated<Self> = filtered
             .paginate(pagination)
             .load_and_count(db_conn().await?.deref_mut())
-            .await?)
+            .await?;
+        paginated.items = Self::all_decrypted(paginated.items)?;
+        Ok(paginated)
     }
 
     pub async fn find(id: Uuid) -> DbResult<Self> {
-        Ok(deployment_resources::table
+        deployment_resources::table
             .find(id)
-            .get_result(db_conn().await?.deref_mut())
-            .await?)
+            .get_result::<Self>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
     }
 
     /// Like [`Self::find`] but only returns the resource if its owning
@@ -117,30 +209,96 @@ impl DeploymentResource {
     pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
         match scope {
             AccessScope::All => Self::find(id).await,
-            AccessScope::Envs(env_ids) => {
-                let deployment_ids = Deployment::ids_in_envs(env_ids).await?;
-                Ok(deployment_resources::table
+            AccessScope::Envs {
+                env_ids,
+                deployment_ids,
+            } => {
+                let mut visible_deployment_ids = Deployment::ids_in_envs(env_ids).await?;
+                visible_deployment_ids.extend(deployment_ids);
+                deployment_resources::table
                     .find(id)
-                    .filter(deployment_resources::deployment_id.eq_any(deployment_ids))
-                    .get_result(db_conn().await?.deref_mut())
-                    .await?)
+                    .filter(deployment_resources::deployment_id.eq_any(visible_deployment_ids))
+                    .get_result::<Self>(db_conn().await?.deref_mut())
+                    .await?
+                    .decrypted()
             }
         }
     }
 
     pub async fn find_by_type(type_id: Uuid) -> DbResult<Vec<Self>> {
-        Ok(deployment_resources::table
-            .filter(deployment_resources::type_id.eq(type_id))
-            .get_results(db_conn().await?.deref_mut())
-            .await?)
+        Self::all_decrypted(
+            deployment_resources::table
+                .filter(deployment_resources::type_id.eq(type_id))
+                .get_results(db_conn().await?.deref_mut())
+                .await?,
+        )
     }
 
     pub async fn find_of_type(type_id: Uuid, id: Uuid) -> DbResult<Self> {
-        Ok(deployment_resources::table
+        deployment_resources::table
             .filter(deployment_resources::type_id.eq(type_id))
             .find(id)
-            .get_result(db_conn().await?.deref_mut())
-            .await?)
+            .get_result::<Self>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
+    }
+
+    /// Wraps the data key of every resource not encrypted with the current
+    /// master key with it, and encrypts sensitive props stored in plaintext.
+    /// Each resource is updated separately, only if it wasn't changed in the
+    /// meantime. Returns the number of updated resources.
+    pub async fn rewrap_all() -> DbResult<usize> {
+        let Some(keys) = master_keys()? else {
+            return Ok(0);
+        };
+        let current_key_id = keys.current_key_id();
+        let stale = deployment_resources::table
+            .filter(deployment_resources::key_id.is_distinct_from(current_key_id))
+            .select((
+                deployment_resources::id,
+                deployment_resources::type_id,
+                deployment_resources::props,
+                deployment_resources::data_key,
+                deployment_resources::key_id,
+            ))
+            .get_results::<(
+                Uuid,
+                Uuid,
+                serde_json::Value,
+                Option<String>,
+                Option<String>,
+            )>(db_conn().await?.deref_mut())
+            .await?;
+        let mut updated = 0;
+        for (id, type_id, props, data_key, key_id) in stale {
+            let unchanged = deployment_resources::table
+                .find(id)
+                .filter(deployment_resources::key_id.is_not_distinct_from(key_id.clone()));
+            updated += match (data_key, key_id.as_deref()) {
+                (Some(data_key), Some(old_key_id)) => {
+                    diesel::update(unchanged)
+                        .set((
+                            deployment_resources::data_key.eq(keys.rewrap(&data_key, old_key_id)?),
+                            deployment_resources::key_id.eq(current_key_id),
+                        ))
+                        .execute(db_conn().await?.deref_mut())
+                        .await?
+                }
+                _ => match seal_props(type_id, props).await? {
+                    (props, Some(sealed)) => {
+                        diesel::update(unchanged)
+                            .set((
+                                deployment_resources::props.eq(props),
+                                sealed_columns(Some(sealed)),
+                            ))
+                            .execute(db_conn().await?.deref_mut())
+                            .await?
+                    }
+                    (_, None) => 0,
+                },
+            };
+        }
+        Ok(updated)
     }
 
     pub async fn without_sensitive_props(mut self) -> DbResult<Self> {
@@ -196,10 +354,12 @@ pub struct NewDeploymentResource {
 
 impl NewDeploymentResource {
     pub async fn insert(self) -> DbResult<DeploymentResource> {
-        Ok(diesel::insert_into(deployment_resources::table)
-            .values(self)
-            .get_result(db_conn().await?.deref_mut())
-            .await?)
+        let (props, sealed) = seal_props(self.type_id, self.props).await?;
+        diesel::insert_into(deployment_resources::table)
+            .values((Self { props, ..self }, sealed_columns(sealed)))
+            .get_result::<DeploymentResource>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
     }
 }
 
@@ -233,18 +393,22 @@ impl UpdateDeploymentResource {
                 let current = DeploymentResource::find(id).await?;
                 let mut props = current.props;
                 merge(&mut props, &updates);
-                Some(props)
+                Some(seal_props(current.type_id, props).await?)
             }
         };
-        Ok(
-            diesel::update(deployment_resources::table.filter(deployment_resources::id.eq(id)))
-                .set((
-                    self.name.map(|name| deployment_resources::name.eq(name)),
-                    props.map(|props| deployment_resources::props.eq(props)),
-                ))
-                .get_result(db_conn().await?.deref_mut())
-                .await?,
-        )
+        diesel::update(deployment_resources::table.filter(deployment_resources::id.eq(id)))
+            .set((
+                self.name.map(|name| deployment_resources::name.eq(name)),
+                props.map(|(props, sealed)| {
+                    (
+                        deployment_resources::props.eq(props),
+                        sealed_columns(sealed),
+                    )
+                }),
+            ))
+            .get_result::<DeploymentResource>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
     }
 }
 
@@ -256,12 +420,11 @@ pub struct UpdateDeploymentResourceExists {
 
 impl UpdateDeploymentResourceExists {
     pub async fn save(self, id: Uuid) -> DbResult<DeploymentResource> {
-        Ok(
-            diesel::update(deployment_resources::table.filter(deployment_resources::id.eq(id)))
-                .set(self)
-                .get_result(db_conn().await?.deref_mut())
-                .await?,
-        )
+        diesel::update(deployment_resources::table.filter(deployment_resources::id.eq(id)))
+            .set(self)
+            .get_result::<DeploymentResource>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
     }
 }
 
@@ -274,11 +437,10 @@ pub struct UpdateDeploymentResourceSyncStatus {
 
 impl UpdateDeploymentResourceSyncStatus {
     pub async fn save(self, id: Uuid) -> DbResult<DeploymentResource> {
-        Ok(
-            diesel::update(deployment_resources::table.filter(deployment_resources::id.eq(id)))
-                .set(self)
-                .get_result(db_conn().await?.deref_mut())
-                .await?,
-        )
+        diesel::update(deployment_resources::table.filter(deployment_resources::id.eq(id)))
+            .set(self)
+            .get_result::<DeploymentResource>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
     }
 }
@@ -35,6 +35,20 @@ impl DeploymentReportedStatus {
             error: Some(error.to_string()),
         }
     }
+
+    /// Whether the deployment reported a status shown in red
+    pub fn is_danger(&self) -> bool {
+        matches!(
+            self.content,
+            Some(DeploymentReportedStatusContent {
+                status: DeploymentReportedStatusSummary {
+                    color: DeploymentReportedStatusColor::Danger,
+                    ..
+                },
+                ..
+            })
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -37,6 +37,7 @@ table! {
         operation -> Jsonb,
         status -> Varchar,
         reason -> Nullable<Varchar>,
+        traceparent -> Nullable<Varchar>,
     }
 }
 
@@ -94,6 +95,9 @@ pub struct DeploymentTask {
     pub status: DeploymentTaskStatus,
     #[schema(required)]
     pub reason: Option<String>,
+    /// W3C trace context of the request that created the task
+    #[schema(required)]
+    pub traceparent: Option<String>,
 }
 
 #[derive(Debug, Default, Deserialize, ToSchema)]
@@ -139,9 +143,17 @@ impl DeploymentTask {
             None
         };
         let mut filtered = Self::filter(filters);
-        if let AccessScope::Envs(env_ids) = scope {
+        if let AccessScope::Envs {
+            env_ids,
+            deployment_ids,
+        } = scope
+        {
             let cluster_ids = K8sCluster::ids_in_envs(env_ids).await?;
-            filtered = filtered.filter(deployment_tasks::cluster_id.eq_any(cluster_ids));
+            filtered = filtered.filter(
+                deployment_tasks::cluster_id
+                    .eq_any(cluster_ids)
+                    .or(deployment_tasks::deployment_id.eq_any(deployment_ids.clone())),
+            );
         }
         if extra_filters.active_only.unwrap_or(false) {
             filtered = filtered.filter(
@@ -174,6 +186,18 @@ impl DeploymentTask {
             .await?)
     }
 
+    /// Tasks of the given deployments created at or after the given time
+    pub async fn find_by_deployment_ids_since(
+        deployment_ids: Vec<Uuid>,
+        created_from: DateTime<Utc>,
+    ) -> DbResult<Vec<Self>> {
+        Ok(deployment_tasks::table
+            .filter(deployment_tasks::deployment_id.eq_any(deployment_ids))
+            .filter(deployment_tasks::created_at.ge(created_from))
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
     pub async fn find(id: Uuid) -> DbResult<Self> {
         Ok(deployment_tasks::table
             .find(id)
@@ -187,11 +211,18 @@ impl DeploymentTask {
     pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
         match scope {
             AccessScope::All => Self::find(id).await,
-            AccessScope::Envs(env_ids) => {
+            AccessScope::Envs {
+                env_ids,
+                deployment_ids,
+            } => {
                 let cluster_ids = K8sCluster::ids_in_envs(env_ids).await?;
                 Ok(deployment_tasks::table
                     .find(id)
-                    .filter(deployment_tasks::cluster_id.eq_any(cluster_ids))
+                    .filter(
+                        deployment_tasks::cluster_id
+                            .eq_any(cluster_ids)
+                            .or(deployment_tasks::deployment_id.eq_any(deployment_ids.clone())),
+                    )
                     .get_result(db_conn().await?.deref_mut())
                     .await?)
             }
@@ -235,6 +266,22 @@ impl DeploymentTask {
         .await
     }
 
+    /// Fails all pending tasks of a cluster, e.g. when it goes missing
+    pub async fn fail_pending_in_cluster(cluster_id: Uuid, reason: String) -> DbResult<Vec<Self>> {
+        Ok(diesel::update(
+            deployment_tasks::table
+                .filter(deployment_tasks::cluster_id.eq(cluster_id))
+                .filter(deployment_tasks::status.eq(DeploymentTaskStatus::Pending)),
+        )
+        .set((
+            deployment_tasks::status.eq(DeploymentTaskStatus::Failed),
+            deployment_tasks::reason.eq(reason),
+            deployment_tasks::finished_at.eq(diesel::dsl::now),
+        ))
+        .get_results(db_conn().await?.deref_mut())
+        .await?)
+    }
+
     pub async fn helm_chart(&self) -> DbResult<HelmChart> {
         let helm_chart_id = match &self.operation {
             Json(DeploymentTaskOperation::Install(params)) => params.helm_chart_id,
@@ -312,7 +359,10 @@ pub struct NewDeploymentTask {
 impl NewDeploymentTask {
     pub async fn insert(self) -> DbResult<DeploymentTask> {
         Ok(diesel::insert_into(deployment_tasks::table)
-            .values(self)
+            .values((
+                self,
+                deployment_tasks::traceparent.eq(platz_otel::current_traceparent()),
+            ))
             .get_result(db_conn().await?.deref_mut())
             .await?)
     }
@@ -339,11 +389,12 @@ impl UpdateDeploymentTask {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, AsRefStr, ToSchema)]
 pub enum DeploymentTaskOperation {
     Install(DeploymentInstallTask),
     Upgrade(DeploymentUpgradeTask),
     Reinstall(DeploymentReinstallTask),
+    RefreshSecrets(DeploymentRefreshSecretsTask),
     Recreate(DeploymentRecreaseTask),
     Uninstall(DeploymentUninstallTask),
     InvokeAction(DeploymentInvokeActionTask),
@@ -454,6 +505,39 @@ impl DeploymentTask {
     }
 }
 
+/// Re-applies the deployment's Kubernetes secrets whose contents changed and
+/// restarts the workloads using them, without running helm. Falls back to a
+/// reinstall when that's not possible.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeploymentRefreshSecretsTask {
+    pub reason: String,
+}
+
+impl DeploymentTask {
+    pub async fn create_refresh_secrets_task<I>(
+        deployment: &Deployment,
+        identity: &I,
+        reason: String,
+    ) -> DbResult<Self>
+    where
+        I: std::borrow::Borrow<Identity>,
+    {
+        NewDeploymentTask {
+            cluster_id: deployment.cluster_id,
+            deployment_id: deployment.id,
+            acting_user_id: identity.borrow().user_id(),
+            acting_deployment_id: identity.borrow().deployment_id(),
+            operation: Json(DeploymentTaskOperation::RefreshSecrets(
+                DeploymentRefreshSecretsTask { reason },
+            )),
+            status: Default::default(),
+            execute_at: None,
+        }
+        .insert()
+        .await
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DeploymentRecreaseTask {
     pub old_cluster_id: Uuid,
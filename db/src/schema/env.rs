@@ -47,7 +47,7 @@ impl Env {
         scope: &AccessScope,
     ) -> DbResult<Paginated<Self>> {
         let mut filtered = Self::filter(filters);
-        if let AccessScope::Envs(env_ids) = scope {
+        if let AccessScope::Envs { env_ids, .. } = scope {
             filtered = filtered.filter(envs::id.eq_any(env_ids.clone()));
         }
         Ok(filtered
@@ -63,6 +63,14 @@ impl Env {
             .await?)
     }
 
+    pub async fn find_by_name(name: &str) -> DbResult<Option<Self>> {
+        Ok(envs::table
+            .filter(envs::name.eq(name))
+            .first(db_conn().await?.deref_mut())
+            .await
+            .optional()?)
+    }
+
     /// Like [`Self::find`] but only returns the env if it is within the
     /// identity's [`AccessScope`]. Out-of-scope and missing both yield
     /// `NotFound`.
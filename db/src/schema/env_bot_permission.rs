@@ -0,0 +1,109 @@
+use super::env_user_permission::EnvUserRole;
+use crate::{AccessScope, DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_filter::DieselFilter;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    env_bot_permissions(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        env_id -> Uuid,
+        bot_id -> Uuid,
+        role -> Varchar,
+        custom_role_id -> Nullable<Uuid>,
+    }
+}
+
+/// An env role bound to a bot, see [`EnvUserRole`].
+#[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = env_bot_permissions)]
+pub struct EnvBotPermission {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter]
+    pub env_id: Uuid,
+    #[filter]
+    pub bot_id: Uuid,
+    pub role: EnvUserRole,
+    #[schema(required)]
+    pub custom_role_id: Option<Uuid>,
+}
+
+impl EnvBotPermission {
+    pub async fn all_filtered(
+        filters: EnvBotPermissionFilters,
+        pagination: PaginationParams,
+        scope: &AccessScope,
+    ) -> DbResult<Paginated<Self>> {
+        let mut filtered = Self::filter(filters);
+        if let AccessScope::Envs { env_ids, .. } = scope {
+            filtered = filtered.filter(env_bot_permissions::env_id.eq_any(env_ids.clone()));
+        }
+        Ok(filtered
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(env_bot_permissions::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Like [`Self::find`] but only returns the permission if its environment is
+    /// within the identity's [`AccessScope`].
+    pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
+        match scope {
+            AccessScope::All => Self::find(id).await,
+            AccessScope::Envs { env_ids, .. } => Ok(env_bot_permissions::table
+                .find(id)
+                .filter(env_bot_permissions::env_id.eq_any(env_ids.clone()))
+                .get_result(db_conn().await?.deref_mut())
+                .await?),
+        }
+    }
+
+    pub async fn find_bot_permission_in_env(env_id: Uuid, bot_id: Uuid) -> DbResult<Option<Self>> {
+        Ok(env_bot_permissions::table
+            .filter(env_bot_permissions::env_id.eq(env_id))
+            .filter(env_bot_permissions::bot_id.eq(bot_id))
+            .get_result(db_conn().await?.deref_mut())
+            .await
+            .optional()?)
+    }
+
+    pub async fn delete(&self) -> DbResult<()> {
+        diesel::delete(env_bot_permissions::table.find(self.id))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Insertable, Deserialize, ToSchema)]
+#[diesel(table_name = env_bot_permissions)]
+pub struct NewEnvBotPermission {
+    pub env_id: Uuid,
+    pub bot_id: Uuid,
+    pub role: EnvUserRole,
+    #[serde(default)]
+    pub custom_role_id: Option<Uuid>,
+}
+
+impl NewEnvBotPermission {
+    pub async fn insert(self) -> DbResult<EnvBotPermission> {
+        Ok(diesel::insert_into(env_bot_permissions::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
@@ -1,3 +1,4 @@
+use super::custom_role::RolePermission;
 use crate::{AccessScope, DbResult, db_conn};
 use chrono::prelude::*;
 use diesel::prelude::*;
@@ -18,6 +19,8 @@ table! {
         env_id -> Uuid,
         user_id -> Uuid,
         role -> Varchar,
+        custom_role_id -> Nullable<Uuid>,
+        expires_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -35,8 +38,23 @@ table! {
     ToSchema,
 )]
 pub enum EnvUserRole {
+    /// Env admins have all permissions in the env and manage its permissions
     Admin,
+    /// Env users can see the env and its deployments
     User,
+    /// The permissions of the custom role set in `custom_role_id`
+    Custom,
+}
+
+impl EnvUserRole {
+    /// Permissions granted by built-in roles, or `None` for custom roles
+    pub fn built_in_permissions(&self) -> Option<Vec<RolePermission>> {
+        match self {
+            Self::Admin => Some(RolePermission::all()),
+            Self::User => Some(vec![RolePermission::Read]),
+            Self::Custom => None,
+        }
+    }
 }
 
 #[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
@@ -48,6 +66,11 @@ pub struct EnvUserPermission {
     pub env_id: Uuid,
     pub user_id: Uuid,
     pub role: EnvUserRole,
+    #[schema(required)]
+    pub custom_role_id: Option<Uuid>,
+    /// Temporary permissions are ignored once expired, and then deleted
+    #[schema(required)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 impl EnvUserPermission {
@@ -63,7 +86,7 @@ impl EnvUserPermission {
         scope: &AccessScope,
     ) -> DbResult<Paginated<Self>> {
         let mut filtered = Self::filter(filters);
-        if let AccessScope::Envs(env_ids) = scope {
+        if let AccessScope::Envs { env_ids, .. } = scope {
             filtered = filtered.filter(env_user_permissions::env_id.eq_any(env_ids.clone()));
         }
         Ok(filtered
@@ -84,7 +107,7 @@ impl EnvUserPermission {
     pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
         match scope {
             AccessScope::All => Self::find(id).await,
-            AccessScope::Envs(env_ids) => Ok(env_user_permissions::table
+            AccessScope::Envs { env_ids, .. } => Ok(env_user_permissions::table
                 .find(id)
                 .filter(env_user_permissions::env_id.eq_any(env_ids.clone()))
                 .get_result(db_conn().await?.deref_mut())
@@ -92,17 +115,29 @@ impl EnvUserPermission {
         }
     }
 
-    pub async fn find_user_role_in_env(
-        env_id: Uuid,
-        user_id: Uuid,
-    ) -> DbResult<Option<EnvUserRole>> {
+    /// Unexpired permissions of the user in the env: the permanent one, if
+    /// any, and temporary ones
+    pub async fn find_user_permissions_in_env(env_id: Uuid, user_id: Uuid) -> DbResult<Vec<Self>> {
         Ok(env_user_permissions::table
             .filter(env_user_permissions::env_id.eq(env_id))
             .filter(env_user_permissions::user_id.eq(user_id))
-            .get_result::<Self>(db_conn().await?.deref_mut())
-            .await
-            .optional()?
-            .map(|p| p.role))
+            .filter(
+                env_user_permissions::expires_at
+                    .is_null()
+                    .or(env_user_permissions::expires_at.gt(diesel::dsl::now)),
+            )
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Deletes all expired permissions, returning them
+    pub async fn delete_expired() -> DbResult<Vec<Self>> {
+        Ok(diesel::delete(
+            env_user_permissions::table
+                .filter(env_user_permissions::expires_at.le(diesel::dsl::now)),
+        )
+        .get_results(db_conn().await?.deref_mut())
+        .await?)
     }
 
     pub async fn delete(&self) -> DbResult<()> {
@@ -119,6 +154,10 @@ pub struct NewEnvUserPermission {
     pub env_id: Uuid,
     pub user_id: Uuid,
     pub role: EnvUserRole,
+    #[serde(default)]
+    pub custom_role_id: Option<Uuid>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 impl NewEnvUserPermission {
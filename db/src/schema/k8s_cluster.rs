@@ -1,3 +1,4 @@
+use super::deployment_task::deployment_tasks;
 use crate::{DbResult, db_conn};
 use chrono::prelude::*;
 use diesel::prelude::*;
@@ -26,6 +27,10 @@ table! {
         ingress_tls_secret_name -> Nullable<Varchar>,
         grafana_url -> Nullable<Varchar>,
         grafana_datasource_name -> Nullable<Varchar>,
+        tags -> Jsonb,
+        provider -> Varchar,
+        cordoned -> Bool,
+        missing_since -> Nullable<Timestamptz>,
     }
 }
 
@@ -56,6 +61,17 @@ pub struct K8sCluster {
     pub grafana_url: Option<String>,
     #[schema(required)]
     pub grafana_datasource_name: Option<String>,
+    /// Tags reported by the cluster provider, as a map of strings
+    pub tags: serde_json::Value,
+    /// The provider that discovered this cluster (`eks`, `local` or `static`)
+    #[filter]
+    pub provider: String,
+    /// Cordoned clusters don't accept new deployments, e.g. while being drained
+    #[filter]
+    pub cordoned: bool,
+    /// Set when the cluster stopped showing up in cluster discovery
+    #[schema(required)]
+    pub missing_since: Option<DateTime<Utc>>,
 }
 
 impl K8sCluster {
@@ -108,6 +124,40 @@ impl K8sCluster {
             .optional()?)
     }
 
+    /// Clusters of the given provider last seen before the given time, which
+    /// aren't already marked as missing
+    pub async fn find_unseen_since(
+        provider: &str,
+        seen_before: DateTime<Utc>,
+    ) -> DbResult<Vec<Self>> {
+        Ok(k8s_clusters::table
+            .filter(k8s_clusters::provider.eq(provider))
+            .filter(k8s_clusters::last_seen_at.lt(seen_before))
+            .filter(k8s_clusters::missing_since.is_null())
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn set_missing(&self, reason: String) -> DbResult<Self> {
+        Ok(diesel::update(k8s_clusters::table.find(self.id))
+            .set((
+                k8s_clusters::missing_since.eq(diesel::dsl::now),
+                k8s_clusters::is_ok.eq(false),
+                k8s_clusters::not_ok_reason.eq(reason),
+            ))
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Deletes a cluster along with its deployments and all of their tasks,
+    /// including tasks of deployments that were moved out of this cluster.
+    pub async fn forget(&self) -> DbResult<()> {
+        diesel::delete(deployment_tasks::table.filter(deployment_tasks::cluster_id.eq(self.id)))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        self.delete().await
+    }
+
     pub async fn delete(&self) -> DbResult<()> {
         diesel::delete(k8s_clusters::table.find(self.id))
             .execute(db_conn().await?.deref_mut())
@@ -125,6 +175,7 @@ impl K8sCluster {
                 ingress_tls_secret_name: None,
                 grafana_url: None,
                 grafana_datasource_name: None,
+                cordoned: None,
             })
             .execute(db_conn().await?.deref_mut())
             .await?;
@@ -140,12 +191,20 @@ pub struct NewK8sCluster {
     #[schema(required)]
     pub env_id: Option<Uuid>,
     pub region_name: String,
+    pub tags: serde_json::Value,
+    pub provider: String,
+    /// Only applied when the cluster is first discovered. New clusters are
+    /// ignored by default.
+    #[serde(default)]
+    pub ignore: Option<bool>,
 }
 
 impl NewK8sCluster {
     pub async fn insert(self) -> DbResult<K8sCluster> {
         let name = self.name.clone();
         let region_name = self.region_name.clone();
+        let tags = self.tags.clone();
+        let provider = self.provider.clone();
         Ok(diesel::insert_into(k8s_clusters::table)
             .values(self)
             .on_conflict(k8s_clusters::provider_id)
@@ -154,6 +213,9 @@ impl NewK8sCluster {
                 k8s_clusters::last_seen_at.eq(Utc::now()),
                 k8s_clusters::name.eq(name),
                 k8s_clusters::region_name.eq(region_name),
+                k8s_clusters::tags.eq(tags),
+                k8s_clusters::provider.eq(provider),
+                k8s_clusters::missing_since.eq(None::<DateTime<Utc>>),
             ))
             .get_result(db_conn().await?.deref_mut())
             .await?)
@@ -194,6 +256,7 @@ pub struct UpdateK8sCluster {
     pub grafana_url: Option<Option<String>>,
     #[serde(default, with = "::serde_with::rust::double_option")]
     pub grafana_datasource_name: Option<Option<String>>,
+    pub cordoned: Option<bool>,
 }
 
 impl UpdateK8sCluster {
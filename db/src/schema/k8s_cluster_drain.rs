@@ -0,0 +1,163 @@
+use crate::{DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_enum_derive::DieselEnum;
+use diesel_filter::DieselFilter;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use strum::{AsRefStr, Display, EnumIter, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    k8s_cluster_drains(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+        created_by_user_id -> Uuid,
+        cluster_id -> Uuid,
+        target_cluster_id -> Uuid,
+        batch_size -> Integer,
+        status -> Varchar,
+        reason -> Nullable<Varchar>,
+        total_deployments -> Integer,
+        migrated_deployment_ids -> Array<Uuid>,
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    AsRefStr,
+    Display,
+    DieselEnum,
+    ToSchema,
+)]
+pub enum K8sClusterDrainStatus {
+    Running,
+    Done,
+    Failed,
+    Canceled,
+}
+
+/// Migration of all deployments in a cluster to another cluster in the same
+/// env. Deployments are moved in batches of `batch_size`, waiting for the
+/// previous batch's tasks to finish before moving on to the next one.
+#[derive(Debug, Clone, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = k8s_cluster_drains)]
+pub struct K8sClusterDrain {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[schema(required)]
+    pub finished_at: Option<DateTime<Utc>>,
+    pub created_by_user_id: Uuid,
+    #[filter]
+    pub cluster_id: Uuid,
+    #[filter]
+    pub target_cluster_id: Uuid,
+    pub batch_size: i32,
+    #[filter]
+    pub status: K8sClusterDrainStatus,
+    #[schema(required)]
+    pub reason: Option<String>,
+    /// Number of deployments in the cluster when the drain started
+    pub total_deployments: i32,
+    /// Deployments moved to the target cluster so far
+    pub migrated_deployment_ids: Vec<Uuid>,
+}
+
+impl K8sClusterDrain {
+    pub async fn all_filtered(
+        filters: K8sClusterDrainFilters,
+        pagination: PaginationParams,
+    ) -> DbResult<Paginated<Self>> {
+        Ok(Self::filter(filters)
+            .order_by(k8s_cluster_drains::created_at.desc())
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(k8s_cluster_drains::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find_running() -> DbResult<Vec<Self>> {
+        Ok(k8s_cluster_drains::table
+            .filter(k8s_cluster_drains::status.eq(K8sClusterDrainStatus::Running))
+            .order_by(k8s_cluster_drains::created_at.asc())
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find_running_for_cluster(cluster_id: Uuid) -> DbResult<Option<Self>> {
+        Ok(k8s_cluster_drains::table
+            .filter(k8s_cluster_drains::cluster_id.eq(cluster_id))
+            .filter(k8s_cluster_drains::status.eq(K8sClusterDrainStatus::Running))
+            .first(db_conn().await?.deref_mut())
+            .await
+            .optional()?)
+    }
+
+    pub async fn add_migrated(&self, deployment_ids: Vec<Uuid>) -> DbResult<Self> {
+        let mut migrated_deployment_ids = self.migrated_deployment_ids.clone();
+        migrated_deployment_ids.extend(deployment_ids);
+        Ok(diesel::update(k8s_cluster_drains::table.find(self.id))
+            .set(k8s_cluster_drains::migrated_deployment_ids.eq(migrated_deployment_ids))
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Marks the drain as finished. Only running drains are updated, so a
+    /// drain canceled in the meantime stays canceled.
+    pub async fn finish(
+        &self,
+        status: K8sClusterDrainStatus,
+        reason: Option<String>,
+    ) -> DbResult<Option<Self>> {
+        Ok(diesel::update(
+            k8s_cluster_drains::table
+                .find(self.id)
+                .filter(k8s_cluster_drains::status.eq(K8sClusterDrainStatus::Running)),
+        )
+        .set((
+            k8s_cluster_drains::status.eq(status),
+            k8s_cluster_drains::reason.eq(reason),
+            k8s_cluster_drains::finished_at.eq(diesel::dsl::now),
+        ))
+        .get_result(db_conn().await?.deref_mut())
+        .await
+        .optional()?)
+    }
+}
+
+#[derive(Debug, Insertable, Deserialize, ToSchema)]
+#[diesel(table_name = k8s_cluster_drains)]
+pub struct NewK8sClusterDrain {
+    pub created_by_user_id: Uuid,
+    pub cluster_id: Uuid,
+    pub target_cluster_id: Uuid,
+    pub batch_size: i32,
+    pub total_deployments: i32,
+}
+
+impl NewK8sClusterDrain {
+    pub async fn insert(self) -> DbResult<K8sClusterDrain> {
+        Ok(diesel::insert_into(k8s_cluster_drains::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    k8s_cluster_health(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        cluster_id -> Uuid,
+        k8s_version -> Nullable<Varchar>,
+        node_count -> Nullable<Integer>,
+        ready_node_count -> Nullable<Integer>,
+        allocatable_cpu_millis -> Nullable<BigInt>,
+        allocatable_memory_bytes -> Nullable<BigInt>,
+        requested_cpu_millis -> Nullable<BigInt>,
+        requested_memory_bytes -> Nullable<BigInt>,
+        missing_permissions -> Array<Varchar>,
+        errors -> Array<Varchar>,
+    }
+}
+
+/// A health and capacity sample of a cluster. Values that could not be
+/// collected are null, with the reason listed in `errors`.
+#[derive(Debug, Clone, Identifiable, Queryable, Serialize, ToSchema)]
+#[diesel(table_name = k8s_cluster_health)]
+pub struct K8sClusterHealth {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub cluster_id: Uuid,
+    /// Kubernetes version reported by the API server, e.g. `v1.33.2-eks-1`
+    #[schema(required)]
+    pub k8s_version: Option<String>,
+    #[schema(required)]
+    pub node_count: Option<i32>,
+    #[schema(required)]
+    pub ready_node_count: Option<i32>,
+    /// Total allocatable CPU of all nodes, in millicores
+    #[schema(required)]
+    pub allocatable_cpu_millis: Option<i64>,
+    /// Total allocatable memory of all nodes, in bytes
+    #[schema(required)]
+    pub allocatable_memory_bytes: Option<i64>,
+    /// CPU requested by running pods in Platz namespaces, in millicores
+    #[schema(required)]
+    pub requested_cpu_millis: Option<i64>,
+    /// Memory requested by running pods in Platz namespaces, in bytes
+    #[schema(required)]
+    pub requested_memory_bytes: Option<i64>,
+    /// Permissions the agent needs but doesn't have, e.g. `watch apps/deployments`
+    pub missing_permissions: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl K8sClusterHealth {
+    /// The most recent sample of a cluster, if any
+    pub async fn latest(cluster_id: Uuid) -> DbResult<Option<Self>> {
+        Ok(k8s_cluster_health::table
+            .filter(k8s_cluster_health::cluster_id.eq(cluster_id))
+            .order_by(k8s_cluster_health::created_at.desc())
+            .first(db_conn().await?.deref_mut())
+            .await
+            .optional()?)
+    }
+
+    /// All samples of a cluster, newest first
+    pub async fn history(
+        cluster_id: Uuid,
+        extra_filters: K8sClusterHealthExtraFilters,
+        pagination: PaginationParams,
+    ) -> DbResult<Paginated<Self>> {
+        let mut query = k8s_cluster_health::table
+            .filter(k8s_cluster_health::cluster_id.eq(cluster_id))
+            .into_boxed();
+        if let Some(since) = extra_filters.since {
+            query = query.filter(k8s_cluster_health::created_at.ge(since));
+        }
+        if let Some(until) = extra_filters.until {
+            query = query.filter(k8s_cluster_health::created_at.lt(until));
+        }
+        Ok(query
+            .order_by(k8s_cluster_health::created_at.desc())
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Deletes samples of all clusters taken before the given time
+    pub async fn delete_older_than(created_before: DateTime<Utc>) -> DbResult<usize> {
+        Ok(diesel::delete(
+            k8s_cluster_health::table.filter(k8s_cluster_health::created_at.lt(created_before)),
+        )
+        .execute(db_conn().await?.deref_mut())
+        .await?)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct K8sClusterHealthExtraFilters {
+    #[schema(required)]
+    since: Option<DateTime<Utc>>,
+    #[schema(required)]
+    until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Insertable)]
+#[diesel(table_name = k8s_cluster_health)]
+pub struct NewK8sClusterHealth {
+    pub cluster_id: Uuid,
+    pub k8s_version: Option<String>,
+    pub node_count: Option<i32>,
+    pub ready_node_count: Option<i32>,
+    pub allocatable_cpu_millis: Option<i64>,
+    pub allocatable_memory_bytes: Option<i64>,
+    pub requested_cpu_millis: Option<i64>,
+    pub requested_memory_bytes: Option<i64>,
+    pub missing_permissions: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl NewK8sClusterHealth {
+    pub async fn insert(self) -> DbResult<K8sClusterHealth> {
+        Ok(diesel::insert_into(k8s_cluster_health::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
@@ -0,0 +1,305 @@
+use super::k8s_cluster::{K8sCluster, UpdateK8sCluster};
+use crate::{DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_filter::DieselFilter;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    k8s_cluster_rules(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        name -> Varchar,
+        priority -> Integer,
+        match_name_pattern -> Nullable<Varchar>,
+        match_region_name -> Nullable<Varchar>,
+        match_provider -> Nullable<Varchar>,
+        match_tags -> Jsonb,
+        env_id -> Nullable<Uuid>,
+        ingress_domain -> Nullable<Varchar>,
+        ingress_class -> Nullable<Varchar>,
+        ingress_tls_secret_name -> Nullable<Varchar>,
+        ignore -> Nullable<Bool>,
+    }
+}
+
+/// A rule applied to newly discovered clusters. All `match_*` fields that are
+/// set must match for the rule to apply; the first matching rule (lowest
+/// priority, then oldest) sets any of its non-null fields on the cluster.
+#[derive(Debug, Clone, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = k8s_cluster_rules)]
+pub struct K8sClusterRule {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter(insensitive, substring)]
+    pub name: String,
+    pub priority: i32,
+    /// Cluster name pattern, where `*` matches any number of characters and
+    /// `?` matches a single character
+    #[schema(required)]
+    pub match_name_pattern: Option<String>,
+    #[schema(required)]
+    pub match_region_name: Option<String>,
+    #[schema(required)]
+    pub match_provider: Option<String>,
+    /// Tags the cluster must have, as a map of strings
+    pub match_tags: serde_json::Value,
+    #[filter]
+    #[schema(required)]
+    pub env_id: Option<Uuid>,
+    #[schema(required)]
+    pub ingress_domain: Option<String>,
+    #[schema(required)]
+    pub ingress_class: Option<String>,
+    #[schema(required)]
+    pub ingress_tls_secret_name: Option<String>,
+    #[schema(required)]
+    pub ignore: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct K8sClusterRuleMatches {
+    pub rule_id: Uuid,
+    /// Clusters this rule would apply to, i.e. that no earlier rule matches
+    pub cluster_ids: Vec<Uuid>,
+}
+
+impl K8sClusterRule {
+    pub async fn all() -> DbResult<Vec<Self>> {
+        Ok(k8s_cluster_rules::table
+            .order_by((
+                k8s_cluster_rules::priority.asc(),
+                k8s_cluster_rules::created_at.asc(),
+            ))
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn all_filtered(
+        filters: K8sClusterRuleFilters,
+        pagination: PaginationParams,
+    ) -> DbResult<Paginated<Self>> {
+        Ok(Self::filter(filters)
+            .order_by((
+                k8s_cluster_rules::priority.asc(),
+                k8s_cluster_rules::created_at.asc(),
+            ))
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(k8s_cluster_rules::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn delete(&self) -> DbResult<()> {
+        diesel::delete(k8s_cluster_rules::table.find(self.id))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        Ok(())
+    }
+
+    pub fn matches(&self, cluster: &K8sCluster) -> bool {
+        self.match_name_pattern
+            .as_ref()
+            .is_none_or(|pattern| glob_match(pattern, &cluster.name))
+            && self
+                .match_region_name
+                .as_ref()
+                .is_none_or(|region_name| *region_name == cluster.region_name)
+            && self
+                .match_provider
+                .as_ref()
+                .is_none_or(|provider| *provider == cluster.provider)
+            && self.match_tags.as_object().is_none_or(|tags| {
+                tags.iter()
+                    .all(|(key, value)| cluster.tags.get(key) == Some(value))
+            })
+    }
+
+    /// Applies the first matching rule (if any) to the cluster, returning the
+    /// updated cluster and the applied rule.
+    pub async fn apply_first_match(cluster: K8sCluster) -> DbResult<(K8sCluster, Option<Self>)> {
+        let Some(rule) = Self::all()
+            .await?
+            .into_iter()
+            .find(|rule| rule.matches(&cluster))
+        else {
+            return Ok((cluster, None));
+        };
+        if rule.env_id.is_none()
+            && rule.ignore.is_none()
+            && rule.ingress_domain.is_none()
+            && rule.ingress_class.is_none()
+            && rule.ingress_tls_secret_name.is_none()
+        {
+            // Nothing to set, but the rule still shadows later ones
+            return Ok((cluster, Some(rule)));
+        }
+
+        let cluster = UpdateK8sCluster {
+            env_id: rule.env_id.map(Some),
+            // Clusters attached to an env are watched, like ones with a default env
+            ignore: if rule.env_id.is_some() {
+                Some(false)
+            } else {
+                rule.ignore
+            },
+            ingress_domain: rule.ingress_domain.clone().map(Some),
+            ingress_class: rule.ingress_class.clone().map(Some),
+            ingress_tls_secret_name: rule.ingress_tls_secret_name.clone().map(Some),
+            grafana_url: None,
+            grafana_datasource_name: None,
+            cordoned: None,
+        }
+        .save(cluster.id)
+        .await?;
+
+        Ok((cluster, Some(rule)))
+    }
+
+    /// Evaluates all rules against all known clusters without changing them.
+    pub async fn dry_run() -> DbResult<Vec<K8sClusterRuleMatches>> {
+        let rules = Self::all().await?;
+        let clusters = K8sCluster::all().await?;
+        let mut matches = rules
+            .iter()
+            .map(|rule| K8sClusterRuleMatches {
+                rule_id: rule.id,
+                cluster_ids: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        for cluster in clusters.iter() {
+            if let Some(index) = rules.iter().position(|rule| rule.matches(cluster)) {
+                matches[index].cluster_ids.push(cluster.id);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+#[derive(Debug, Insertable, Deserialize, ToSchema)]
+#[diesel(table_name = k8s_cluster_rules)]
+pub struct NewK8sClusterRule {
+    pub name: String,
+    #[serde(default)]
+    pub priority: i32,
+    pub match_name_pattern: Option<String>,
+    pub match_region_name: Option<String>,
+    pub match_provider: Option<String>,
+    #[serde(default = "empty_tags")]
+    pub match_tags: serde_json::Value,
+    pub env_id: Option<Uuid>,
+    pub ingress_domain: Option<String>,
+    pub ingress_class: Option<String>,
+    pub ingress_tls_secret_name: Option<String>,
+    pub ignore: Option<bool>,
+}
+
+fn empty_tags() -> serde_json::Value {
+    serde_json::Value::Object(Default::default())
+}
+
+impl NewK8sClusterRule {
+    pub async fn insert(self) -> DbResult<K8sClusterRule> {
+        Ok(diesel::insert_into(k8s_cluster_rules::table)
+            .values(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
+
+#[derive(Debug, AsChangeset, Deserialize, ToSchema)]
+#[diesel(table_name = k8s_cluster_rules)]
+pub struct UpdateK8sClusterRule {
+    pub name: Option<String>,
+    pub priority: Option<i32>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub match_name_pattern: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub match_region_name: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub match_provider: Option<Option<String>>,
+    pub match_tags: Option<serde_json::Value>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub env_id: Option<Option<Uuid>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub ingress_domain: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub ingress_class: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub ingress_tls_secret_name: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub ignore: Option<Option<bool>>,
+}
+
+impl UpdateK8sClusterRule {
+    pub async fn save(self, id: Uuid) -> DbResult<K8sClusterRule> {
+        Ok(diesel::update(k8s_cluster_rules::table.find(id))
+            .set(self)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
+
+/// Matches text against a pattern where `*` matches any sequence of characters
+/// (including none) and `?` matches exactly one character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern, and the text position it was
+    // tried at, for backtracking
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("prod-*", "prod-eu-1"));
+        assert!(glob_match("prod-*", "prod-"));
+        assert!(!glob_match("prod-*", "staging-eu-1"));
+        assert!(glob_match("*-eu-?", "prod-eu-1"));
+        assert!(!glob_match("*-eu-?", "prod-eu-12"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+}
@@ -1,10 +1,14 @@
 use crate::{DbResult, db_conn};
 use chrono::prelude::*;
+use diesel::dsl::sql;
 use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer};
+use diesel::upsert::excluded;
 use diesel_async::RunQueryDsl;
 use diesel_filter::DieselFilter;
+use diesel_json::Json;
 use diesel_pagination::{Paginate, Paginated, PaginationParams};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::ops::DerefMut;
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -20,6 +24,7 @@ table! {
         name -> Varchar,
         status_color -> Array<Varchar>,
         metadata -> Jsonb,
+        status -> Jsonb,
     }
 }
 
@@ -39,6 +44,98 @@ pub struct K8sResource {
     pub name: String,
     pub status_color: Vec<String>,
     pub metadata: serde_json::Value,
+    #[schema(value_type = K8sResourceStatus)]
+    pub status: Json<K8sResourceStatus>,
+}
+
+/// Structured status extracted from the Kubernetes object, complementing the
+/// summarized `status_color`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct K8sResourceStatus {
+    pub conditions: Vec<K8sResourceCondition>,
+    pub containers: Vec<K8sResourceContainer>,
+    #[schema(required)]
+    pub replicas: Option<K8sResourceReplicas>,
+    #[schema(required)]
+    pub pod: Option<K8sResourcePodStatus>,
+    #[schema(required)]
+    pub cron_job: Option<K8sResourceCronJobStatus>,
+    #[schema(required)]
+    pub ingress: Option<K8sResourceIngressStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct K8sResourceCondition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub status: String,
+    #[schema(required)]
+    pub reason: Option<String>,
+    #[schema(required)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct K8sResourceContainer {
+    pub name: String,
+    pub image: String,
+    #[schema(required)]
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct K8sResourceReplicas {
+    pub wanted: i32,
+    pub ready: i32,
+    pub updated: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct K8sResourcePodStatus {
+    #[schema(required)]
+    pub node_name: Option<String>,
+    pub restart_count: i32,
+    #[schema(required)]
+    pub last_termination_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct K8sResourceCronJobStatus {
+    #[schema(required)]
+    pub last_schedule_time: Option<DateTime<Utc>>,
+    #[schema(required)]
+    pub last_successful_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct K8sResourceIngressStatus {
+    /// Hosts routed by the ingress rules
+    pub hosts: Vec<String>,
+    /// IPs or hostnames of the load balancers assigned to the ingress
+    pub addresses: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct K8sResourceExtraFilters {
+    /// Only resources currently showing this status color
+    #[schema(required)]
+    status_color: Option<String>,
+    /// Only resources running a container with this image (without tag)
+    #[schema(required)]
+    image: Option<String>,
+    /// Only resources running a container with this image tag
+    #[schema(required)]
+    tag: Option<String>,
+    /// Only resources with a condition having this reason
+    #[schema(required)]
+    condition_reason: Option<String>,
+    /// Only pods scheduled on this node
+    #[schema(required)]
+    node_name: Option<String>,
+    /// Only pods that restarted at least this many times
+    #[schema(required)]
+    min_restart_count: Option<i32>,
 }
 
 impl K8sResource {
@@ -50,9 +147,44 @@ impl K8sResource {
 
     pub async fn all_filtered(
         filters: K8sResourceFilters,
+        extra_filters: K8sResourceExtraFilters,
         pagination: PaginationParams,
     ) -> DbResult<Paginated<Self>> {
-        Ok(Self::filter(filters)
+        let mut filtered = Self::filter(filters);
+        if let Some(status_color) = extra_filters.status_color {
+            filtered = filtered.filter(k8s_resources::status_color.contains(vec![status_color]));
+        }
+        if let Some(image) = extra_filters.image {
+            filtered = filtered.filter(
+                k8s_resources::status
+                    .contains(serde_json::json!({"containers": [{"image": image}]})),
+            );
+        }
+        if let Some(tag) = extra_filters.tag {
+            filtered = filtered.filter(
+                k8s_resources::status.contains(serde_json::json!({"containers": [{"tag": tag}]})),
+            );
+        }
+        if let Some(reason) = extra_filters.condition_reason {
+            filtered = filtered.filter(
+                k8s_resources::status
+                    .contains(serde_json::json!({"conditions": [{"reason": reason}]})),
+            );
+        }
+        if let Some(node_name) = extra_filters.node_name {
+            filtered = filtered.filter(
+                k8s_resources::status
+                    .contains(serde_json::json!({"pod": {"node_name": node_name}})),
+            );
+        }
+        if let Some(min_restart_count) = extra_filters.min_restart_count {
+            filtered = filtered.filter(
+                sql::<Bool>("coalesce((status->'pod'->>'restart_count')::int, 0) >= ")
+                    .bind::<Integer, _>(min_restart_count),
+            );
+        }
+
+        Ok(filtered
             .paginate(pagination)
             .load_and_count(db_conn().await?.deref_mut())
             .await?)
@@ -73,6 +205,7 @@ impl K8sResource {
         let name = self.name.clone();
         let status_color = self.status_color.clone();
         let metadata = self.metadata.clone();
+        let status = self.status.clone();
         let last_updated_at = self.last_updated_at;
         Ok(diesel::insert_into(k8s_resources::table)
             .values(self)
@@ -85,30 +218,81 @@ impl K8sResource {
                 k8s_resources::name.eq(name),
                 k8s_resources::status_color.eq(status_color),
                 k8s_resources::metadata.eq(metadata),
+                k8s_resources::status.eq(status),
                 k8s_resources::last_updated_at.eq(last_updated_at),
             ))
             .get_result(db_conn().await?.deref_mut())
             .await?)
     }
 
-    pub async fn find_older_than(
-        cluster_id: Uuid,
-        timestamp: DateTime<Utc>,
-    ) -> DbResult<Vec<Self>> {
-        Ok(k8s_resources::table
-            .filter(k8s_resources::cluster_id.eq(cluster_id))
-            .filter(k8s_resources::last_updated_at.lt(timestamp))
-            .get_results(db_conn().await?.deref_mut())
-            .await?)
+    /// Upserts many resources in a single statement
+    pub async fn save_all(resources: Vec<Self>) -> DbResult<()> {
+        if resources.is_empty() {
+            return Ok(());
+        }
+        diesel::insert_into(k8s_resources::table)
+            .values(resources)
+            .on_conflict(k8s_resources::id)
+            .do_update()
+            .set((
+                k8s_resources::deployment_id.eq(excluded(k8s_resources::deployment_id)),
+                k8s_resources::kind.eq(excluded(k8s_resources::kind)),
+                k8s_resources::api_version.eq(excluded(k8s_resources::api_version)),
+                k8s_resources::name.eq(excluded(k8s_resources::name)),
+                k8s_resources::status_color.eq(excluded(k8s_resources::status_color)),
+                k8s_resources::metadata.eq(excluded(k8s_resources::metadata)),
+                k8s_resources::status.eq(excluded(k8s_resources::status)),
+                k8s_resources::last_updated_at.eq(excluded(k8s_resources::last_updated_at)),
+            ))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        Ok(())
     }
 
-    pub async fn delete_by_id(id: Uuid) -> DbResult<()> {
-        diesel::delete(k8s_resources::table.find(id))
+    pub async fn delete_by_ids(ids: Vec<Uuid>) -> DbResult<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        diesel::delete(k8s_resources::table.filter(k8s_resources::id.eq_any(ids)))
             .execute(db_conn().await?.deref_mut())
             .await?;
         Ok(())
     }
 
+    /// Deletes all resources of a kind belonging to a deployment in a cluster,
+    /// except for the given ones. Returns the number of deleted resources.
+    pub async fn delete_stale(
+        cluster_id: Uuid,
+        deployment_id: Uuid,
+        kind: &str,
+        keep_ids: Vec<Uuid>,
+    ) -> DbResult<usize> {
+        Ok(diesel::delete(
+            k8s_resources::table
+                .filter(k8s_resources::cluster_id.eq(cluster_id))
+                .filter(k8s_resources::deployment_id.eq(deployment_id))
+                .filter(k8s_resources::kind.eq(kind))
+                .filter(k8s_resources::id.ne_all(keep_ids)),
+        )
+        .execute(db_conn().await?.deref_mut())
+        .await?)
+    }
+
+    /// Deletes all resources in a cluster that don't belong to one of the
+    /// given deployments. Returns the number of deleted resources.
+    pub async fn delete_outside_deployments(
+        cluster_id: Uuid,
+        keep_deployment_ids: Vec<Uuid>,
+    ) -> DbResult<usize> {
+        Ok(diesel::delete(
+            k8s_resources::table
+                .filter(k8s_resources::cluster_id.eq(cluster_id))
+                .filter(k8s_resources::deployment_id.ne_all(keep_deployment_ids)),
+        )
+        .execute(db_conn().await?.deref_mut())
+        .await?)
+    }
+
     pub async fn delete(&self) -> DbResult<()> {
         diesel::delete(k8s_resources::table.find(self.id))
             .execute(db_conn().await?.deref_mut())
@@ -1,6 +1,12 @@
+pub mod access_request;
+pub mod audit_log;
 pub mod bot;
 pub mod bot_token;
+pub mod custom_role;
 pub mod deployment;
+pub mod deployment_action_restriction;
+pub mod deployment_bot_permission;
+pub mod deployment_env_grant;
 pub mod deployment_kind;
 pub mod deployment_permission;
 pub mod deployment_resource;
@@ -8,13 +14,21 @@ pub mod deployment_resource_type;
 pub mod deployment_status;
 pub mod deployment_task;
 pub mod env;
+pub mod env_bot_permission;
 pub mod env_user_permission;
 pub mod helm_chart;
 pub mod helm_registry;
 pub mod helm_tag_format;
 pub mod k8s_cluster;
+pub mod k8s_cluster_drain;
+pub mod k8s_cluster_health;
+pub mod k8s_cluster_rule;
 pub mod k8s_resource;
 pub mod secret;
+pub mod secret_version;
 pub mod setting;
 pub mod user;
 pub mod user_token;
+pub mod webhook;
+pub mod webhook_delivery;
+pub mod webhook_deployment_state;
@@ -1,11 +1,18 @@
-use crate::{AccessScope, DbResult, db_conn};
+use super::secret_version::SecretVersion;
+use crate::{
+    AccessScope, DbError, DbResult, Identity, db_conn,
+    encryption::{self, master_keys},
+    secret_store::{SecretStore, files_store, vault_store},
+};
 use chrono::prelude::*;
 use diesel::{QueryDsl, prelude::*};
 use diesel_async::RunQueryDsl;
+use diesel_enum_derive::DieselEnum;
 use diesel_filter::DieselFilter;
 use diesel_pagination::{Paginate, Paginated, PaginationParams};
 use serde::{Deserialize, Serialize};
 use std::ops::DerefMut;
+use strum::{AsRefStr, Display, EnumIter, EnumString};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -18,6 +25,91 @@ table! {
         collection -> Varchar,
         name -> Varchar,
         contents -> Varchar,
+        data_key -> Nullable<Varchar>,
+        key_id -> Nullable<Varchar>,
+        store -> Varchar,
+        store_path -> Nullable<Varchar>,
+        store_key -> Nullable<Varchar>,
+        write_only -> Bool,
+        fingerprint -> Nullable<Varchar>,
+        secret_type -> Varchar,
+    }
+}
+
+const IMAGE_PULL_SECRET_PREFIX: &str = "platz-registry-";
+
+/// Where a secret's value is kept. See [`crate::secret_store`] for configuring
+/// the external stores.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    AsRefStr,
+    Display,
+    DieselEnum,
+    ToSchema,
+)]
+pub enum SecretStoreKind {
+    /// The contents are stored in the secret itself
+    #[default]
+    Postgres,
+    /// A key of a secret in a Vault KV v2 engine
+    Vault,
+    /// A file in a mounted secrets directory
+    Files,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    AsRefStr,
+    Display,
+    DieselEnum,
+    ToSchema,
+)]
+pub enum SecretType {
+    /// A value referenced by chart inputs
+    #[default]
+    Opaque,
+    /// Registry credentials in `.dockerconfigjson` format, created as an image
+    /// pull secret in every deployment namespace of the env
+    DockerConfigJson,
+}
+
+impl SecretType {
+    /// The type of Kubernetes secrets created for secrets of this type
+    pub fn k8s_type(&self) -> &'static str {
+        match self {
+            Self::Opaque => "Opaque",
+            Self::DockerConfigJson => "kubernetes.io/dockerconfigjson",
+        }
+    }
+
+    /// Checks that contents are valid for this type
+    pub fn validate(&self, contents: &str) -> Result<(), String> {
+        match self {
+            Self::Opaque => Ok(()),
+            Self::DockerConfigJson => match serde_json::from_str::<serde_json::Value>(contents) {
+                Ok(config) if config.get("auths").is_some_and(|auths| auths.is_object()) => Ok(()),
+                Ok(_) => Err("Registry credentials must contain an `auths` object".to_owned()),
+                Err(err) => Err(format!("Registry credentials must be valid JSON: {err}")),
+            },
+        }
     }
 }
 
@@ -33,15 +125,100 @@ pub struct Secret {
     pub collection: String,
     #[filter(insensitive)]
     pub name: String,
+    /// The decrypted contents. Secrets are always decrypted when loaded.
     #[serde(skip)]
     pub contents: String,
+    #[serde(skip)]
+    pub data_key: Option<String>,
+    /// The master key the secret is encrypted with, or `None` if it was
+    /// stored before encryption was enabled
+    #[schema(required)]
+    pub key_id: Option<String>,
+    #[filter]
+    pub store: SecretStoreKind,
+    /// Path of the referenced secret in an external store
+    #[schema(required)]
+    pub store_path: Option<String>,
+    /// Key within the referenced secret in an external store
+    #[schema(required)]
+    pub store_key: Option<String>,
+    /// Contents are never returned through the API. Write-only is kept as a
+    /// record that the secret was meant to never be read, once set it can't
+    /// be unset.
+    #[filter]
+    pub write_only: bool,
+    /// HMAC of the contents keyed with the master key, see
+    /// [`crate::encryption`]. `None` for secrets in external stores.
+    #[schema(required)]
+    pub fingerprint: Option<String>,
+    #[filter]
+    pub secret_type: SecretType,
 }
 
 impl Secret {
+    fn decrypted(mut self) -> DbResult<Self> {
+        self.contents = encryption::open(
+            &self.contents,
+            self.data_key.as_deref(),
+            self.key_id.as_deref(),
+        )?;
+        // Secrets stored before fingerprints were added don't have one
+        if self.fingerprint.is_none() && self.store == SecretStoreKind::Postgres {
+            self.fingerprint = Some(encryption::fingerprint(&self.contents)?);
+        }
+        Ok(self)
+    }
+
+    /// Returns the secret's value, reading it from its store if the secret is
+    /// a reference.
+    pub async fn resolve_contents(&self) -> DbResult<String> {
+        let (Some(path), Some(key)) = (self.store_path.as_deref(), self.store_key.as_deref())
+        else {
+            return Ok(self.contents.clone());
+        };
+        let result = match self.store {
+            SecretStoreKind::Postgres => return Ok(self.contents.clone()),
+            SecretStoreKind::Vault => match vault_store() {
+                Ok(store) => store.read(self.env_id, path, key).await,
+                Err(err) => Err(err),
+            },
+            SecretStoreKind::Files => match files_store() {
+                Ok(store) => store.read(self.env_id, path, key).await,
+                Err(err) => Err(err),
+            },
+        };
+        result.map_err(|err| {
+            DbError::SecretStoreError(format!("{}/{}", self.collection, self.name), err)
+        })
+    }
+
+    /// Name of the image pull secret created for this secret in deployment
+    /// namespaces, a valid Kubernetes name derived from the collection, name
+    /// and ID.
+    pub fn image_pull_secret_name(&self) -> String {
+        image_pull_secret_name(self.id, &self.collection, &self.name)
+    }
+
+    /// Registry credentials to create as image pull secrets in the env
+    pub async fn all_image_pull_secrets(env_id: Uuid) -> DbResult<Vec<Self>> {
+        secrets::table
+            .filter(secrets::env_id.eq(env_id))
+            .filter(secrets::secret_type.eq(SecretType::DockerConfigJson))
+            .order_by((secrets::collection, secrets::name))
+            .get_results(db_conn().await?.deref_mut())
+            .await?
+            .into_iter()
+            .map(Self::decrypted)
+            .collect()
+    }
+
     pub async fn all() -> DbResult<Vec<Self>> {
-        Ok(secrets::table
+        secrets::table
             .get_results(db_conn().await?.deref_mut())
-            .await?)
+            .await?
+            .into_iter()
+            .map(Self::decrypted)
+            .collect()
     }
 
     pub async fn all_filtered(
@@ -50,20 +227,27 @@ impl Secret {
         scope: &AccessScope,
     ) -> DbResult<Paginated<Self>> {
         let mut filtered = Self::filter(filters);
-        if let AccessScope::Envs(env_ids) = scope {
+        if let AccessScope::Envs { env_ids, .. } = scope {
             filtered = filtered.filter(secrets::env_id.eq_any(env_ids.clone()));
         }
-        Ok(filtered
+        let mut paginated: Paginated<Self> = filtered
             .paginate(pagination)
             .load_and_count(db_conn().await?.deref_mut())
-            .await?)
+            .await?;
+        paginated.items = paginated
+            .items
+            .into_iter()
+            .map(Self::decrypted)
+            .collect::<DbResult<_>>()?;
+        Ok(paginated)
     }
 
     pub async fn find(id: Uuid) -> DbResult<Self> {
-        Ok(secrets::table
+        secrets::table
             .find(id)
-            .get_result(db_conn().await?.deref_mut())
-            .await?)
+            .get_result::<Self>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
     }
 
     /// Like [`Self::find`] but only returns the secret if it is within the
@@ -72,12 +256,93 @@ impl Secret {
     pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
         match scope {
             AccessScope::All => Self::find(id).await,
-            AccessScope::Envs(env_ids) => Ok(secrets::table
+            AccessScope::Envs { env_ids, .. } => secrets::table
                 .find(id)
                 .filter(secrets::env_id.eq_any(env_ids.clone()))
-                .get_result(db_conn().await?.deref_mut())
-                .await?),
+                .get_result::<Self>(db_conn().await?.deref_mut())
+                .await?
+                .decrypted(),
+        }
+    }
+
+    /// Wraps the data key of every secret not encrypted with the current master
+    /// key with it, encrypts secrets stored in plaintext, and recomputes their
+    /// fingerprints with the current master key. Each secret is
+    /// updated separately, only if it wasn't changed in the meantime, so this
+    /// can run while secrets are being used. Returns the number of updated
+    /// secrets.
+    pub async fn rewrap_all() -> DbResult<usize> {
+        let Some(keys) = master_keys()? else {
+            return Ok(0);
+        };
+        let current_key_id = keys.current_key_id();
+        let stale = secrets::table
+            .filter(secrets::key_id.is_distinct_from(current_key_id))
+            .select((
+                secrets::id,
+                secrets::contents,
+                secrets::data_key,
+                secrets::key_id,
+                secrets::store,
+            ))
+            .get_results::<(
+                Uuid,
+                String,
+                Option<String>,
+                Option<String>,
+                SecretStoreKind,
+            )>(db_conn().await?.deref_mut())
+            .await?;
+        let mut updated = 0;
+        for (id, contents, data_key, key_id, store) in stale {
+            let fingerprint = (store == SecretStoreKind::Postgres)
+                .then(|| {
+                    encryption::open(&contents, data_key.as_deref(), key_id.as_deref())
+                        .map(|plaintext| keys.fingerprint(plaintext.as_bytes()))
+                })
+                .transpose()?;
+            let (contents, data_key) =
+                keys.reseal(contents, data_key.as_deref(), key_id.as_deref())?;
+            updated += diesel::update(
+                secrets::table
+                    .find(id)
+                    .filter(secrets::key_id.is_not_distinct_from(key_id)),
+            )
+            .set((
+                secrets::contents.eq(contents),
+                secrets::data_key.eq(data_key),
+                secrets::key_id.eq(current_key_id),
+                secrets::fingerprint.eq(fingerprint),
+            ))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
         }
+        Ok(updated)
+    }
+
+    /// Sets the secret to a previous version's value, recording it as a new
+    /// version.
+    pub async fn restore(&self, version: &SecretVersion, identity: &Identity) -> DbResult<Self> {
+        let fingerprint = match self.store {
+            SecretStoreKind::Postgres => Some(encryption::fingerprint(&version.contents)?),
+            _ => None,
+        };
+        let sealed = encryption::seal(version.contents.clone())?;
+        let secret = diesel::update(secrets::table.find(self.id))
+            .set((
+                secrets::contents.eq(sealed.value),
+                secrets::data_key.eq(sealed.data_key),
+                secrets::key_id.eq(sealed.key_id),
+                secrets::fingerprint.eq(fingerprint),
+                secrets::store_path.eq(&version.store_path),
+                secrets::store_key.eq(&version.store_key),
+                secrets::updated_at.eq(diesel::dsl::now),
+            ))
+            .get_result::<Self>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()?;
+        SecretVersion::record(&secret, identity, Some(version.id)).await?;
+        Ok(secret)
     }
 
     pub async fn delete(&self) -> DbResult<()> {
@@ -88,36 +353,143 @@ impl Secret {
     }
 }
 
-#[derive(Debug, Insertable, Deserialize, ToSchema)]
-#[diesel(table_name = secrets)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct NewSecret {
     pub env_id: Uuid,
     pub collection: String,
     pub name: String,
+    /// The secret's value, must be empty when referencing an external store
+    #[serde(default)]
     pub contents: String,
+    #[serde(default)]
+    pub store: SecretStoreKind,
+    /// Required for external stores
+    #[serde(default)]
+    pub store_path: Option<String>,
+    /// Required for external stores
+    #[serde(default)]
+    pub store_key: Option<String>,
+    #[serde(default)]
+    pub write_only: bool,
+    #[serde(default)]
+    pub secret_type: SecretType,
 }
 
 impl NewSecret {
-    pub async fn insert(self) -> DbResult<Secret> {
-        Ok(diesel::insert_into(secrets::table)
-            .values(self)
-            .get_result(db_conn().await?.deref_mut())
-            .await?)
+    pub async fn insert(self, identity: &Identity) -> DbResult<Secret> {
+        let fingerprint = match self.store {
+            SecretStoreKind::Postgres => Some(encryption::fingerprint(&self.contents)?),
+            _ => None,
+        };
+        let sealed = encryption::seal(self.contents)?;
+        let secret = diesel::insert_into(secrets::table)
+            .values((
+                secrets::env_id.eq(self.env_id),
+                secrets::collection.eq(self.collection),
+                secrets::name.eq(self.name),
+                secrets::contents.eq(sealed.value),
+                secrets::data_key.eq(sealed.data_key),
+                secrets::key_id.eq(sealed.key_id),
+                secrets::store.eq(self.store),
+                secrets::store_path.eq(self.store_path),
+                secrets::store_key.eq(self.store_key),
+                secrets::write_only.eq(self.write_only),
+                secrets::secret_type.eq(self.secret_type),
+                secrets::fingerprint.eq(fingerprint),
+            ))
+            .get_result::<Secret>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()?;
+        SecretVersion::record(&secret, identity, None).await?;
+        Ok(secret)
     }
 }
 
-#[derive(Debug, AsChangeset, Deserialize, ToSchema)]
-#[diesel(table_name = secrets)]
+/// A secret's store can't be changed. Postgres secrets update their
+/// `contents`, references update their `store_path` and `store_key`.
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateSecret {
-    name: Option<String>,
-    contents: Option<String>,
+    pub name: Option<String>,
+    pub contents: Option<String>,
+    pub store_path: Option<String>,
+    pub store_key: Option<String>,
+    /// Makes the secret write-only, can't be set back to `false`
+    pub write_only: Option<bool>,
 }
 
 impl UpdateSecret {
-    pub async fn save(self, id: Uuid) -> DbResult<Secret> {
-        Ok(diesel::update(secrets::table.filter(secrets::id.eq(id)))
-            .set((self, secrets::updated_at.eq(diesel::dsl::now)))
-            .get_result(db_conn().await?.deref_mut())
-            .await?)
+    pub async fn save(self, id: Uuid, identity: &Identity) -> DbResult<Secret> {
+        let changes_value =
+            self.contents.is_some() || self.store_path.is_some() || self.store_key.is_some();
+        let fingerprint = self
+            .contents
+            .as_deref()
+            .map(encryption::fingerprint)
+            .transpose()?;
+        let contents = self.contents.map(encryption::seal).transpose()?;
+        let secret = diesel::update(secrets::table.filter(secrets::id.eq(id)))
+            .set((
+                self.name.map(|name| secrets::name.eq(name)),
+                contents.map(|sealed| {
+                    (
+                        secrets::contents.eq(sealed.value),
+                        secrets::data_key.eq(sealed.data_key),
+                        secrets::key_id.eq(sealed.key_id),
+                    )
+                }),
+                self.store_path.map(|path| secrets::store_path.eq(path)),
+                self.store_key.map(|key| secrets::store_key.eq(key)),
+                self.write_only
+                    .map(|write_only| secrets::write_only.eq(write_only)),
+                fingerprint.map(|fingerprint| secrets::fingerprint.eq(fingerprint)),
+                secrets::updated_at.eq(diesel::dsl::now),
+            ))
+            .get_result::<Secret>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()?;
+        if changes_value {
+            SecretVersion::record(&secret, identity, None).await?;
+        }
+        Ok(secret)
+    }
+}
+
+/// Sanitizing the collection and name can map different secrets to the same
+/// name, so it ends with the start of the secret's ID to keep names unique.
+fn image_pull_secret_name(id: Uuid, collection: &str, name: &str) -> String {
+    let suffix = &id.simple().to_string()[..8];
+    let sanitized = format!("{collection}-{name}")
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let mut name = format!("{IMAGE_PULL_SECRET_PREFIX}{sanitized}");
+    name.truncate(253 - suffix.len() - 1);
+    format!("{}-{suffix}", name.trim_end_matches('-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_pull_secret_name() {
+        let id = Uuid::parse_str("0f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9").unwrap();
+        assert_eq!(
+            image_pull_secret_name(id, "Registries", "GHCR read-only"),
+            "platz-registry-registries-ghcr-read-only-0f1e2d3c"
+        );
+        assert_eq!(
+            image_pull_secret_name(id, "docker", "_hub."),
+            "platz-registry-docker-hub-0f1e2d3c"
+        );
+        let long = image_pull_secret_name(id, "a", &"b".repeat(300));
+        assert!(long.len() <= 253);
+        assert!(long.ends_with("b-0f1e2d3c"));
+        assert_ne!(
+            image_pull_secret_name(Uuid::new_v4(), "a-b", "c"),
+            image_pull_secret_name(Uuid::new_v4(), "a", "b-c")
+        );
     }
 }
@@ -0,0 +1,178 @@
+use super::secret::Secret;
+use crate::{
+    DbResult, Identity, db_conn,
+    encryption::{self, master_keys},
+};
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::Serialize;
+use std::ops::DerefMut;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    secret_versions(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        secret_id -> Uuid,
+        contents -> Varchar,
+        data_key -> Nullable<Varchar>,
+        key_id -> Nullable<Varchar>,
+        fingerprint -> Nullable<Varchar>,
+        store_path -> Nullable<Varchar>,
+        store_key -> Nullable<Varchar>,
+        acting_user_id -> Nullable<Uuid>,
+        acting_bot_id -> Nullable<Uuid>,
+        acting_deployment_id -> Nullable<Uuid>,
+        restored_from_version_id -> Nullable<Uuid>,
+    }
+}
+
+/// A value a secret had, recorded whenever a secret is created, its value is
+/// updated, or a previous version is restored. The latest version is the
+/// secret's current value.
+#[derive(Debug, Identifiable, Queryable, Serialize, ToSchema)]
+#[diesel(table_name = secret_versions)]
+pub struct SecretVersion {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub secret_id: Uuid,
+    #[serde(skip)]
+    pub contents: String,
+    #[serde(skip)]
+    pub data_key: Option<String>,
+    #[schema(required)]
+    pub key_id: Option<String>,
+    /// Like [`Secret::fingerprint`]
+    #[schema(required)]
+    pub fingerprint: Option<String>,
+    #[schema(required)]
+    pub store_path: Option<String>,
+    #[schema(required)]
+    pub store_key: Option<String>,
+    #[schema(required)]
+    pub acting_user_id: Option<Uuid>,
+    #[schema(required)]
+    pub acting_bot_id: Option<Uuid>,
+    #[schema(required)]
+    pub acting_deployment_id: Option<Uuid>,
+    /// Set when this version was created by restoring a previous one
+    #[schema(required)]
+    pub restored_from_version_id: Option<Uuid>,
+}
+
+impl SecretVersion {
+    fn decrypted(mut self) -> DbResult<Self> {
+        self.contents = encryption::open(
+            &self.contents,
+            self.data_key.as_deref(),
+            self.key_id.as_deref(),
+        )?;
+        if self.fingerprint.is_none() && self.store_path.is_none() {
+            self.fingerprint = Some(encryption::fingerprint(&self.contents)?);
+        }
+        Ok(self)
+    }
+
+    /// Versions of a secret, newest first
+    pub async fn all_for_secret(
+        secret_id: Uuid,
+        pagination: PaginationParams,
+    ) -> DbResult<Paginated<Self>> {
+        let mut paginated: Paginated<Self> = secret_versions::table
+            .filter(secret_versions::secret_id.eq(secret_id))
+            .order_by(secret_versions::created_at.desc())
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?;
+        paginated.items = paginated
+            .items
+            .into_iter()
+            .map(Self::decrypted)
+            .collect::<DbResult<_>>()?;
+        Ok(paginated)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        secret_versions::table
+            .find(id)
+            .get_result::<Self>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
+    }
+
+    /// Records the secret's current value as a new version
+    pub(crate) async fn record(
+        secret: &Secret,
+        identity: &Identity,
+        restored_from_version_id: Option<Uuid>,
+    ) -> DbResult<Self> {
+        let sealed = encryption::seal(secret.contents.clone())?;
+        diesel::insert_into(secret_versions::table)
+            .values((
+                secret_versions::secret_id.eq(secret.id),
+                secret_versions::contents.eq(sealed.value),
+                secret_versions::data_key.eq(sealed.data_key),
+                secret_versions::key_id.eq(sealed.key_id),
+                secret_versions::fingerprint.eq(&secret.fingerprint),
+                secret_versions::store_path.eq(&secret.store_path),
+                secret_versions::store_key.eq(&secret.store_key),
+                secret_versions::acting_user_id.eq(identity.user_id()),
+                secret_versions::acting_bot_id.eq(identity.bot_id()),
+                secret_versions::acting_deployment_id.eq(identity.deployment_id()),
+                secret_versions::restored_from_version_id.eq(restored_from_version_id),
+            ))
+            .get_result::<Self>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
+    }
+
+    /// Like [`Secret::rewrap_all`], for secret versions
+    pub async fn rewrap_all() -> DbResult<usize> {
+        let Some(keys) = master_keys()? else {
+            return Ok(0);
+        };
+        let current_key_id = keys.current_key_id();
+        let stale = secret_versions::table
+            .filter(secret_versions::key_id.is_distinct_from(current_key_id))
+            .select((
+                secret_versions::id,
+                secret_versions::contents,
+                secret_versions::data_key,
+                secret_versions::key_id,
+                secret_versions::store_path,
+            ))
+            .get_results::<(Uuid, String, Option<String>, Option<String>, Option<String>)>(
+                db_conn().await?.deref_mut(),
+            )
+            .await?;
+        let mut updated = 0;
+        for (id, contents, data_key, key_id, store_path) in stale {
+            let fingerprint = store_path
+                .is_none()
+                .then(|| {
+                    encryption::open(&contents, data_key.as_deref(), key_id.as_deref())
+                        .map(|plaintext| keys.fingerprint(plaintext.as_bytes()))
+                })
+                .transpose()?;
+            let (contents, data_key) =
+                keys.reseal(contents, data_key.as_deref(), key_id.as_deref())?;
+            updated += diesel::update(
+                secret_versions::table
+                    .find(id)
+                    .filter(secret_versions::key_id.is_not_distinct_from(key_id)),
+            )
+            .set((
+                secret_versions::contents.eq(contents),
+                secret_versions::data_key.eq(data_key),
+                secret_versions::key_id.eq(current_key_id),
+                secret_versions::fingerprint.eq(fingerprint),
+            ))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        }
+        Ok(updated)
+    }
+}
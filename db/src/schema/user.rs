@@ -104,6 +104,8 @@ impl NewUser {
                     env_id: env.id,
                     user_id: user.id,
                     role: EnvUserRole::User,
+                    custom_role_id: None,
+                    expires_at: None,
                 }
                 .insert()
                 .await?;
@@ -0,0 +1,319 @@
+use crate::{
+    AccessScope, DbResult, db_conn,
+    encryption::{self, master_keys},
+};
+use aws_lc_rs::hmac;
+use chrono::prelude::*;
+use diesel::{QueryDsl, prelude::*};
+use diesel_async::RunQueryDsl;
+use diesel_filter::DieselFilter;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write, ops::DerefMut};
+use strum::{AsRefStr, Display, EnumIter, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    webhooks(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        env_id -> Uuid,
+        kind_id -> Nullable<Uuid>,
+        name -> Varchar,
+        url -> Varchar,
+        event_types -> Array<Varchar>,
+        enabled -> Bool,
+        signing_secret -> Varchar,
+        data_key -> Nullable<Varchar>,
+        key_id -> Nullable<Varchar>,
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    AsRefStr,
+    Display,
+    ToSchema,
+)]
+pub enum WebhookEventType {
+    TaskStarted,
+    TaskFailed,
+    TaskCanceled,
+    TaskDone,
+    /// The deployment's status changed, e.g. from `Upgrading` to `Running`
+    DeploymentStatusChanged,
+    /// The status reported by the deployment turned to `danger`
+    DeploymentReportedDanger,
+}
+
+/// An HTTP endpoint notified of task and deployment events in an env.
+/// Payloads are signed with the webhook's signing secret, see
+/// [`Webhook::signature`].
+#[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = webhooks)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter]
+    pub env_id: Uuid,
+    /// Only send events of deployments of this kind
+    #[schema(required)]
+    pub kind_id: Option<Uuid>,
+    #[filter(insensitive, substring)]
+    pub name: String,
+    pub url: String,
+    /// Events to send, all events if empty
+    #[schema(value_type = Vec<WebhookEventType>)]
+    pub event_types: Vec<String>,
+    #[filter]
+    pub enabled: bool,
+    /// The decrypted signing secret, never returned by the API
+    #[serde(skip)]
+    pub signing_secret: String,
+    #[serde(skip)]
+    pub data_key: Option<String>,
+    #[schema(required)]
+    pub key_id: Option<String>,
+}
+
+impl Webhook {
+    fn decrypted(mut self) -> DbResult<Self> {
+        self.signing_secret = encryption::open(
+            &self.signing_secret,
+            self.data_key.as_deref(),
+            self.key_id.as_deref(),
+        )?;
+        Ok(self)
+    }
+
+    pub fn wants(&self, event_type: WebhookEventType) -> bool {
+        self.event_types.is_empty()
+            || self
+                .event_types
+                .iter()
+                .any(|wanted| wanted == event_type.as_ref())
+    }
+
+    /// Signature of a payload sent at `timestamp`, sent in the
+    /// `X-Platz-Signature` header
+    pub fn signature(&self, timestamp: i64, body: &[u8]) -> String {
+        signature(&self.signing_secret, timestamp, body)
+    }
+
+    pub async fn all_filtered(
+        filters: WebhookFilters,
+        pagination: PaginationParams,
+        scope: &AccessScope,
+    ) -> DbResult<Paginated<Self>> {
+        let mut filtered = Self::filter(filters);
+        if let AccessScope::Envs { env_ids, .. } = scope {
+            filtered = filtered.filter(webhooks::env_id.eq_any(env_ids.clone()));
+        }
+        let mut paginated: Paginated<Self> = filtered
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?;
+        paginated.items = paginated
+            .items
+            .into_iter()
+            .map(Self::decrypted)
+            .collect::<DbResult<_>>()?;
+        Ok(paginated)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        webhooks::table
+            .find(id)
+            .get_result::<Self>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
+    }
+
+    /// Like [`Self::find`] but only returns the webhook if it is within the
+    /// identity's [`AccessScope`]. Out-of-scope and missing both yield
+    /// `NotFound`.
+    pub async fn find_scoped(id: Uuid, scope: &AccessScope) -> DbResult<Self> {
+        match scope {
+            AccessScope::All => Self::find(id).await,
+            AccessScope::Envs { env_ids, .. } => webhooks::table
+                .find(id)
+                .filter(webhooks::env_id.eq_any(env_ids.clone()))
+                .get_result::<Self>(db_conn().await?.deref_mut())
+                .await?
+                .decrypted(),
+        }
+    }
+
+    /// Enabled webhooks notified of events of deployments of a kind in an env
+    pub async fn all_subscribed(env_id: Uuid, kind_id: Uuid) -> DbResult<Vec<Self>> {
+        webhooks::table
+            .filter(webhooks::env_id.eq(env_id))
+            .filter(
+                webhooks::kind_id
+                    .is_null()
+                    .or(webhooks::kind_id.eq(kind_id)),
+            )
+            .filter(webhooks::enabled.eq(true))
+            .get_results(db_conn().await?.deref_mut())
+            .await?
+            .into_iter()
+            .map(Self::decrypted)
+            .collect()
+    }
+
+    /// Like [`super::secret::Secret::rewrap_all`], for webhook signing secrets
+    pub async fn rewrap_all() -> DbResult<usize> {
+        let Some(keys) = master_keys()? else {
+            return Ok(0);
+        };
+        let current_key_id = keys.current_key_id();
+        let stale = webhooks::table
+            .filter(webhooks::key_id.is_distinct_from(current_key_id))
+            .select((
+                webhooks::id,
+                webhooks::signing_secret,
+                webhooks::data_key,
+                webhooks::key_id,
+            ))
+            .get_results::<(Uuid, String, Option<String>, Option<String>)>(
+                db_conn().await?.deref_mut(),
+            )
+            .await?;
+        let mut updated = 0;
+        for (id, signing_secret, data_key, key_id) in stale {
+            let (signing_secret, data_key) =
+                keys.reseal(signing_secret, data_key.as_deref(), key_id.as_deref())?;
+            updated += diesel::update(
+                webhooks::table
+                    .find(id)
+                    .filter(webhooks::key_id.is_not_distinct_from(key_id)),
+            )
+            .set((
+                webhooks::signing_secret.eq(signing_secret),
+                webhooks::data_key.eq(data_key),
+                webhooks::key_id.eq(current_key_id),
+            ))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        }
+        Ok(updated)
+    }
+
+    pub async fn delete(&self) -> DbResult<()> {
+        diesel::delete(webhooks::table.find(self.id))
+            .execute(db_conn().await?.deref_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewWebhook {
+    pub env_id: Uuid,
+    #[serde(default)]
+    pub kind_id: Option<Uuid>,
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub event_types: Vec<WebhookEventType>,
+    /// Used for signing payloads, can't be read back
+    pub signing_secret: String,
+}
+
+impl NewWebhook {
+    pub async fn insert(self) -> DbResult<Webhook> {
+        let sealed = encryption::seal(self.signing_secret)?;
+        diesel::insert_into(webhooks::table)
+            .values((
+                webhooks::env_id.eq(self.env_id),
+                webhooks::kind_id.eq(self.kind_id),
+                webhooks::name.eq(self.name),
+                webhooks::url.eq(self.url),
+                webhooks::event_types.eq(event_type_names(&self.event_types)),
+                webhooks::signing_secret.eq(sealed.value),
+                webhooks::data_key.eq(sealed.data_key),
+                webhooks::key_id.eq(sealed.key_id),
+            ))
+            .get_result::<Webhook>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateWebhook {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub event_types: Option<Vec<WebhookEventType>>,
+    pub enabled: Option<bool>,
+    pub signing_secret: Option<String>,
+}
+
+impl UpdateWebhook {
+    pub async fn save(self, id: Uuid) -> DbResult<Webhook> {
+        let signing_secret = self.signing_secret.map(encryption::seal).transpose()?;
+        diesel::update(webhooks::table.find(id))
+            .set((
+                self.name.map(|name| webhooks::name.eq(name)),
+                self.url.map(|url| webhooks::url.eq(url)),
+                self.event_types
+                    .map(|event_types| webhooks::event_types.eq(event_type_names(&event_types))),
+                self.enabled.map(|enabled| webhooks::enabled.eq(enabled)),
+                signing_secret.map(|sealed| {
+                    (
+                        webhooks::signing_secret.eq(sealed.value),
+                        webhooks::data_key.eq(sealed.data_key),
+                        webhooks::key_id.eq(sealed.key_id),
+                    )
+                }),
+            ))
+            .get_result::<Webhook>(db_conn().await?.deref_mut())
+            .await?
+            .decrypted()
+    }
+}
+
+fn event_type_names(event_types: &[WebhookEventType]) -> Vec<String> {
+    event_types
+        .iter()
+        .map(|event_type| event_type.to_string())
+        .collect()
+}
+
+/// `sha256=` followed by the hex encoded HMAC-SHA256 of `{timestamp}.{body}`.
+/// Including the timestamp lets receivers reject replayed payloads.
+fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let mut ctx = hmac::Context::with_key(&key);
+    ctx.update(format!("{timestamp}.").as_bytes());
+    ctx.update(body);
+    ctx.sign()
+        .as_ref()
+        .iter()
+        .fold(String::from("sha256="), |mut signature, byte| {
+            let _ = write!(signature, "{byte:02x}");
+            signature
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            signature("secret", 1700000000, br#"{"event":"TaskDone"}"#),
+            "sha256=ed204b5f0b5b732fba9849c252c4ad22a743d60415de5bf7216378f001102a84"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use super::webhook::{WebhookEventType, webhooks};
+use crate::{AccessScope, DbResult, db_conn};
+use chrono::prelude::*;
+use diesel::{QueryDsl, prelude::*};
+use diesel_async::RunQueryDsl;
+use diesel_enum_derive::DieselEnum;
+use diesel_filter::DieselFilter;
+use diesel_pagination::{Paginate, Paginated, PaginationParams};
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use strum::{AsRefStr, Display, EnumIter, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+table! {
+    webhook_deliveries(id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        webhook_id -> Uuid,
+        event_type -> Varchar,
+        event_key -> Varchar,
+        payload -> Jsonb,
+        status -> Varchar,
+        attempts -> Integer,
+        next_attempt_at -> Timestamptz,
+        last_attempted_at -> Nullable<Timestamptz>,
+        delivered_at -> Nullable<Timestamptz>,
+        response_status -> Nullable<Integer>,
+        error -> Nullable<Varchar>,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(webhook_deliveries, webhooks);
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    AsRefStr,
+    Display,
+    DieselEnum,
+    ToSchema,
+)]
+pub enum WebhookDeliveryStatus {
+    /// Waiting for its first attempt or for a retry
+    Pending,
+    Delivered,
+    /// Ran out of attempts, kept until redelivered
+    DeadLetter,
+}
+
+/// An event sent to a webhook
+#[derive(Debug, Identifiable, Queryable, Serialize, DieselFilter, ToSchema)]
+#[diesel(table_name = webhook_deliveries)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[filter]
+    pub webhook_id: Uuid,
+    #[filter]
+    #[schema(value_type = WebhookEventType)]
+    pub event_type: String,
+    /// Identifies the event, the same event is only sent once to each webhook
+    pub event_key: String,
+    /// The request body sent to the webhook
+    pub payload: serde_json::Value,
+    #[filter]
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    #[schema(required)]
+    pub last_attempted_at: Option<DateTime<Utc>>,
+    #[schema(required)]
+    pub delivered_at: Option<DateTime<Utc>>,
+    /// HTTP status of the last attempt's response
+    #[schema(required)]
+    pub response_status: Option<i32>,
+    /// Why the last attempt failed
+    #[schema(required)]
+    pub error: Option<String>,
+}
+
+impl WebhookDelivery {
+    pub async fn all_filtered(
+        filters: WebhookDeliveryFilters,
+        pagination: PaginationParams,
+        scope: &AccessScope,
+    ) -> DbResult<Paginated<Self>> {
+        let mut filtered = Self::filter(filters);
+        if let AccessScope::Envs { env_ids, .. } = scope {
+            filtered = filtered.filter(
+                webhook_deliveries::webhook_id.eq_any(
+                    webhooks::table
+                        .select(webhooks::id)
+                        .filter(webhooks::env_id.eq_any(env_ids.clone())),
+                ),
+            );
+        }
+        Ok(filtered
+            .order_by(webhook_deliveries::created_at.desc())
+            .paginate(pagination)
+            .load_and_count(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    pub async fn find(id: Uuid) -> DbResult<Self> {
+        Ok(webhook_deliveries::table
+            .find(id)
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Adds an event to a webhook's deliveries, unless an event with the same
+    /// key was already added. Returns the new delivery.
+    pub async fn enqueue(
+        webhook_id: Uuid,
+        event_type: WebhookEventType,
+        event_key: String,
+        payload: serde_json::Value,
+    ) -> DbResult<Option<Self>> {
+        Ok(diesel::insert_into(webhook_deliveries::table)
+            .values((
+                webhook_deliveries::webhook_id.eq(webhook_id),
+                webhook_deliveries::event_type.eq(event_type.to_string()),
+                webhook_deliveries::event_key.eq(event_key),
+                webhook_deliveries::payload.eq(payload),
+            ))
+            .on_conflict((
+                webhook_deliveries::webhook_id,
+                webhook_deliveries::event_key,
+            ))
+            .do_nothing()
+            .get_result(db_conn().await?.deref_mut())
+            .await
+            .optional()?)
+    }
+
+    /// Pending deliveries due for an attempt, oldest first
+    pub async fn all_due(limit: i64) -> DbResult<Vec<Self>> {
+        Ok(webhook_deliveries::table
+            .filter(webhook_deliveries::status.eq(WebhookDeliveryStatus::Pending))
+            .filter(webhook_deliveries::next_attempt_at.le(diesel::dsl::now))
+            .order_by(webhook_deliveries::next_attempt_at.asc())
+            .limit(limit)
+            .get_results(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Takes the delivery for an attempt by pushing its next attempt to
+    /// `lease_until`, so other workers skip it meanwhile. Returns `None` if
+    /// another worker took it first.
+    pub async fn claim(&self, lease_until: DateTime<Utc>) -> DbResult<Option<Self>> {
+        Ok(diesel::update(
+            webhook_deliveries::table
+                .find(self.id)
+                .filter(webhook_deliveries::status.eq(WebhookDeliveryStatus::Pending))
+                .filter(webhook_deliveries::next_attempt_at.eq(self.next_attempt_at)),
+        )
+        .set(webhook_deliveries::next_attempt_at.eq(lease_until))
+        .get_result(db_conn().await?.deref_mut())
+        .await
+        .optional()?)
+    }
+
+    pub async fn set_delivered(&self, response_status: i32) -> DbResult<Self> {
+        Ok(diesel::update(webhook_deliveries::table.find(self.id))
+            .set((
+                webhook_deliveries::status.eq(WebhookDeliveryStatus::Delivered),
+                webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+                webhook_deliveries::last_attempted_at.eq(diesel::dsl::now),
+                webhook_deliveries::delivered_at.eq(diesel::dsl::now),
+                webhook_deliveries::response_status.eq(response_status),
+                webhook_deliveries::error.eq(None::<String>),
+            ))
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Records a failed attempt. The delivery is retried at `retry_at`, or
+    /// becomes a dead letter if it's `None`.
+    pub async fn set_failed(
+        &self,
+        response_status: Option<i32>,
+        error: String,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> DbResult<Self> {
+        Ok(diesel::update(webhook_deliveries::table.find(self.id))
+            .set((
+                webhook_deliveries::status.eq(match retry_at {
+                    Some(_) => WebhookDeliveryStatus::Pending,
+                    None => WebhookDeliveryStatus::DeadLetter,
+                }),
+                webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+                webhook_deliveries::last_attempted_at.eq(diesel::dsl::now),
+                retry_at.map(|retry_at| webhook_deliveries::next_attempt_at.eq(retry_at)),
+                webhook_deliveries::response_status.eq(response_status),
+                webhook_deliveries::error.eq(error),
+            ))
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+
+    /// Deletes delivered deliveries and dead letters last attempted before the
+    /// given time. Pending deliveries are kept regardless of their age.
+    pub async fn delete_older_than(attempted_before: DateTime<Utc>) -> DbResult<usize> {
+        Ok(diesel::delete(
+            webhook_deliveries::table
+                .filter(webhook_deliveries::status.ne(WebhookDeliveryStatus::Pending))
+                .filter(webhook_deliveries::last_attempted_at.lt(attempted_before)),
+        )
+        .execute(db_conn().await?.deref_mut())
+        .await?)
+    }
+
+    /// Sends the delivery again, with a fresh set of attempts
+    pub async fn redeliver(&self) -> DbResult<Self> {
+        Ok(diesel::update(webhook_deliveries::table.find(self.id))
+            .set((
+                webhook_deliveries::status.eq(WebhookDeliveryStatus::Pending),
+                webhook_deliveries::attempts.eq(0),
+                webhook_deliveries::next_attempt_at.eq(diesel::dsl::now),
+            ))
+            .get_result(db_conn().await?.deref_mut())
+            .await?)
+    }
+}
@@ -0,0 +1,79 @@
+use super::deployment::Deployment;
+use crate::{DbResult, db_conn};
+use diesel::{
+    prelude::*,
+    sql_types::{Bool, Nullable, Uuid as SqlUuid, Varchar},
+};
+use diesel_async::RunQueryDsl;
+use std::ops::DerefMut;
+
+table! {
+    webhook_deployment_states(deployment_id) {
+        deployment_id -> Uuid,
+        updated_at -> Timestamptz,
+        status -> Varchar,
+        reported_danger -> Bool,
+    }
+}
+
+/// The state a deployment had before [`WebhookDeploymentState::observe`]
+/// noticed it changed. `None` fields mean the deployment wasn't observed
+/// before.
+#[derive(Debug, QueryableByName)]
+pub struct WebhookDeploymentState {
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub previous_status: Option<String>,
+    #[diesel(sql_type = Nullable<Bool>)]
+    pub previous_reported_danger: Option<bool>,
+}
+
+impl WebhookDeploymentState {
+    /// Records the deployment's current status, returning the previous one if
+    /// it changed since last observed. Concurrent observers of the same change
+    /// block on the row lock, so only one of them gets it.
+    pub async fn observe(deployment: &Deployment) -> DbResult<Option<Self>> {
+        Ok(diesel::sql_query(
+            "with previous as (
+                select status, reported_danger from webhook_deployment_states
+                where deployment_id = $1
+                for update
+            )
+            insert into webhook_deployment_states as s (deployment_id, status, reported_danger)
+            values ($1, $2, $3)
+            on conflict (deployment_id) do update
+                set status = excluded.status,
+                    reported_danger = excluded.reported_danger,
+                    updated_at = now()
+                where (s.status, s.reported_danger)
+                    is distinct from (excluded.status, excluded.reported_danger)
+            returning
+                (select status from previous) as previous_status,
+                (select reported_danger from previous) as previous_reported_danger",
+        )
+        .bind::<SqlUuid, _>(deployment.id)
+        .bind::<Varchar, _>(deployment.status.to_string())
+        .bind::<Bool, _>(reported_danger(deployment))
+        .get_result(db_conn().await?.deref_mut())
+        .await
+        .optional()?)
+    }
+
+    /// Whether the deployment's status changed. Deployments observed for the
+    /// first time didn't change, their status is just recorded.
+    pub fn status_changed(&self, deployment: &Deployment) -> bool {
+        self.previous_status
+            .as_deref()
+            .is_some_and(|previous| previous != deployment.status.as_ref())
+    }
+
+    pub fn turned_danger(&self, deployment: &Deployment) -> bool {
+        reported_danger(deployment) && self.previous_reported_danger != Some(true)
+    }
+}
+
+fn reported_danger(deployment: &Deployment) -> bool {
+    deployment
+        .reported_status
+        .as_ref()
+        .is_some_and(|reported_status| reported_status.is_danger())
+}
@@ -0,0 +1,334 @@
+//! External secret stores.
+//!
+//! Secrets are stored in Postgres by default, along with their contents. A
+//! secret can instead reference a value kept in an external store by a path
+//! and a key, which is read when deployments using it are installed. Only the
+//! reference is stored in Postgres.
+//!
+//! References are scoped to their env: `store_path` must be under
+//! `envs/<env id>`, so users who can manage secrets in one env can't reference
+//! another env's values, or anything else in the store. This is checked both
+//! when a reference is saved and when it's read.
+//!
+//! # Vault
+//!
+//! Values are read from a KV v2 secrets engine. `store_path` is the secret's
+//! path in the engine and `store_key` is the key within it.
+//!
+//! * `PLATZ_VAULT_ADDR`: Vault's address, e.g. `https://vault.example.com:8200`
+//! * `PLATZ_VAULT_TOKEN`, or `PLATZ_VAULT_TOKEN_FILE` pointing to a file
+//!   containing the token. The file is read on every request, so tokens
+//!   renewed by an agent are picked up.
+//! * `PLATZ_VAULT_KV_MOUNT`: where the KV engine is mounted, defaults to
+//!   `secret`
+//! * `PLATZ_VAULT_NAMESPACE`: optional Vault Enterprise namespace
+//!
+//! # Files
+//!
+//! Values are read from files under `PLATZ_SECRET_FILES_DIR`, laid out like a
+//! mounted Kubernetes secret: `store_path` is a directory relative to it and
+//! `store_key` is a file in that directory.
+
+use lazy_static::lazy_static;
+use reqwest::StatusCode;
+use std::{
+    env,
+    path::{Component, Path, PathBuf},
+};
+use url::Url;
+use uuid::Uuid;
+
+const PLATZ_VAULT_ADDR: &str = "PLATZ_VAULT_ADDR";
+const PLATZ_VAULT_TOKEN: &str = "PLATZ_VAULT_TOKEN";
+const PLATZ_VAULT_TOKEN_FILE: &str = "PLATZ_VAULT_TOKEN_FILE";
+const PLATZ_VAULT_KV_MOUNT: &str = "PLATZ_VAULT_KV_MOUNT";
+const PLATZ_VAULT_NAMESPACE: &str = "PLATZ_VAULT_NAMESPACE";
+const PLATZ_SECRET_FILES_DIR: &str = "PLATZ_SECRET_FILES_DIR";
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum SecretStoreError {
+    #[error("The {0} secret store is not configured, set {1}")]
+    NotConfigured(&'static str, &'static str),
+
+    #[error("Invalid {0} secret store configuration: {1}")]
+    InvalidConfig(&'static str, String),
+
+    #[error("Invalid secret path {0:?}, expected relative path segments without `.` or `..`")]
+    InvalidPath(String),
+
+    #[error("Secret path {0:?} is outside of its env, expected it under {1}")]
+    OutsideEnv(String, String),
+
+    #[error("Invalid secret key {0:?}")]
+    InvalidKey(String),
+
+    #[error("Failed reading the Vault token from {0}: {1}")]
+    VaultTokenError(String, String),
+
+    #[error("Failed reading {0} from Vault: {1}")]
+    VaultRequestError(String, String),
+
+    #[error("Vault denied access to {0}, check the token's policies")]
+    VaultPermissionDenied(String),
+
+    #[error("Failed reading {0} from Vault, got HTTP status {1}")]
+    VaultStatusError(String, StatusCode),
+
+    #[error("Secret {0} does not exist in the store")]
+    SecretNotFound(String),
+
+    #[error("Secret {0} has no key {1:?}")]
+    KeyNotFound(String, String),
+
+    #[error("Failed reading {0}: {1}")]
+    FileError(String, String),
+}
+
+type Result<T> = std::result::Result<T, SecretStoreError>;
+
+/// A store of secret values, addressed by a path and a key within it.
+pub trait SecretStore {
+    fn read(
+        &self,
+        env_id: Uuid,
+        path: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<String>> + Send;
+}
+
+/// The path all references of an env must be under
+pub fn env_prefix(env_id: Uuid) -> PathBuf {
+    Path::new("envs").join(env_id.to_string())
+}
+
+/// Secret paths are always relative and may not escape their root, whether
+/// it's a directory or a Vault mount, or their env's prefix.
+fn validate_path(env_id: Uuid, path: &str) -> Result<PathBuf> {
+    let parsed = Path::new(path);
+    if path.is_empty()
+        || !parsed
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(SecretStoreError::InvalidPath(path.to_owned()));
+    }
+    let prefix = env_prefix(env_id);
+    if !parsed.starts_with(&prefix) {
+        return Err(SecretStoreError::OutsideEnv(
+            path.to_owned(),
+            prefix.display().to_string(),
+        ));
+    }
+    Ok(parsed.to_path_buf())
+}
+
+fn validate_key(key: &str) -> Result<()> {
+    let mut components = Path::new(key).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(SecretStoreError::InvalidKey(key.to_owned())),
+    }
+}
+
+/// Checks the path and key of a secret reference without reading it.
+pub fn validate_reference(env_id: Uuid, path: &str, key: &str) -> Result<()> {
+    validate_path(env_id, path)?;
+    validate_key(key)
+}
+
+enum VaultToken {
+    Static(String),
+    File(String),
+}
+
+pub struct VaultStore {
+    client: reqwest::Client,
+    addr: Url,
+    mount: String,
+    namespace: Option<String>,
+    token: VaultToken,
+}
+
+impl VaultStore {
+    fn from_env() -> Result<Self> {
+        let addr = env::var(PLATZ_VAULT_ADDR)
+            .map_err(|_| SecretStoreError::NotConfigured("Vault", PLATZ_VAULT_ADDR))?;
+        let addr = Url::parse(&addr)
+            .map_err(|err| SecretStoreError::InvalidConfig("Vault", format!("{addr}: {err}")))?;
+        let token = match (
+            env::var(PLATZ_VAULT_TOKEN).ok(),
+            env::var(PLATZ_VAULT_TOKEN_FILE).ok(),
+        ) {
+            (Some(token), _) => VaultToken::Static(token),
+            (None, Some(path)) => VaultToken::File(path),
+            (None, None) => {
+                return Err(SecretStoreError::NotConfigured("Vault", PLATZ_VAULT_TOKEN));
+            }
+        };
+        Ok(Self {
+            client: reqwest::Client::new(),
+            addr,
+            mount: env::var(PLATZ_VAULT_KV_MOUNT)
+                .unwrap_or_else(|_| "secret".to_owned())
+                .trim_matches('/')
+                .to_owned(),
+            namespace: env::var(PLATZ_VAULT_NAMESPACE).ok(),
+            token,
+        })
+    }
+
+    async fn token(&self) -> Result<String> {
+        match &self.token {
+            VaultToken::Static(token) => Ok(token.clone()),
+            VaultToken::File(path) => tokio::fs::read_to_string(path)
+                .await
+                .map(|token| token.trim().to_owned())
+                .map_err(|err| SecretStoreError::VaultTokenError(path.clone(), err.to_string())),
+        }
+    }
+}
+
+impl SecretStore for VaultStore {
+    async fn read(&self, env_id: Uuid, path: &str, key: &str) -> Result<String> {
+        validate_reference(env_id, path, key)?;
+        let url = self
+            .addr
+            .join(&format!("v1/{}/data/{}", self.mount, path))
+            .map_err(|_| SecretStoreError::InvalidPath(path.to_owned()))?;
+
+        let mut request = self
+            .client
+            .get(url)
+            .header("X-Vault-Token", self.token().await?);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|err| SecretStoreError::VaultRequestError(path.to_owned(), err.to_string()))?;
+
+        match response.status() {
+            StatusCode::OK => (),
+            StatusCode::NOT_FOUND => return Err(SecretStoreError::SecretNotFound(path.to_owned())),
+            StatusCode::FORBIDDEN => {
+                return Err(SecretStoreError::VaultPermissionDenied(path.to_owned()));
+            }
+            status => return Err(SecretStoreError::VaultStatusError(path.to_owned(), status)),
+        }
+
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|err| SecretStoreError::VaultRequestError(path.to_owned(), err.to_string()))?;
+        // KV v2 wraps the secret's data with its metadata
+        match body.pointer("/data/data").and_then(|data| data.get(key)) {
+            Some(serde_json::Value::String(value)) => Ok(value.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => Err(SecretStoreError::KeyNotFound(
+                path.to_owned(),
+                key.to_owned(),
+            )),
+        }
+    }
+}
+
+pub struct FilesStore {
+    root: PathBuf,
+}
+
+impl FilesStore {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            root: env::var(PLATZ_SECRET_FILES_DIR)
+                .map_err(|_| SecretStoreError::NotConfigured("files", PLATZ_SECRET_FILES_DIR))?
+                .into(),
+        })
+    }
+}
+
+impl SecretStore for FilesStore {
+    async fn read(&self, env_id: Uuid, path: &str, key: &str) -> Result<String> {
+        validate_key(key)?;
+        let file = self.root.join(validate_path(env_id, path)?).join(key);
+        tokio::fs::read_to_string(&file)
+            .await
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => {
+                    SecretStoreError::KeyNotFound(path.to_owned(), key.to_owned())
+                }
+                _ => SecretStoreError::FileError(file.display().to_string(), err.to_string()),
+            })
+    }
+}
+
+lazy_static! {
+    static ref VAULT_STORE: Result<VaultStore> = VaultStore::from_env();
+    static ref FILES_STORE: Result<FilesStore> = FilesStore::from_env();
+}
+
+pub fn vault_store() -> Result<&'static VaultStore> {
+    VAULT_STORE.as_ref().map_err(Clone::clone)
+}
+
+pub fn files_store() -> Result<&'static FilesStore> {
+    FILES_STORE.as_ref().map_err(Clone::clone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_reference() {
+        let env_id = Uuid::new_v4();
+        let path = |suffix: &str| format!("envs/{env_id}/{suffix}");
+        assert!(validate_reference(env_id, &path("billing"), "password").is_ok());
+        assert!(validate_reference(env_id, &path("../../etc"), "passwd").is_err());
+        assert!(validate_reference(env_id, "/etc", "passwd").is_err());
+        assert!(validate_reference(env_id, "", "password").is_err());
+        assert!(validate_reference(env_id, &path("apps"), "nested/password").is_err());
+        assert!(validate_reference(env_id, &path("apps"), "..").is_err());
+        assert!(matches!(
+            validate_reference(env_id, "apps/billing", "password"),
+            Err(SecretStoreError::OutsideEnv(_, _))
+        ));
+        assert!(matches!(
+            validate_reference(Uuid::new_v4(), &path("billing"), "password"),
+            Err(SecretStoreError::OutsideEnv(_, _))
+        ));
+        assert!(matches!(
+            validate_reference(env_id, &format!("envs/{env_id}-other/billing"), "password"),
+            Err(SecretStoreError::OutsideEnv(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_files_store() {
+        let env_id = Uuid::new_v4();
+        let billing = format!("envs/{env_id}/billing");
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join(&billing)).unwrap();
+        std::fs::write(root.path().join(&billing).join("password"), "hunter2").unwrap();
+        let store = FilesStore {
+            root: root.path().to_path_buf(),
+        };
+
+        assert_eq!(
+            store.read(env_id, &billing, "password").await.unwrap(),
+            "hunter2"
+        );
+        assert!(matches!(
+            store.read(env_id, &billing, "username").await,
+            Err(SecretStoreError::KeyNotFound(_, _))
+        ));
+        assert!(matches!(
+            store.read(env_id, "../billing", "password").await,
+            Err(SecretStoreError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            store.read(Uuid::new_v4(), &billing, "password").await,
+            Err(SecretStoreError::OutsideEnv(_, _))
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::k8s::tracker::K8S_TRACKER;
+use anyhow::Result;
+use platz_db::{
+    DbTable, DbTableOrDeploymentResource, Identity,
+    schema::{
+        deployment::{Deployment, DeploymentStatus, UpdateDeployment},
+        deployment_task::{DeploymentTask, DeploymentTaskStatus},
+        k8s_cluster_drain::{K8sClusterDrain, K8sClusterDrainStatus},
+    },
+};
+use tokio::time;
+use tracing::{debug, error, info};
+
+const DRAIN_POLL_INTERVAL: time::Duration = time::Duration::from_secs(10);
+
+#[tracing::instrument(err, skip_all, name = "cluster-drain")]
+pub async fn start() -> Result<()> {
+    let mut interval = time::interval(DRAIN_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let cluster_ids = K8S_TRACKER.get_ids().await;
+        for drain in K8sClusterDrain::find_running()
+            .await?
+            .into_iter()
+            .filter(|drain| cluster_ids.contains(&drain.cluster_id))
+        {
+            let drain_id = drain.id;
+            if let Err(err) = advance_drain(drain).await {
+                error!("Failed advancing cluster drain {drain_id}: {err:?}");
+            }
+        }
+    }
+}
+
+/// Moves the next batch of deployments once all tasks of the previous batch
+/// have finished, and finishes the drain when no deployments are left.
+#[tracing::instrument(err, skip_all, fields(drain_id = %drain.id))]
+async fn advance_drain(drain: K8sClusterDrain) -> Result<()> {
+    let tasks = DeploymentTask::find_by_deployment_ids_since(
+        drain.migrated_deployment_ids.clone(),
+        drain.created_at,
+    )
+    .await?;
+
+    if let Some(task) = tasks
+        .iter()
+        .find(|task| task.status == DeploymentTaskStatus::Failed)
+    {
+        let deployment = Deployment::find(task.deployment_id).await?;
+        info!("Stopping drain, migrating {} failed", deployment.name);
+        drain
+            .finish(
+                K8sClusterDrainStatus::Failed,
+                Some(format!(
+                    "Migrating deployment {} failed: {}",
+                    deployment.name,
+                    task.reason.as_deref().unwrap_or("unknown error")
+                )),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let in_flight = tasks
+        .iter()
+        .filter(|task| {
+            matches!(
+                task.status,
+                DeploymentTaskStatus::Pending | DeploymentTaskStatus::Started
+            )
+        })
+        .count();
+    if in_flight > 0 {
+        debug!("Waiting for {in_flight} tasks to finish");
+        return Ok(());
+    }
+
+    // Deployments being deleted will be gone once their uninstall task finishes
+    let remaining = Deployment::find_by_cluster_id(drain.cluster_id)
+        .await?
+        .into_iter()
+        .filter(|deployment| deployment.status != DeploymentStatus::Deleting)
+        .collect::<Vec<_>>();
+    if remaining.is_empty() {
+        info!("Cluster {} is drained", drain.cluster_id);
+        drain.finish(K8sClusterDrainStatus::Done, None).await?;
+        return Ok(());
+    }
+
+    // Re-check right before moving deployments, in case the drain was
+    // canceled while waiting for the previous batch
+    let mut drain = K8sClusterDrain::find(drain.id).await?;
+    if drain.status != K8sClusterDrainStatus::Running {
+        return Ok(());
+    }
+
+    let identity = Identity::User(drain.created_by_user_id);
+    let batch_size = drain.batch_size.max(1) as usize;
+    for deployment in remaining.iter().take(batch_size) {
+        info!(
+            "Migrating deployment {} ({}) to cluster {}",
+            deployment.name, deployment.id, drain.target_cluster_id
+        );
+        // Retrying wouldn't help, and a deployment may have been moved
+        // without its tasks, so stop the drain for someone to look into it
+        if let Err(err) = migrate_deployment(deployment, &mut drain, &identity).await {
+            error!(
+                "Stopping drain, migrating {} failed: {err:?}",
+                deployment.name
+            );
+            drain
+                .finish(
+                    K8sClusterDrainStatus::Failed,
+                    Some(format!(
+                        "Migrating deployment {} failed: {err}",
+                        deployment.name
+                    )),
+                )
+                .await?;
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Moves a deployment to the drain's target cluster, the same way as changing
+/// its cluster in the API. The deployment is recorded as migrated as soon as
+/// it's moved, so its tasks are waited for even if creating them fails.
+async fn migrate_deployment(
+    old_deployment: &Deployment,
+    drain: &mut K8sClusterDrain,
+    identity: &Identity,
+) -> Result<()> {
+    let new_deployment = UpdateDeployment {
+        name: None,
+        cluster_id: Some(drain.target_cluster_id),
+        helm_chart_id: None,
+        config: None,
+        values_override: None,
+        enabled: None,
+        description_md: None,
+    }
+    .save(old_deployment.id)
+    .await?;
+    *drain = drain.add_migrated(vec![old_deployment.id]).await?;
+
+    if new_deployment.enabled {
+        DeploymentTask::create_recreate_task(old_deployment, &new_deployment, identity).await?;
+        DeploymentTask::create_upgrade_task(old_deployment, &new_deployment, identity).await?;
+        Deployment::reinstall_all_using(
+            &DbTableOrDeploymentResource::DbTable(DbTable::Deployments),
+            new_deployment.id,
+            identity,
+            format!(
+                "The {} deployment has been moved to another cluster",
+                old_deployment.name
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
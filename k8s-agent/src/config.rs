@@ -7,6 +7,9 @@ pub struct Config {
     #[command(flatten)]
     pub cluster_discovery: crate::k8s::cluster_discovery::Config,
 
+    #[command(flatten)]
+    pub cluster_health: crate::k8s::cluster_health::Config,
+
     #[arg(long, env = "PLATZ_SELF_NAMESPACE")]
     pub self_namespace: String,
 
@@ -39,6 +42,14 @@ pub struct Config {
     )]
     pub deployment_credentials_token_duration: humantime::Duration,
 
+    /// How often to sync env registry credentials to deployment namespaces.
+    #[arg(
+        long,
+        env = "PLATZ_REGISTRY_SECRETS_REFRESH_INTERVAL",
+        default_value = "1m"
+    )]
+    pub registry_secrets_refresh_interval: humantime::Duration,
+
     #[arg(long, env = "PLATZ_OWN_URL")]
     pub platz_url: Url,
 }
@@ -1,16 +1,25 @@
 use super::{
-    cluster_type::{K8s, LocalCluster},
+    cluster_type::{EksCluster, K8s, LocalCluster},
+    missing_clusters, static_clusters,
     tracker::K8S_TRACKER,
 };
 use anyhow::{Result, anyhow};
 use aws_types::region::Region;
+use chrono::prelude::*;
 use clap::ValueEnum;
 use futures::future::try_join_all;
+use platz_otel::health;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::time;
 use tracing::{debug, error, info};
 
+/// How long a discovery may take before its loop is considered wedged
+pub(super) const DISCOVERY_HEARTBEAT_GRACE: Duration = Duration::from_secs(10 * 60);
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
 #[clap(rename_all = "lowercase")]
 pub enum ClusterProvider {
@@ -18,6 +27,19 @@ pub enum ClusterProvider {
     Eks,
     /// Register a single cluster from a kubeconfig file.
     Local,
+    /// Register clusters listed in a YAML file or directory, reloaded on change.
+    Static,
+}
+
+impl ClusterProvider {
+    /// The provider name stored on discovered clusters
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Eks => "eks",
+            Self::Local => "local",
+            Self::Static => "static",
+        }
+    }
 }
 
 #[derive(clap::Args)]
@@ -26,6 +48,12 @@ pub struct Config {
     #[arg(long, env = "K8S_REFRESH_INTERVAL", default_value = "1h")]
     pub k8s_refresh_interval: humantime::Duration,
 
+    /// Mark clusters as missing (orphaning their deployments and failing
+    /// their pending tasks) once they haven't been discovered for this long.
+    /// Should be a few times `K8S_REFRESH_INTERVAL`.
+    #[arg(long, env = "PLATZ_CLUSTER_MISSING_THRESHOLD", default_value = "3h")]
+    pub cluster_missing_threshold: humantime::Duration,
+
     /// Selects how clusters are discovered. Defaults to `eks` (production behaviour);
     /// set to `local` for laptop/dev workflows that target a kubeconfig context.
     #[arg(
@@ -36,6 +64,27 @@ pub struct Config {
     )]
     pub provider: ClusterProvider,
 
+    /// Only discover EKS clusters in these regions (comma-separated).
+    /// Defaults to all regions enabled in the account.
+    #[arg(long, env = "PLATZ_EKS_REGIONS", value_delimiter = ',')]
+    pub eks_regions: Vec<String>,
+
+    /// Only register EKS clusters having all of these tags (comma-separated
+    /// `key=value` or `key`), e.g. `platz.io/managed=true`.
+    #[arg(long, env = "PLATZ_EKS_INCLUDE_TAGS", value_delimiter = ',')]
+    pub eks_include_tags: Vec<TagFilter>,
+
+    /// Skip EKS clusters having any of these tags (comma-separated `key=value`
+    /// or `key`).
+    #[arg(long, env = "PLATZ_EKS_EXCLUDE_TAGS", value_delimiter = ',')]
+    pub eks_exclude_tags: Vec<TagFilter>,
+
+    /// IAM roles to assume for discovering (and accessing) EKS clusters in
+    /// other accounts (comma-separated ARNs). Clusters in the running account
+    /// are always discovered.
+    #[arg(long, env = "PLATZ_EKS_ASSUME_ROLE_ARNS", value_delimiter = ',')]
+    pub eks_assume_role_arns: Vec<String>,
+
     /// Path to the kubeconfig file used in `local` mode.
     /// Falls back to `$KUBECONFIG`, then `~/.kube/config`.
     #[arg(long, env = "PLATZ_LOCAL_KUBECONFIG")]
@@ -45,63 +94,149 @@ pub struct Config {
     /// Defaults to the kubeconfig's `current-context`.
     #[arg(long, env = "PLATZ_LOCAL_CONTEXT")]
     pub local_context: Option<String>,
+
+    /// Path to a YAML file, or a directory of YAML files, listing the
+    /// clusters to register in `static` mode.
+    #[arg(long, env = "PLATZ_STATIC_CLUSTERS")]
+    pub static_clusters: Option<PathBuf>,
+
+    /// How often to check the static clusters file(s) for changes.
+    #[arg(
+        long,
+        env = "PLATZ_STATIC_CLUSTERS_RELOAD_INTERVAL",
+        default_value = "30s"
+    )]
+    pub static_clusters_reload_interval: humantime::Duration,
 }
 
 pub async fn run_cluster_discovery(config: &Config) -> Result<()> {
-    let mut interval = time::interval(config.k8s_refresh_interval.into());
+    health::set_ready("clusters", Err("Clusters not discovered yet".to_owned()));
+    if config.provider == ClusterProvider::Static {
+        return static_clusters::run_static_cluster_discovery(config).await;
+    }
+
+    let refresh_interval: Duration = config.k8s_refresh_interval.into();
+    let mut interval = time::interval(refresh_interval);
 
     loop {
         interval.tick().await;
+        health::heartbeat(
+            "cluster_discovery",
+            refresh_interval + DISCOVERY_HEARTBEAT_GRACE,
+        );
         if let Err(err) = load_clusters(config).await {
             error!("Error scanning for clusters: {:?}", err);
         }
     }
 }
 
-async fn load_clusters(config: &Config) -> Result<()> {
+pub(super) async fn load_clusters(config: &Config) -> Result<()> {
     let tracker_tx = K8S_TRACKER.inbound_requests_tx().await;
+    let started_at = Utc::now();
 
     for cluster in discover_clusters(config).await?.into_iter() {
         tracing::debug!(%cluster);
         tracker_tx.send(Arc::new(cluster))?;
     }
+    // Clusters stay loaded even if a later discovery fails
+    health::set_ready("clusters", Ok(()));
 
-    Ok(())
+    missing_clusters::mark_missing_clusters(config, started_at).await
 }
 
 #[tracing::instrument(skip_all, err, ret)]
 async fn discover_clusters(config: &Config) -> Result<Vec<K8s>> {
     match config.provider {
-        ClusterProvider::Eks => discover_eks_clusters().await,
+        ClusterProvider::Eks => discover_eks_clusters(config).await,
         ClusterProvider::Local => discover_local_cluster(config).await.map(|c| vec![c]),
+        ClusterProvider::Static => static_clusters::load_static_clusters(config).await,
     }
 }
 
-async fn discover_eks_clusters() -> Result<Vec<K8s>> {
+async fn discover_eks_clusters(config: &Config) -> Result<Vec<K8s>> {
     debug!("starting EKS discovery...");
     let shared_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-    let ec2 = aws_sdk_ec2::Client::new(&shared_config);
-    debug!("discovering regions...");
-    let regions = ec2
-        .describe_regions()
-        .send()
-        .await?
-        .regions
-        .ok_or_else(|| anyhow!("Got an empty region list"))?
+
+    let mut accounts = vec![(None, shared_config)];
+    for role_arn in config.eks_assume_role_arns.iter() {
+        debug!(role_arn, "assuming role for cross-account discovery");
+        let provider = aws_config::sts::AssumeRoleProvider::builder(role_arn)
+            .session_name("platz-cluster-discovery")
+            .build()
+            .await;
+        let account_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .credentials_provider(provider)
+            .load()
+            .await;
+        accounts.push((Some(role_arn.clone()), account_config));
+    }
+
+    let results = try_join_all(accounts.iter().map(|(role_arn, sdk_config)| {
+        discover_account_eks_clusters(config, sdk_config, role_arn.as_deref())
+    }))
+    .await?;
+
+    Ok(results
         .into_iter()
-        .filter_map(|ec2_region| ec2_region.region_name().map(ToOwned::to_owned))
-        .map(Region::new);
+        .flatten()
+        .filter(|cluster| {
+            let tags = cluster.cluster.tags.clone().unwrap_or_default();
+            let included = config
+                .eks_include_tags
+                .iter()
+                .all(|filter| filter.matches(&tags));
+            let excluded = config
+                .eks_exclude_tags
+                .iter()
+                .any(|filter| filter.matches(&tags));
+            if !included || excluded {
+                debug!(name = ?cluster.cluster.name, "skipping cluster due to tag filters");
+            }
+            included && !excluded
+        })
+        .map(K8s::from)
+        .collect())
+}
+
+#[tracing::instrument(skip_all, err, fields(role_arn = ?role_arn))]
+async fn discover_account_eks_clusters(
+    config: &Config,
+    sdk_config: &aws_config::SdkConfig,
+    role_arn: Option<&str>,
+) -> Result<Vec<EksCluster>> {
+    let regions = if config.eks_regions.is_empty() {
+        debug!("discovering regions...");
+        aws_sdk_ec2::Client::new(sdk_config)
+            .describe_regions()
+            .send()
+            .await?
+            .regions
+            .ok_or_else(|| anyhow!("Got an empty region list"))?
+            .into_iter()
+            .filter_map(|ec2_region| ec2_region.region_name().map(ToOwned::to_owned))
+            .collect()
+    } else {
+        config.eks_regions.clone()
+    };
 
     debug!("discovering...");
-    let results = try_join_all(regions.map(get_clusters)).await?;
+    let results = try_join_all(
+        regions
+            .into_iter()
+            .map(|region| get_clusters(sdk_config, Region::new(region), role_arn)),
+    )
+    .await?;
     Ok(results.into_iter().flatten().collect())
 }
 
-#[tracing::instrument(err, fields(region=%region))]
-async fn get_clusters(region: Region) -> Result<Vec<K8s>> {
+#[tracing::instrument(skip(sdk_config, role_arn), err, fields(region=%region))]
+async fn get_clusters(
+    sdk_config: &aws_config::SdkConfig,
+    region: Region,
+    role_arn: Option<&str>,
+) -> Result<Vec<EksCluster>> {
     debug!("started");
-    let shared_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-    let client_config = aws_sdk_eks::config::Builder::from(&shared_config)
+    let client_config = aws_sdk_eks::config::Builder::from(sdk_config)
         .region(Some(region.clone()))
         .build();
     let eks = aws_sdk_eks::Client::from_conf(client_config);
@@ -125,18 +260,85 @@ async fn get_clusters(region: Region) -> Result<Vec<K8s>> {
     })
     .collect::<std::result::Result<Vec<_>, _>>()?;
 
-    Ok(eks_clusters.into_iter().map(K8s::from).collect())
+    Ok(eks_clusters
+        .into_iter()
+        .map(|cluster| EksCluster {
+            cluster,
+            assume_role_arn: role_arn.map(ToOwned::to_owned),
+        })
+        .collect())
+}
+
+/// A tag filter for discovered EKS clusters, either `key=value` or just
+/// `key` (matching any value).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TagFilter {
+    key: String,
+    value: Option<String>,
+}
+
+impl FromStr for TagFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = match s.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value.trim().to_owned())),
+            None => (s.trim(), None),
+        };
+        if key.is_empty() {
+            return Err(format!(
+                "Invalid tag filter {s:?}, expected key=value or key"
+            ));
+        }
+        Ok(Self {
+            key: key.to_owned(),
+            value,
+        })
+    }
+}
+
+impl TagFilter {
+    fn matches(&self, tags: &HashMap<String, String>) -> bool {
+        match (tags.get(&self.key), &self.value) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(actual), Some(expected)) => actual == expected,
+        }
+    }
 }
 
 #[tracing::instrument(skip_all, err)]
 async fn discover_local_cluster(config: &Config) -> Result<K8s> {
     let kubeconfig = load_local_kubeconfig(config.local_kubeconfig.as_deref()).await?;
+    if config.local_context.is_none() && kubeconfig.current_context.is_none() {
+        return Err(anyhow!(
+            "Kubeconfig has no current-context and PLATZ_LOCAL_CONTEXT is not set"
+        ));
+    }
+    let (context_name, scoped_kubeconfig) =
+        scope_kubeconfig(&kubeconfig, config.local_context.as_deref())?;
+
+    info!("Registering local cluster from context {context_name:?}");
 
-    let context_name = match &config.local_context {
-        Some(name) => name.clone(),
-        None => kubeconfig.current_context.clone().ok_or_else(|| {
-            anyhow!("Kubeconfig has no current-context and PLATZ_LOCAL_CONTEXT is not set")
-        })?,
+    Ok(K8s::Local(Box::new(LocalCluster {
+        name: context_name.clone(),
+        provider_id: format!("local:{context_name}"),
+        kubeconfig: scoped_kubeconfig,
+    })))
+}
+
+/// Returns a kubeconfig containing only the given context (or the current
+/// context), along with its cluster and user, and the selected context name.
+pub(super) fn scope_kubeconfig(
+    kubeconfig: &kube::config::Kubeconfig,
+    context_name: Option<&str>,
+) -> Result<(String, kube::config::Kubeconfig)> {
+    let context_name = match context_name {
+        Some(name) => name.to_owned(),
+        None => kubeconfig
+            .current_context
+            .clone()
+            .ok_or_else(|| anyhow!("Kubeconfig has no current-context"))?,
     };
 
     let context = kubeconfig
@@ -179,13 +381,7 @@ async fn discover_local_cluster(config: &Config) -> Result<K8s> {
         extensions: kubeconfig.extensions.clone(),
     };
 
-    info!("Registering local cluster from context {context_name:?}");
-
-    Ok(K8s::Local(Box::new(LocalCluster {
-        name: context_name.clone(),
-        provider_id: format!("local:{context_name}"),
-        kubeconfig: scoped_kubeconfig,
-    })))
+    Ok((context_name, scoped_kubeconfig))
 }
 
 async fn load_local_kubeconfig(
@@ -198,3 +394,26 @@ async fn load_local_kubeconfig(
     debug!("Loading kubeconfig from KUBECONFIG / default location");
     Ok(kube::config::Kubeconfig::read()?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_filter() {
+        let tags = HashMap::from([
+            ("platz.io/managed".to_owned(), "true".to_owned()),
+            ("team".to_owned(), "payments".to_owned()),
+        ]);
+
+        let filter: TagFilter = "platz.io/managed=true".parse().unwrap();
+        assert!(filter.matches(&tags));
+        let filter: TagFilter = "platz.io/managed=false".parse().unwrap();
+        assert!(!filter.matches(&tags));
+        let filter: TagFilter = "team".parse().unwrap();
+        assert!(filter.matches(&tags));
+        let filter: TagFilter = "owner".parse().unwrap();
+        assert!(!filter.matches(&tags));
+        assert!("=value".parse::<TagFilter>().is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use super::annotations::DEPLOYMENT_NAMESPACE_LABELS_SELECTOR;
+use super::tracker::K8S_TRACKER;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::prelude::*;
+use futures::future::join_all;
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
+use k8s_openapi::api::core::v1::{Namespace, Node, Pod, ResourceRequirements};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kube::ResourceExt;
+use kube::api::{Api, ListParams, PostParams};
+use platz_db::schema::k8s_cluster_health::{K8sClusterHealth, NewK8sClusterHealth};
+use std::collections::HashSet;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+#[derive(clap::Args)]
+#[group(skip)]
+pub struct Config {
+    /// How often to collect the health and capacity of each cluster.
+    #[arg(long, env = "PLATZ_CLUSTER_HEALTH_INTERVAL", default_value = "5m")]
+    pub cluster_health_interval: humantime::Duration,
+
+    /// How long to keep cluster health history.
+    #[arg(long, env = "PLATZ_CLUSTER_HEALTH_RETENTION", default_value = "30days")]
+    pub cluster_health_retention: humantime::Duration,
+}
+
+/// Permissions the agent needs on each cluster, as (API group, resource, verb)
+const REQUIRED_PERMISSIONS: &[(&str, &str, &str)] = &[
+    ("", "namespaces", "list"),
+    ("", "namespaces", "watch"),
+    ("", "namespaces", "create"),
+    ("", "namespaces", "delete"),
+    ("", "secrets", "create"),
+    ("", "secrets", "patch"),
+    ("", "pods", "list"),
+    ("", "pods", "watch"),
+    ("", "persistentvolumeclaims", "list"),
+    ("", "persistentvolumeclaims", "watch"),
+    ("", "nodes", "list"),
+    ("apps", "deployments", "list"),
+    ("apps", "deployments", "watch"),
+    ("apps", "deployments", "patch"),
+    ("apps", "statefulsets", "list"),
+    ("apps", "statefulsets", "watch"),
+    ("apps", "statefulsets", "patch"),
+    ("apps", "daemonsets", "list"),
+    ("apps", "daemonsets", "watch"),
+    ("apps", "daemonsets", "patch"),
+    ("batch", "jobs", "list"),
+    ("batch", "jobs", "watch"),
+    ("batch", "cronjobs", "list"),
+    ("batch", "cronjobs", "watch"),
+    ("networking.k8s.io", "ingresses", "list"),
+    ("networking.k8s.io", "ingresses", "watch"),
+];
+
+#[tracing::instrument(err, skip_all, name = "cluster-health")]
+pub async fn run_cluster_health(config: &Config) -> Result<()> {
+    let every: std::time::Duration = config.cluster_health_interval.into();
+    if every.is_zero() {
+        bail!("PLATZ_CLUSTER_HEALTH_INTERVAL must be greater than zero");
+    }
+    let retention = chrono::Duration::from_std(config.cluster_health_retention.into())
+        .context("PLATZ_CLUSTER_HEALTH_RETENTION is out of range")?;
+    let mut interval = tokio::time::interval(every);
+
+    loop {
+        interval.tick().await;
+
+        let cluster_ids = K8S_TRACKER.get_ids().await;
+        debug!("Collecting health of {} clusters", cluster_ids.len());
+        for health in join_all(cluster_ids.into_iter().map(collect_cluster_health)).await {
+            let cluster_id = health.cluster_id;
+            if let Err(err) = health.insert().await {
+                error!("Failed saving health of cluster {cluster_id}: {err:?}");
+            }
+        }
+
+        match K8sClusterHealth::delete_older_than(Utc::now() - retention).await {
+            Ok(0) => (),
+            Ok(deleted) => info!("Deleted {deleted} old cluster health records"),
+            Err(err) => error!("Failed deleting old cluster health records: {err:?}"),
+        }
+    }
+}
+
+/// Collects as much as possible of a cluster's health. Parts that fail are
+/// left empty and their errors recorded.
+#[tracing::instrument(skip_all, fields(%cluster_id))]
+async fn collect_cluster_health(cluster_id: Uuid) -> NewK8sClusterHealth {
+    let mut health = NewK8sClusterHealth {
+        cluster_id,
+        ..Default::default()
+    };
+
+    let client = match cluster_client(cluster_id).await {
+        Ok(client) => client,
+        Err(err) => {
+            health.errors.push(format!("{err:#}"));
+            return health;
+        }
+    };
+
+    match client.apiserver_version().await {
+        Ok(info) => health.k8s_version = Some(info.git_version),
+        Err(err) => health
+            .errors
+            .push(format!("Failed getting Kubernetes version: {err}")),
+    }
+
+    match node_capacity(&client).await {
+        Ok(capacity) => {
+            health.node_count = Some(capacity.node_count);
+            health.ready_node_count = Some(capacity.ready_node_count);
+            health.allocatable_cpu_millis = Some(capacity.cpu_millis);
+            health.allocatable_memory_bytes = Some(capacity.memory_bytes);
+        }
+        Err(err) => health.errors.push(format!("{err:#}")),
+    }
+
+    match requested_resources(&client).await {
+        Ok((cpu_millis, memory_bytes)) => {
+            health.requested_cpu_millis = Some(cpu_millis);
+            health.requested_memory_bytes = Some(memory_bytes);
+        }
+        Err(err) => health.errors.push(format!("{err:#}")),
+    }
+
+    match missing_permissions(&client).await {
+        Ok(missing) => health.missing_permissions = missing,
+        Err(err) => health.errors.push(format!("{err:#}")),
+    }
+
+    health
+}
+
+async fn cluster_client(cluster_id: Uuid) -> Result<kube::Client> {
+    K8S_TRACKER
+        .get_cluster(cluster_id)
+        .await?
+        .kube_client()
+        .await
+}
+
+struct NodeCapacity {
+    node_count: i32,
+    ready_node_count: i32,
+    cpu_millis: i64,
+    memory_bytes: i64,
+}
+
+async fn node_capacity(client: &kube::Client) -> Result<NodeCapacity> {
+    let nodes = Api::<Node>::all(client.clone())
+        .list(&ListParams::default())
+        .await
+        .context("Failed listing nodes")?;
+
+    let mut capacity = NodeCapacity {
+        node_count: 0,
+        ready_node_count: 0,
+        cpu_millis: 0,
+        memory_bytes: 0,
+    };
+    for node in nodes.items.iter() {
+        capacity.node_count += 1;
+        let Some(status) = node.status.as_ref() else {
+            continue;
+        };
+        let is_ready = status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|condition| condition.type_ == "Ready" && condition.status == "True");
+        if is_ready {
+            capacity.ready_node_count += 1;
+        }
+        if let Some(allocatable) = status.allocatable.as_ref() {
+            capacity.cpu_millis += quantity_millis(allocatable.get("cpu"))?;
+            capacity.memory_bytes += quantity_units(allocatable.get("memory"))?;
+        }
+    }
+    Ok(capacity)
+}
+
+/// Sums the CPU (in millicores) and memory (in bytes) requested by pods
+/// running in Platz namespaces
+async fn requested_resources(client: &kube::Client) -> Result<(i64, i64)> {
+    let namespaces = Api::<Namespace>::all(client.clone())
+        .list(&ListParams::default().labels(&DEPLOYMENT_NAMESPACE_LABELS_SELECTOR))
+        .await
+        .context("Failed listing namespaces")?
+        .items
+        .iter()
+        .map(|namespace| namespace.name_any())
+        .collect::<HashSet<_>>();
+
+    let pods = Api::<Pod>::all(client.clone())
+        .list(&ListParams::default().fields("status.phase!=Succeeded,status.phase!=Failed"))
+        .await
+        .context("Failed listing pods")?;
+
+    let (mut cpu_millis, mut memory_bytes) = (0, 0);
+    for pod in pods.items.iter() {
+        if !pod
+            .namespace()
+            .is_some_and(|namespace| namespaces.contains(&namespace))
+        {
+            continue;
+        }
+        let Some(spec) = pod.spec.as_ref() else {
+            continue;
+        };
+        let (pod_cpu, pod_memory) = effective_pod_requests(
+            spec.containers.iter().map(|c| c.resources.as_ref()),
+            spec.init_containers
+                .iter()
+                .flatten()
+                .map(|c| c.resources.as_ref()),
+        )?;
+        cpu_millis += pod_cpu;
+        memory_bytes += pod_memory;
+    }
+    Ok((cpu_millis, memory_bytes))
+}
+
+/// A pod's effective requests are the sum of its containers' requests, or
+/// the largest init container request if that's higher (init containers run
+/// one at a time, before the other containers).
+fn effective_pod_requests<'a>(
+    containers: impl Iterator<Item = Option<&'a ResourceRequirements>>,
+    init_containers: impl Iterator<Item = Option<&'a ResourceRequirements>>,
+) -> Result<(i64, i64)> {
+    let (mut cpu_millis, mut memory_bytes) = (0, 0);
+    for (cpu, memory) in containers
+        .map(container_requests)
+        .collect::<Result<Vec<_>>>()?
+    {
+        cpu_millis += cpu;
+        memory_bytes += memory;
+    }
+    for (cpu, memory) in init_containers
+        .map(container_requests)
+        .collect::<Result<Vec<_>>>()?
+    {
+        cpu_millis = cpu_millis.max(cpu);
+        memory_bytes = memory_bytes.max(memory);
+    }
+    Ok((cpu_millis, memory_bytes))
+}
+
+fn container_requests(resources: Option<&ResourceRequirements>) -> Result<(i64, i64)> {
+    let requests = resources.and_then(|resources| resources.requests.as_ref());
+    Ok((
+        quantity_millis(requests.and_then(|requests| requests.get("cpu")))?,
+        quantity_units(requests.and_then(|requests| requests.get("memory")))?,
+    ))
+}
+
+async fn missing_permissions(client: &kube::Client) -> Result<Vec<String>> {
+    let api = Api::<SelfSubjectAccessReview>::all(client.clone());
+    let mut missing = Vec::new();
+    for (group, resource, verb) in REQUIRED_PERMISSIONS.iter() {
+        let review = SelfSubjectAccessReview {
+            spec: SelfSubjectAccessReviewSpec {
+                resource_attributes: Some(ResourceAttributes {
+                    group: Some(group.to_string()),
+                    resource: Some(resource.to_string()),
+                    verb: Some(verb.to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let review = api
+            .create(&PostParams::default(), &review)
+            .await
+            .context("Failed checking permissions")?;
+        if !review.status.is_some_and(|status| status.allowed) {
+            missing.push(if group.is_empty() {
+                format!("{verb} {resource}")
+            } else {
+                format!("{verb} {group}/{resource}")
+            });
+        }
+    }
+    Ok(missing)
+}
+
+fn quantity_millis(quantity: Option<&Quantity>) -> Result<i64> {
+    Ok(quantity
+        .map(|quantity| parse_quantity(&quantity.0))
+        .transpose()?
+        .map(|value| (value * 1000.0).round() as i64)
+        .unwrap_or_default())
+}
+
+fn quantity_units(quantity: Option<&Quantity>) -> Result<i64> {
+    Ok(quantity
+        .map(|quantity| parse_quantity(&quantity.0))
+        .transpose()?
+        .map(|value| value.round() as i64)
+        .unwrap_or_default())
+}
+
+/// Parses a Kubernetes quantity (e.g. `250m`, `1.5`, `128Mi` or `1e3`) into
+/// its value in base units.
+fn parse_quantity(quantity: &str) -> Result<f64> {
+    let invalid = || anyhow!("Invalid quantity {quantity:?}");
+    let quantity = quantity.trim();
+    let (number, suffix) = quantity.split_at(
+        quantity
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '+' | '-')))
+            .unwrap_or(quantity.len()),
+    );
+    let number = number.parse::<f64>().map_err(|_| invalid())?;
+    let multiplier = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024_f64,
+        "Mi" => 1024_f64.powi(2),
+        "Gi" => 1024_f64.powi(3),
+        "Ti" => 1024_f64.powi(4),
+        "Pi" => 1024_f64.powi(5),
+        "Ei" => 1024_f64.powi(6),
+        exponent if exponent.starts_with(['e', 'E']) => {
+            10_f64.powi(exponent[1..].parse::<i32>().map_err(|_| invalid())?)
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(number * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_quantity;
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(parse_quantity("2").unwrap(), 2.0);
+        assert_eq!(parse_quantity("1.5").unwrap(), 1.5);
+        assert_eq!(parse_quantity("250m").unwrap(), 0.25);
+        assert_eq!(parse_quantity("128Mi").unwrap(), 134217728.0);
+        assert_eq!(parse_quantity("16Gi").unwrap(), 17179869184.0);
+        assert_eq!(parse_quantity("1k").unwrap(), 1000.0);
+        assert_eq!(parse_quantity("1e3").unwrap(), 1000.0);
+        assert_eq!(parse_quantity("1E").unwrap(), 1e18);
+        assert!(parse_quantity("").is_err());
+        assert!(parse_quantity("12Qi").is_err());
+        assert!(parse_quantity("abc").is_err());
+    }
+}
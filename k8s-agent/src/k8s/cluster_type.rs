@@ -2,6 +2,7 @@ use anyhow::{Result, anyhow};
 use base64::prelude::*;
 use kube::config::ExecInteractiveMode;
 use platz_db::schema::k8s_cluster::NewK8sCluster;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt;
 use tracing::debug;
@@ -10,8 +11,17 @@ const LOCAL_REGION: &str = "local";
 
 #[derive(Debug)]
 pub enum K8s {
-    Eks(Box<aws_sdk_eks::types::Cluster>),
+    Eks(Box<EksCluster>),
     Local(Box<LocalCluster>),
+    Static(Box<StaticCluster>),
+}
+
+#[derive(Debug, Clone)]
+pub struct EksCluster {
+    pub cluster: aws_sdk_eks::types::Cluster,
+    /// IAM role assumed to discover the cluster (and to access it), for
+    /// clusters in other accounts.
+    pub assume_role_arn: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -26,24 +36,37 @@ pub struct LocalCluster {
     pub kubeconfig: kube::config::Kubeconfig,
 }
 
+#[derive(Debug, Clone)]
+pub struct StaticCluster {
+    pub name: String,
+    /// Taken from the clusters file, defaulting to `static:<name>`.
+    pub provider_id: String,
+    pub region_name: String,
+    /// Name or id of the env to attach the cluster to when first discovered.
+    pub default_env: Option<String>,
+    pub tags: BTreeMap<String, String>,
+    pub kubeconfig: kube::config::Kubeconfig,
+}
+
 impl fmt::Display for K8s {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Eks(cluster) => write!(
+            Self::Eks(eks) => write!(
                 f,
                 "EKS({})",
-                cluster
+                eks.cluster
                     .endpoint
                     .as_ref()
                     .unwrap_or(&String::from("unknown"))
             ),
             Self::Local(cluster) => write!(f, "Local({})", cluster.name),
+            Self::Static(cluster) => write!(f, "Static({})", cluster.name),
         }
     }
 }
 
-impl From<aws_sdk_eks::types::Cluster> for K8s {
-    fn from(cluster: aws_sdk_eks::types::Cluster) -> Self {
+impl From<EksCluster> for K8s {
+    fn from(cluster: EksCluster) -> Self {
         Self::Eks(Box::new(cluster))
     }
 }
@@ -54,6 +77,12 @@ impl From<LocalCluster> for K8s {
     }
 }
 
+impl From<StaticCluster> for K8s {
+    fn from(cluster: StaticCluster) -> Self {
+        Self::Static(Box::new(cluster))
+    }
+}
+
 impl K8s {
     pub async fn kube_client(&self) -> Result<kube::Client> {
         Ok(kube::Client::try_from(self.kube_config().await?)?)
@@ -61,22 +90,26 @@ impl K8s {
 
     pub fn name(&self) -> Result<&str> {
         Ok(match self {
-            K8s::Eks(cluster) => cluster
+            K8s::Eks(eks) => eks
+                .cluster
                 .name
                 .as_ref()
                 .ok_or_else(|| anyhow!("Cluster has empty name"))?,
             K8s::Local(cluster) => &cluster.name,
+            K8s::Static(cluster) => &cluster.name,
         })
     }
 
     fn provider_id(&self) -> Result<String> {
         Ok(match self {
-            K8s::Eks(cluster) => cluster
+            K8s::Eks(eks) => eks
+                .cluster
                 .arn
                 .as_ref()
                 .ok_or_else(|| anyhow!("Cluster has no ARN"))?
                 .clone(),
             K8s::Local(cluster) => cluster.provider_id.clone(),
+            K8s::Static(cluster) => cluster.provider_id.clone(),
         })
     }
 
@@ -84,13 +117,47 @@ impl K8s {
         Ok(match self {
             K8s::Eks(_) => self.eks_region()?.into(),
             K8s::Local(_) => LOCAL_REGION.to_owned(),
+            K8s::Static(cluster) => cluster.region_name.clone(),
         })
     }
 
+    /// The provider that discovered the cluster
+    fn provider(&self) -> &'static str {
+        match self {
+            K8s::Eks(_) => "eks",
+            K8s::Local(_) => "local",
+            K8s::Static(_) => "static",
+        }
+    }
+
+    /// Tags reported by the cluster provider
+    pub fn tags(&self) -> BTreeMap<String, String> {
+        match self {
+            K8s::Eks(eks) => eks
+                .cluster
+                .tags
+                .iter()
+                .flatten()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            K8s::Local(_) => Default::default(),
+            K8s::Static(cluster) => cluster.tags.clone(),
+        }
+    }
+
+    /// Name or id of the env a newly discovered cluster should be attached to
+    pub fn default_env(&self) -> Option<&str> {
+        match self {
+            K8s::Eks(_) | K8s::Local(_) => None,
+            K8s::Static(cluster) => cluster.default_env.as_deref(),
+        }
+    }
+
     fn eks_region(&self) -> Result<aws_arn::Identifier> {
         match self {
-            K8s::Eks(cluster) => {
-                let resource_name: aws_arn::ResourceName = cluster
+            K8s::Eks(eks) => {
+                let resource_name: aws_arn::ResourceName = eks
+                    .cluster
                     .arn
                     .as_ref()
                     .ok_or_else(|| anyhow!("Cluster has no ARN"))?
@@ -101,6 +168,7 @@ impl K8s {
                     .ok_or_else(|| anyhow!("Cluster ARN has no region"))
             }
             K8s::Local(_) => Err(anyhow!("Local clusters have no AWS region")),
+            K8s::Static(_) => Err(anyhow!("Static clusters have no AWS region")),
         }
     }
 
@@ -146,6 +214,9 @@ impl From<&K8s> for NewK8sCluster {
             name: cluster.name().unwrap().to_owned(),
             env_id: None,
             region_name: cluster.region_name().unwrap(),
+            tags: serde_json::to_value(cluster.tags()).unwrap(),
+            provider: cluster.provider().to_owned(),
+            ignore: None,
         }
     }
 }
@@ -155,13 +226,17 @@ impl TryFrom<&K8s> for kube::config::Kubeconfig {
 
     fn try_from(k8s: &K8s) -> Result<Self, Self::Error> {
         match k8s {
-            K8s::Eks(cluster) => eks_kubeconfig(cluster),
+            K8s::Eks(eks) => eks_kubeconfig(&eks.cluster, eks.assume_role_arn.as_deref()),
             K8s::Local(cluster) => Ok(cluster.kubeconfig.clone()),
+            K8s::Static(cluster) => Ok(cluster.kubeconfig.clone()),
         }
     }
 }
 
-fn eks_kubeconfig(cluster: &aws_sdk_eks::types::Cluster) -> Result<kube::config::Kubeconfig> {
+fn eks_kubeconfig(
+    cluster: &aws_sdk_eks::types::Cluster,
+    assume_role_arn: Option<&str>,
+) -> Result<kube::config::Kubeconfig> {
     let cluster_name = cluster
         .name
         .as_ref()
@@ -189,6 +264,17 @@ fn eks_kubeconfig(cluster: &aws_sdk_eks::types::Cluster) -> Result<kube::config:
             .ok_or_else(|| anyhow!("Cluster ARN has no region"))?
             .into()
     };
+    let mut get_token_args = vec![
+        "eks".into(),
+        "get-token".into(),
+        "--region".into(),
+        region,
+        "--cluster-name".into(),
+        cluster_name.clone(),
+    ];
+    if let Some(role_arn) = assume_role_arn {
+        get_token_args.extend(["--role-arn".into(), role_arn.to_owned()]);
+    }
     let user = "user";
     Ok(kube::config::Kubeconfig {
         api_version: Some("v1".to_owned()),
@@ -207,14 +293,7 @@ fn eks_kubeconfig(cluster: &aws_sdk_eks::types::Cluster) -> Result<kube::config:
             auth_info: Some(kube::config::AuthInfo {
                 exec: Some(kube::config::ExecConfig {
                     command: Some("aws".into()),
-                    args: Some(vec![
-                        "eks".into(),
-                        "get-token".into(),
-                        "--region".into(),
-                        region,
-                        "--cluster-name".into(),
-                        cluster_name.clone(),
-                    ]),
+                    args: Some(get_token_args),
                     api_version: Some("client.authentication.k8s.io/v1".to_owned()),
                     interactive_mode: Some(ExecInteractiveMode::Never),
                     env: None,
@@ -0,0 +1,44 @@
+use super::cluster_discovery::Config;
+use super::tracker::K8S_TRACKER;
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use platz_db::schema::{
+    deployment::Deployment, deployment_task::DeploymentTask, k8s_cluster::K8sCluster,
+};
+use tracing::warn;
+
+/// Marks clusters as missing if they weren't seen in discovery for longer
+/// than the configured threshold. Called after each successful discovery, so
+/// discovery failures (e.g. a cloud provider outage) never mark clusters as
+/// missing.
+pub(super) async fn mark_missing_clusters(
+    config: &Config,
+    discovery_started_at: DateTime<Utc>,
+) -> Result<()> {
+    let threshold = chrono::Duration::from_std(config.cluster_missing_threshold.into())
+        .context("PLATZ_CLUSTER_MISSING_THRESHOLD is out of range")?;
+
+    for cluster in
+        K8sCluster::find_unseen_since(config.provider.as_str(), discovery_started_at - threshold)
+            .await?
+    {
+        let reason = format!(
+            "Cluster {} has been missing from cluster discovery since {}",
+            cluster.name, cluster.last_seen_at
+        );
+        warn!(cluster_id = %cluster.id, "{reason}");
+
+        let cluster = cluster.set_missing(reason.clone()).await?;
+        K8S_TRACKER.stop_watching_cluster(cluster.id).await;
+
+        let deployments = Deployment::orphan_all_in_cluster(cluster.id, reason.clone()).await?;
+        let tasks = DeploymentTask::fail_pending_in_cluster(cluster.id, reason).await?;
+        warn!(
+            cluster_id = %cluster.id,
+            "Marked {} deployments as orphaned and failed {} pending tasks",
+            deployments.len(),
+            tasks.len(),
+        );
+    }
+    Ok(())
+}
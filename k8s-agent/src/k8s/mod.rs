@@ -1,5 +1,9 @@
 pub mod annotations;
 pub mod cluster_discovery;
+pub mod cluster_health;
 pub mod cluster_type;
+pub mod missing_clusters;
 pub mod pods;
+pub mod resource_status;
+pub mod static_clusters;
 pub mod tracker;
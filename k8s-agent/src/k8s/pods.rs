@@ -1,11 +1,14 @@
 /// The code below was copied from https://github.com/clux/kube-rs/blob/master/examples/pod_attach.rs
-use crate::utils::create_interval_stream;
+use crate::{metrics::HELM_POD_STARTUP, utils::create_interval_stream};
 use anyhow::{Context, Result, anyhow};
 use futures::{StreamExt, stream};
 use k8s_openapi::api::core::v1::Pod;
 use kube::api::{Api, AttachedProcess, DeleteParams, PostParams, ResourceExt};
 use kube::runtime::watcher::{self, Event};
-use std::{fmt, time::Duration};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
 use tap::TapFallible;
 use tokio::select;
 use tracing::{debug, error};
@@ -157,6 +160,7 @@ async fn wait_for_pod(pods: &Api<Pod>, pod_name: &str) -> Result<PodExecutionRes
     };
 
     debug!("Waiting for pod to be ready");
+    let start_time = Instant::now();
     let mut pod_phase = wait_for_pod_phase(
         &mut pod_events,
         |p| {
@@ -168,6 +172,7 @@ async fn wait_for_pod(pods: &Api<Pod>, pod_name: &str) -> Result<PodExecutionRes
     )
     .await
     .with_context(|| format!("Failed waiting for Helm pod {pod_name} to start running"))?;
+    HELM_POD_STARTUP.observe(start_time.elapsed().as_secs_f64());
     debug!("Attaching to {pod_name} (phase: {pod_phase})");
     let attached = pods.attach(pod_name, &Default::default()).await?;
     let output = get_pod_output(attached)
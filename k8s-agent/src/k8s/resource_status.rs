@@ -0,0 +1,661 @@
+use chrono::prelude::*;
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::{CronJob, Job};
+use k8s_openapi::api::core::v1::{PersistentVolumeClaim, Pod, PodSpec};
+use k8s_openapi::api::networking::v1::Ingress;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time};
+use platz_db::schema::{
+    deployment_status::DeploymentReportedStatusColor,
+    k8s_resource::{
+        K8sResourceCondition, K8sResourceContainer, K8sResourceCronJobStatus,
+        K8sResourceIngressStatus, K8sResourcePodStatus, K8sResourceReplicas, K8sResourceStatus,
+    },
+};
+
+/// A Kubernetes resource kind tracked in the `k8s_resources` table
+pub trait TrackedResource:
+    k8s_openapi::Resource + k8s_openapi::Metadata<Ty = ObjectMeta> + std::fmt::Debug
+{
+    /// One color per replica/pod/run, summarizing the resource's health
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor>;
+
+    /// Structured status details, as shown and filtered in the API
+    fn status(&self) -> K8sResourceStatus;
+}
+
+macro_rules! conditions {
+    ($conditions:expr) => {
+        $conditions
+            .iter()
+            .flat_map(|conditions| conditions.iter())
+            .map(|condition| K8sResourceCondition {
+                type_: condition.type_.clone(),
+                status: condition.status.clone(),
+                reason: condition.reason.clone(),
+                message: condition.message.clone(),
+            })
+            .collect()
+    };
+}
+
+impl TrackedResource for Deployment {
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor> {
+        k8s_deployment_status(self)
+    }
+
+    fn status(&self) -> K8sResourceStatus {
+        let status = self.status.as_ref();
+        K8sResourceStatus {
+            conditions: conditions!(status.and_then(|status| status.conditions.as_ref())),
+            containers: pod_spec_containers(
+                self.spec
+                    .as_ref()
+                    .and_then(|spec| spec.template.spec.as_ref()),
+            ),
+            replicas: Some(K8sResourceReplicas {
+                wanted: self
+                    .spec
+                    .as_ref()
+                    .and_then(|spec| spec.replicas)
+                    .unwrap_or(1),
+                ready: status
+                    .and_then(|status| status.ready_replicas)
+                    .unwrap_or_default(),
+                updated: status
+                    .and_then(|status| status.updated_replicas)
+                    .unwrap_or_default(),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl TrackedResource for StatefulSet {
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor> {
+        k8s_statefulset_status(self)
+    }
+
+    fn status(&self) -> K8sResourceStatus {
+        let status = self.status.as_ref();
+        K8sResourceStatus {
+            conditions: conditions!(status.and_then(|status| status.conditions.as_ref())),
+            containers: pod_spec_containers(
+                self.spec
+                    .as_ref()
+                    .and_then(|spec| spec.template.spec.as_ref()),
+            ),
+            replicas: Some(K8sResourceReplicas {
+                wanted: self
+                    .spec
+                    .as_ref()
+                    .and_then(|spec| spec.replicas)
+                    .unwrap_or(1),
+                ready: status
+                    .and_then(|status| status.ready_replicas)
+                    .unwrap_or_default(),
+                updated: status
+                    .and_then(|status| status.updated_replicas)
+                    .unwrap_or_default(),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl TrackedResource for DaemonSet {
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor> {
+        k8s_daemonset_status(self)
+    }
+
+    fn status(&self) -> K8sResourceStatus {
+        let status = self.status.as_ref();
+        K8sResourceStatus {
+            conditions: conditions!(status.and_then(|status| status.conditions.as_ref())),
+            containers: pod_spec_containers(
+                self.spec
+                    .as_ref()
+                    .and_then(|spec| spec.template.spec.as_ref()),
+            ),
+            replicas: status.map(|status| K8sResourceReplicas {
+                wanted: status.desired_number_scheduled,
+                ready: status.number_ready,
+                updated: status.updated_number_scheduled.unwrap_or_default(),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl TrackedResource for Job {
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor> {
+        k8s_job_status(self)
+    }
+
+    fn status(&self) -> K8sResourceStatus {
+        K8sResourceStatus {
+            conditions: conditions!(
+                self.status
+                    .as_ref()
+                    .and_then(|status| status.conditions.as_ref())
+            ),
+            containers: pod_spec_containers(
+                self.spec
+                    .as_ref()
+                    .and_then(|spec| spec.template.spec.as_ref()),
+            ),
+            replicas: None,
+            ..Default::default()
+        }
+    }
+}
+
+impl TrackedResource for CronJob {
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor> {
+        k8s_cronjob_status(self)
+    }
+
+    fn status(&self) -> K8sResourceStatus {
+        let status = self.status.as_ref();
+        K8sResourceStatus {
+            containers: pod_spec_containers(
+                self.spec
+                    .as_ref()
+                    .and_then(|spec| spec.job_template.spec.as_ref())
+                    .and_then(|spec| spec.template.spec.as_ref()),
+            ),
+            cron_job: Some(K8sResourceCronJobStatus {
+                last_schedule_time: status
+                    .and_then(|status| status.last_schedule_time.as_ref())
+                    .and_then(k8s_time),
+                last_successful_time: status
+                    .and_then(|status| status.last_successful_time.as_ref())
+                    .and_then(k8s_time),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl TrackedResource for Pod {
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor> {
+        k8s_pod_status(self)
+    }
+
+    fn status(&self) -> K8sResourceStatus {
+        let status = self.status.as_ref();
+        let container_statuses = status
+            .and_then(|status| status.container_statuses.as_deref())
+            .unwrap_or_default();
+        K8sResourceStatus {
+            conditions: conditions!(status.and_then(|status| status.conditions.as_ref())),
+            containers: pod_spec_containers(self.spec.as_ref()),
+            replicas: None,
+            pod: Some(K8sResourcePodStatus {
+                node_name: self.spec.as_ref().and_then(|spec| spec.node_name.clone()),
+                restart_count: container_statuses
+                    .iter()
+                    .map(|container| container.restart_count)
+                    .sum(),
+                last_termination_reason: container_statuses.iter().find_map(|container| {
+                    container
+                        .last_state
+                        .as_ref()
+                        .and_then(|state| state.terminated.as_ref())
+                        .and_then(|terminated| terminated.reason.clone())
+                }),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl TrackedResource for Ingress {
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor> {
+        k8s_ingress_status(self)
+    }
+
+    fn status(&self) -> K8sResourceStatus {
+        K8sResourceStatus {
+            ingress: Some(K8sResourceIngressStatus {
+                hosts: self
+                    .spec
+                    .iter()
+                    .flat_map(|spec| spec.rules.iter().flatten())
+                    .filter_map(|rule| rule.host.clone())
+                    .collect(),
+                addresses: self
+                    .status
+                    .iter()
+                    .flat_map(|status| status.load_balancer.iter())
+                    .flat_map(|load_balancer| load_balancer.ingress.iter().flatten())
+                    .filter_map(|ingress| ingress.ip.clone().or_else(|| ingress.hostname.clone()))
+                    .collect(),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl TrackedResource for PersistentVolumeClaim {
+    fn status_color(&self) -> Vec<DeploymentReportedStatusColor> {
+        k8s_pvc_status(self)
+    }
+
+    fn status(&self) -> K8sResourceStatus {
+        K8sResourceStatus {
+            conditions: conditions!(
+                self.status
+                    .as_ref()
+                    .and_then(|status| status.conditions.as_ref())
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+fn k8s_time(time: &Time) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(time.0.as_second(), time.0.subsec_nanosecond() as u32)
+}
+
+fn pod_spec_containers(pod_spec: Option<&PodSpec>) -> Vec<K8sResourceContainer> {
+    pod_spec
+        .iter()
+        .flat_map(|spec| spec.containers.iter())
+        .filter_map(|container| {
+            let (image, tag) = split_image_tag(container.image.as_deref()?);
+            Some(K8sResourceContainer {
+                name: container.name.clone(),
+                image,
+                tag,
+            })
+        })
+        .collect()
+}
+
+/// Splits an image reference into the image and its tag (or digest). A colon
+/// before the last slash belongs to a registry port, not to a tag.
+fn split_image_tag(image: &str) -> (String, Option<String>) {
+    if let Some((name, digest)) = image.split_once('@') {
+        return (name.to_owned(), Some(digest.to_owned()));
+    }
+    let name_start = image.rfind('/').map(|pos| pos + 1).unwrap_or_default();
+    match image[name_start..].rfind(':') {
+        Some(pos) => (
+            image[..name_start + pos].to_owned(),
+            Some(image[name_start + pos + 1..].to_owned()),
+        ),
+        None => (image.to_owned(), None),
+    }
+}
+
+fn k8s_deployment_status(deployment: &Deployment) -> Vec<DeploymentReportedStatusColor> {
+    let status = match &deployment.status {
+        Some(status) => status,
+        None => return Vec::new(),
+    };
+
+    let available = status.available_replicas.unwrap_or_default() as usize;
+    let unavailable = status.unavailable_replicas.unwrap_or_default() as usize;
+
+    std::iter::repeat_n(DeploymentReportedStatusColor::Success, available)
+        .chain(std::iter::repeat_n(
+            DeploymentReportedStatusColor::Danger,
+            unavailable,
+        ))
+        .collect()
+}
+
+fn k8s_statefulset_status(statefulset: &StatefulSet) -> Vec<DeploymentReportedStatusColor> {
+    let status = match &statefulset.status {
+        Some(status) => status,
+        None => return Vec::new(),
+    };
+
+    let replicas = status.replicas as usize;
+    let ready = status.ready_replicas.unwrap_or_default() as usize;
+
+    std::iter::repeat_n(DeploymentReportedStatusColor::Success, ready)
+        .take(ready)
+        .chain(std::iter::repeat_n(
+            DeploymentReportedStatusColor::Danger,
+            replicas - ready,
+        ))
+        .collect()
+}
+
+fn k8s_job_status(job: &Job) -> Vec<DeploymentReportedStatusColor> {
+    let status = match &job.status {
+        Some(status) => status,
+        None => return Vec::new(),
+    };
+
+    std::iter::repeat_n(
+        DeploymentReportedStatusColor::Primary,
+        status.active.unwrap_or_default() as usize,
+    )
+    .chain(std::iter::repeat_n(
+        DeploymentReportedStatusColor::Success,
+        status.succeeded.unwrap_or_default() as usize,
+    ))
+    .chain(std::iter::repeat_n(
+        DeploymentReportedStatusColor::Danger,
+        status.failed.unwrap_or_default() as usize,
+    ))
+    .collect()
+}
+
+fn k8s_daemonset_status(daemonset: &DaemonSet) -> Vec<DeploymentReportedStatusColor> {
+    let status = match &daemonset.status {
+        Some(status) => status,
+        None => return Vec::new(),
+    };
+
+    let desired = status.desired_number_scheduled.max(0) as usize;
+    let ready = (status.number_ready.max(0) as usize).min(desired);
+
+    std::iter::repeat_n(DeploymentReportedStatusColor::Success, ready)
+        .chain(std::iter::repeat_n(
+            DeploymentReportedStatusColor::Danger,
+            desired - ready,
+        ))
+        .collect()
+}
+
+/// A CronJob shows one primary dot per running job, followed by the result
+/// of its last schedule: success if the last scheduled run succeeded, danger
+/// if it didn't, or secondary while the CronJob is suspended.
+fn k8s_cronjob_status(cronjob: &CronJob) -> Vec<DeploymentReportedStatusColor> {
+    let suspended = cronjob
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.suspend)
+        .unwrap_or_default();
+    let status = cronjob.status.as_ref();
+    let active = status
+        .and_then(|status| status.active.as_ref())
+        .map(Vec::len)
+        .unwrap_or_default();
+    let last_schedule = status.and_then(|status| status.last_schedule_time.as_ref());
+    let last_success = status.and_then(|status| status.last_successful_time.as_ref());
+
+    let last_result = if suspended {
+        Some(DeploymentReportedStatusColor::Secondary)
+    } else {
+        match (last_schedule, last_success) {
+            (None, _) => None,
+            (Some(_), _) if active > 0 => None,
+            (Some(schedule), Some(success)) if success >= schedule => {
+                Some(DeploymentReportedStatusColor::Success)
+            }
+            (Some(_), _) => Some(DeploymentReportedStatusColor::Danger),
+        }
+    };
+
+    std::iter::repeat_n(DeploymentReportedStatusColor::Primary, active)
+        .chain(last_result)
+        .collect()
+}
+
+fn k8s_pod_status(pod: &Pod) -> Vec<DeploymentReportedStatusColor> {
+    let status = match &pod.status {
+        Some(status) => status,
+        None => return Vec::new(),
+    };
+
+    let container_statuses = status.container_statuses.as_deref().unwrap_or_default();
+    let crash_looping = container_statuses.iter().any(|container| {
+        container
+            .state
+            .as_ref()
+            .and_then(|state| state.waiting.as_ref())
+            .and_then(|waiting| waiting.reason.as_deref())
+            == Some("CrashLoopBackOff")
+    });
+    let all_ready = container_statuses.iter().all(|container| container.ready);
+
+    let color = match status.phase.as_deref() {
+        _ if crash_looping => DeploymentReportedStatusColor::Danger,
+        Some("Running") if all_ready => DeploymentReportedStatusColor::Success,
+        Some("Running") | Some("Pending") => DeploymentReportedStatusColor::Warning,
+        Some("Succeeded") => DeploymentReportedStatusColor::Secondary,
+        Some("Failed") => DeploymentReportedStatusColor::Danger,
+        _ => DeploymentReportedStatusColor::Secondary,
+    };
+
+    vec![color]
+}
+
+/// An ingress is ready once its controller has assigned it a load balancer
+fn k8s_ingress_status(ingress: &Ingress) -> Vec<DeploymentReportedStatusColor> {
+    let has_load_balancer = ingress
+        .status
+        .as_ref()
+        .and_then(|status| status.load_balancer.as_ref())
+        .and_then(|load_balancer| load_balancer.ingress.as_ref())
+        .is_some_and(|ingress| !ingress.is_empty());
+
+    vec![if has_load_balancer {
+        DeploymentReportedStatusColor::Success
+    } else {
+        DeploymentReportedStatusColor::Warning
+    }]
+}
+
+fn k8s_pvc_status(pvc: &PersistentVolumeClaim) -> Vec<DeploymentReportedStatusColor> {
+    let phase = pvc
+        .status
+        .as_ref()
+        .and_then(|status| status.phase.as_deref());
+
+    vec![match phase {
+        Some("Bound") => DeploymentReportedStatusColor::Success,
+        Some("Pending") => DeploymentReportedStatusColor::Warning,
+        Some("Lost") => DeploymentReportedStatusColor::Danger,
+        _ => DeploymentReportedStatusColor::Secondary,
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn colors(statuses: Vec<DeploymentReportedStatusColor>) -> Vec<String> {
+        statuses.iter().map(ToString::to_string).collect()
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_split_image_tag() {
+        assert_eq!(
+            split_image_tag("nginx:1.27"),
+            ("nginx".to_owned(), Some("1.27".to_owned()))
+        );
+        assert_eq!(split_image_tag("nginx"), ("nginx".to_owned(), None));
+        assert_eq!(
+            split_image_tag("registry.local:5000/team/app:v2"),
+            (
+                "registry.local:5000/team/app".to_owned(),
+                Some("v2".to_owned())
+            )
+        );
+        assert_eq!(
+            split_image_tag("registry.local:5000/team/app"),
+            ("registry.local:5000/team/app".to_owned(), None)
+        );
+        assert_eq!(
+            split_image_tag("app@sha256:abcd"),
+            ("app".to_owned(), Some("sha256:abcd".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_daemonset_status() {
+        let rolling_out: DaemonSet = parse(json!({
+            "status": {
+                "currentNumberScheduled": 3,
+                "desiredNumberScheduled": 3,
+                "numberMisscheduled": 0,
+                "numberReady": 1,
+                "updatedNumberScheduled": 1,
+            },
+        }));
+        assert_eq!(
+            colors(k8s_daemonset_status(&rolling_out)),
+            ["Success", "Danger", "Danger"]
+        );
+
+        // Ready pods left over on nodes that are no longer desired
+        let shrinking: DaemonSet = parse(json!({
+            "status": {
+                "currentNumberScheduled": 2,
+                "desiredNumberScheduled": 1,
+                "numberMisscheduled": 1,
+                "numberReady": 2,
+            },
+        }));
+        assert_eq!(colors(k8s_daemonset_status(&shrinking)), ["Success"]);
+        assert!(k8s_daemonset_status(&DaemonSet::default()).is_empty());
+    }
+
+    #[test]
+    fn test_cronjob_status() {
+        let succeeded: CronJob = parse(json!({
+            "status": {
+                "lastScheduleTime": "2026-10-18T10:00:00Z",
+                "lastSuccessfulTime": "2026-10-18T10:01:00Z",
+            },
+        }));
+        assert_eq!(colors(k8s_cronjob_status(&succeeded)), ["Success"]);
+
+        let failed: CronJob = parse(json!({
+            "status": {
+                "lastScheduleTime": "2026-10-18T11:00:00Z",
+                "lastSuccessfulTime": "2026-10-18T10:01:00Z",
+            },
+        }));
+        assert_eq!(colors(k8s_cronjob_status(&failed)), ["Danger"]);
+
+        let running: CronJob = parse(json!({
+            "status": {
+                "active": [{ "name": "backup-29342" }],
+                "lastScheduleTime": "2026-10-18T11:00:00Z",
+            },
+        }));
+        assert_eq!(colors(k8s_cronjob_status(&running)), ["Primary"]);
+
+        let suspended: CronJob = parse(json!({
+            "spec": { "schedule": "@hourly", "suspend": true, "jobTemplate": {} },
+            "status": { "lastScheduleTime": "2026-10-18T11:00:00Z" },
+        }));
+        assert_eq!(colors(k8s_cronjob_status(&suspended)), ["Secondary"]);
+
+        assert!(k8s_cronjob_status(&CronJob::default()).is_empty());
+
+        let status = failed.status().cron_job.unwrap();
+        assert_eq!(
+            status.last_schedule_time.unwrap().to_rfc3339(),
+            "2026-10-18T11:00:00+00:00"
+        );
+        assert_eq!(
+            status.last_successful_time.unwrap().to_rfc3339(),
+            "2026-10-18T10:01:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_pod_status() {
+        let pod = |phase: &str, ready: bool, waiting_reason: Option<&str>| -> Pod {
+            parse(json!({
+                "status": {
+                    "phase": phase,
+                    "containerStatuses": [{
+                        "name": "app",
+                        "image": "app:v1",
+                        "imageID": "",
+                        "ready": ready,
+                        "restartCount": 5,
+                        "state": match waiting_reason {
+                            Some(reason) => json!({ "waiting": { "reason": reason } }),
+                            None => json!({ "running": {} }),
+                        },
+                    }],
+                },
+            }))
+        };
+        assert_eq!(
+            colors(k8s_pod_status(&pod("Running", true, None))),
+            ["Success"]
+        );
+        assert_eq!(
+            colors(k8s_pod_status(&pod(
+                "Running",
+                false,
+                Some("CrashLoopBackOff")
+            ))),
+            ["Danger"]
+        );
+        assert_eq!(
+            colors(k8s_pod_status(&pod("Running", false, None))),
+            ["Warning"]
+        );
+        assert_eq!(
+            colors(k8s_pod_status(&pod(
+                "Pending",
+                false,
+                Some("ContainerCreating")
+            ))),
+            ["Warning"]
+        );
+        assert_eq!(
+            colors(k8s_pod_status(&pod("Failed", false, None))),
+            ["Danger"]
+        );
+        assert_eq!(
+            colors(k8s_pod_status(&pod("Succeeded", false, None))),
+            ["Secondary"]
+        );
+        assert!(k8s_pod_status(&Pod::default()).is_empty());
+    }
+
+    #[test]
+    fn test_ingress_status() {
+        let assigned: Ingress = parse(json!({
+            "status": { "loadBalancer": { "ingress": [{ "ip": "203.0.113.10" }] } },
+        }));
+        assert_eq!(colors(k8s_ingress_status(&assigned)), ["Success"]);
+
+        let waiting: Ingress = parse(json!({ "status": { "loadBalancer": {} } }));
+        assert_eq!(colors(k8s_ingress_status(&waiting)), ["Warning"]);
+        assert_eq!(colors(k8s_ingress_status(&Ingress::default())), ["Warning"]);
+
+        let ingress: Ingress = parse(json!({
+            "spec": { "rules": [{ "host": "app.example.com" }, {}] },
+            "status": {
+                "loadBalancer": {
+                    "ingress": [{ "ip": "203.0.113.10" }, { "hostname": "lb.example.com" }],
+                },
+            },
+        }));
+        let status = ingress.status().ingress.unwrap();
+        assert_eq!(status.hosts, ["app.example.com"]);
+        assert_eq!(status.addresses, ["203.0.113.10", "lb.example.com"]);
+    }
+
+    #[test]
+    fn test_pvc_status() {
+        let pvc = |phase: &str| -> PersistentVolumeClaim {
+            parse(json!({ "status": { "phase": phase } }))
+        };
+        assert_eq!(colors(k8s_pvc_status(&pvc("Bound"))), ["Success"]);
+        assert_eq!(colors(k8s_pvc_status(&pvc("Pending"))), ["Warning"]);
+        assert_eq!(colors(k8s_pvc_status(&pvc("Lost"))), ["Danger"]);
+        assert_eq!(
+            colors(k8s_pvc_status(&PersistentVolumeClaim::default())),
+            ["Secondary"]
+        );
+    }
+}
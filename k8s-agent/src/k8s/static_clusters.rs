@@ -0,0 +1,369 @@
+//! The `static` cluster provider registers clusters listed in a YAML file, or
+//! in all `.yaml`/`.yml` files of a directory:
+//!
+//! ```yaml
+//! clusters:
+//!   - name: staging
+//!     region: eu-west-1
+//!     default_env: Staging
+//!     tags:
+//!       team: payments
+//!     kubeconfig: staging.kubeconfig # relative to this file
+//!     context: staging-admin
+//!   - name: on-prem
+//!     provider_id: dc1:on-prem
+//!     region: dc1
+//!     server: https://10.0.0.1:6443
+//!     certificate_authority_data: LS0tLS1CRUdJTi...
+//!     token: eyJhbGciOi...
+//! ```
+//!
+//! The files, along with the kubeconfig files they reference, are checked for
+//! changes periodically and clusters are re-registered whenever they change,
+//! so rotated kubeconfigs are picked up.
+
+use super::{
+    cluster_discovery::{Config, DISCOVERY_HEARTBEAT_GRACE, scope_kubeconfig},
+    cluster_type::{K8s, StaticCluster},
+    missing_clusters::mark_missing_clusters,
+    tracker::K8S_TRACKER,
+};
+use anyhow::{Context, Result, anyhow};
+use platz_otel::health;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::{self, Instant};
+use tracing::{debug, error, info};
+
+const STATIC_REGION: &str = "static";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StaticClustersFile {
+    #[serde(default)]
+    clusters: Vec<StaticClusterConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StaticClusterConfig {
+    name: String,
+    /// Stable identifier of the cluster, defaults to `static:<name>`
+    provider_id: Option<String>,
+    region: Option<String>,
+    /// Name or id of the env to attach the cluster to when first discovered
+    default_env: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    /// Path to a kubeconfig file, relative to the clusters file
+    kubeconfig: Option<PathBuf>,
+    /// Context to use from `kubeconfig`, defaults to its current-context
+    context: Option<String>,
+    /// API server URL, when not using a kubeconfig file
+    server: Option<String>,
+    certificate_authority_data: Option<String>,
+    #[serde(default)]
+    insecure_skip_tls_verify: bool,
+    token: Option<String>,
+    client_certificate_data: Option<String>,
+    client_key_data: Option<String>,
+}
+
+impl StaticClusterConfig {
+    fn into_cluster(self, base_dir: &Path) -> Result<StaticCluster> {
+        let kubeconfig = match (&self.kubeconfig, &self.server) {
+            (Some(path), None) => {
+                let path = base_dir.join(path);
+                let kubeconfig = kube::config::Kubeconfig::read_from(&path)
+                    .with_context(|| format!("Failed reading kubeconfig {}", path.display()))?;
+                scope_kubeconfig(&kubeconfig, self.context.as_deref())?.1
+            }
+            (None, Some(server)) => self.credentials_kubeconfig(server)?,
+            (Some(_), Some(_)) => {
+                return Err(anyhow!(
+                    "Cluster {:?} has both kubeconfig and server set",
+                    self.name
+                ));
+            }
+            (None, None) => {
+                return Err(anyhow!(
+                    "Cluster {:?} needs either kubeconfig or server",
+                    self.name
+                ));
+            }
+        };
+
+        Ok(StaticCluster {
+            provider_id: self
+                .provider_id
+                .unwrap_or_else(|| format!("static:{}", self.name)),
+            name: self.name,
+            region_name: self.region.unwrap_or_else(|| STATIC_REGION.to_owned()),
+            default_env: self.default_env,
+            tags: self.tags,
+            kubeconfig,
+        })
+    }
+
+    /// Builds a kubeconfig from inline credentials. It's deserialized rather
+    /// than constructed since kube keeps secret fields in opaque types.
+    fn credentials_kubeconfig(&self, server: &str) -> Result<kube::config::Kubeconfig> {
+        Ok(serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Config",
+            "clusters": [{
+                "name": self.name,
+                "cluster": {
+                    "server": server,
+                    "certificate-authority-data": self.certificate_authority_data,
+                    "insecure-skip-tls-verify": self.insecure_skip_tls_verify,
+                },
+            }],
+            "users": [{
+                "name": self.name,
+                "user": {
+                    "token": self.token,
+                    "client-certificate-data": self.client_certificate_data,
+                    "client-key-data": self.client_key_data,
+                },
+            }],
+            "contexts": [{
+                "name": self.name,
+                "context": {
+                    "cluster": self.name,
+                    "user": self.name,
+                },
+            }],
+            "current-context": self.name,
+        }))
+        .with_context(|| format!("Invalid credentials for cluster {:?}", self.name))?)
+    }
+}
+
+pub async fn run_static_cluster_discovery(config: &Config) -> Result<()> {
+    let path = static_clusters_path(config)?;
+    let refresh_interval: std::time::Duration = config.k8s_refresh_interval.into();
+    let reload_interval: std::time::Duration = config.static_clusters_reload_interval.into();
+    let mut interval = time::interval(reload_interval);
+    let mut last_contents = None;
+    let mut last_loaded_at: Option<Instant> = None;
+
+    loop {
+        interval.tick().await;
+        health::heartbeat(
+            "cluster_discovery",
+            reload_interval + DISCOVERY_HEARTBEAT_GRACE,
+        );
+
+        let contents = match read_files(path).await {
+            Ok(contents) => contents,
+            Err(err) => {
+                error!("Error reading static clusters: {:?}", err);
+                continue;
+            }
+        };
+        // Kubeconfigs are compared too, so rotating one reloads its cluster
+        let contents = (read_kubeconfigs(&contents).await, contents);
+
+        let changed = last_contents.as_ref() != Some(&contents);
+        let refresh_due = last_loaded_at.is_none_or(|at| at.elapsed() >= refresh_interval);
+        if !changed && !refresh_due {
+            continue;
+        }
+        if changed && last_contents.is_some() {
+            info!("Static clusters changed, reloading");
+        }
+
+        // A bad edit fails the whole reload, keeping the previously
+        // registered clusters as they were.
+        let started_at = chrono::Utc::now();
+        match send_clusters(&contents.1).await {
+            Ok(()) => {
+                last_contents = Some(contents);
+                last_loaded_at = Some(Instant::now());
+                health::set_ready("clusters", Ok(()));
+                if let Err(err) = mark_missing_clusters(config, started_at).await {
+                    error!("Error marking missing clusters: {:?}", err);
+                }
+            }
+            Err(err) => error!("Error loading static clusters: {:?}", err),
+        }
+    }
+}
+
+pub async fn load_static_clusters(config: &Config) -> Result<Vec<K8s>> {
+    parse_clusters(&read_files(static_clusters_path(config)?).await?)
+}
+
+fn static_clusters_path(config: &Config) -> Result<&Path> {
+    config.static_clusters.as_deref().ok_or_else(|| {
+        anyhow!("PLATZ_STATIC_CLUSTERS must be set when PLATZ_CLUSTER_PROVIDER is static")
+    })
+}
+
+async fn send_clusters(contents: &[(PathBuf, String)]) -> Result<()> {
+    let tracker_tx = K8S_TRACKER.inbound_requests_tx().await;
+    for cluster in parse_clusters(contents)? {
+        tracing::debug!(%cluster);
+        tracker_tx.send(Arc::new(cluster))?;
+    }
+    Ok(())
+}
+
+/// Reads the clusters file, or all YAML files in the clusters directory,
+/// returning their paths and contents sorted by path.
+async fn read_files(path: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let paths = if tokio::fs::metadata(path).await?.is_dir() {
+        let mut paths = Vec::new();
+        let mut entries = tokio::fs::read_dir(path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let is_yaml = path
+                .extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml");
+            if is_yaml && entry.file_type().await?.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths
+    } else {
+        vec![path.to_owned()]
+    };
+
+    let mut contents = Vec::with_capacity(paths.len());
+    for path in paths {
+        debug!("Reading static clusters from {}", path.display());
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed reading {}", path.display()))?;
+        contents.push((path, content));
+    }
+    Ok(contents)
+}
+
+/// Reads the kubeconfig files referenced by the clusters files, returning
+/// their paths and contents sorted by path. Files that can't be parsed or read
+/// are skipped, loading the clusters reports them.
+async fn read_kubeconfigs(contents: &[(PathBuf, String)]) -> Vec<(PathBuf, String)> {
+    let mut paths = Vec::new();
+    for (path, content) in contents {
+        let Ok(file) = serde_yaml::from_str::<StaticClustersFile>(content) else {
+            continue;
+        };
+        let base_dir = base_dir(path);
+        paths.extend(
+            file.clusters
+                .into_iter()
+                .filter_map(|cluster_config| cluster_config.kubeconfig)
+                .map(|kubeconfig| base_dir.join(kubeconfig)),
+        );
+    }
+    paths.sort();
+    paths.dedup();
+
+    let mut kubeconfigs = Vec::with_capacity(paths.len());
+    for path in paths {
+        if let Ok(content) = tokio::fs::read_to_string(&path).await {
+            kubeconfigs.push((path, content));
+        }
+    }
+    kubeconfigs
+}
+
+/// Kubeconfig paths are relative to the file listing the cluster
+fn base_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or_else(|| Path::new("."))
+}
+
+fn parse_clusters(contents: &[(PathBuf, String)]) -> Result<Vec<K8s>> {
+    let mut clusters = Vec::new();
+    let mut provider_ids = HashSet::new();
+    for (path, content) in contents {
+        let file: StaticClustersFile = serde_yaml::from_str(content)
+            .with_context(|| format!("Failed parsing {}", path.display()))?;
+        let base_dir = base_dir(path);
+        for cluster_config in file.clusters {
+            let cluster = cluster_config
+                .into_cluster(base_dir)
+                .with_context(|| format!("Invalid cluster in {}", path.display()))?;
+            if !provider_ids.insert(cluster.provider_id.clone()) {
+                return Err(anyhow!(
+                    "Duplicate static cluster {:?} in {}",
+                    cluster.provider_id,
+                    path.display()
+                ));
+            }
+            clusters.push(K8s::from(cluster));
+        }
+    }
+    Ok(clusters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clusters() {
+        let contents = vec![(
+            PathBuf::from("/etc/platz/clusters.yaml"),
+            r#"
+clusters:
+  - name: on-prem
+    region: dc1
+    default_env: Staging
+    server: https://10.0.0.1:6443
+    token: abc
+  - name: other
+    provider_id: dc2:other
+    server: https://10.0.0.2:6443
+    insecure_skip_tls_verify: true
+"#
+            .to_owned(),
+        )];
+
+        let clusters = parse_clusters(&contents).unwrap();
+        assert_eq!(clusters.len(), 2);
+
+        let K8s::Static(first) = &clusters[0] else {
+            panic!("Expected a static cluster");
+        };
+        assert_eq!(first.provider_id, "static:on-prem");
+        assert_eq!(first.region_name, "dc1");
+        assert_eq!(first.default_env.as_deref(), Some("Staging"));
+        assert_eq!(
+            first.kubeconfig.clusters[0]
+                .cluster
+                .as_ref()
+                .unwrap()
+                .server
+                .as_deref(),
+            Some("https://10.0.0.1:6443")
+        );
+
+        let K8s::Static(second) = &clusters[1] else {
+            panic!("Expected a static cluster");
+        };
+        assert_eq!(second.provider_id, "dc2:other");
+        assert_eq!(second.region_name, STATIC_REGION);
+    }
+
+    #[test]
+    fn test_parse_clusters_rejects_duplicates() {
+        let contents = vec![(
+            PathBuf::from("clusters.yaml"),
+            r#"
+clusters:
+  - name: a
+    server: https://a
+  - name: a
+    server: https://b
+"#
+            .to_owned(),
+        )];
+        assert!(parse_clusters(&contents).is_err());
+    }
+}
@@ -3,21 +3,34 @@ use super::annotations::{
     find_deployment_from_namespace,
 };
 use super::cluster_type::K8s;
+use super::resource_status::TrackedResource;
+use crate::metrics::WATCH_RECONNECTS;
 use anyhow::{Result, anyhow};
 use chrono::prelude::*;
-use futures::{FutureExt, StreamExt, TryStreamExt};
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::{CronJob, Job};
+use k8s_openapi::api::core::v1::{Namespace, PersistentVolumeClaim, Pod};
+use k8s_openapi::api::networking::v1::Ingress;
 use kube::ResourceExt;
-use kube::api::{Api, ListParams, WatchEvent, WatchParams};
+use kube::api::{Api, ListParams};
+use kube::runtime::reflector::{self, ObjectRef};
+use kube::runtime::{WatchStreamExt, watcher};
 use lazy_static::lazy_static;
+use platz_db::Json;
 use platz_db::schema::{
     deployment::DeploymentStatus,
-    deployment_status::DeploymentReportedStatusColor,
-    k8s_cluster::{NewK8sCluster, UpdateK8sClusterStatus},
-    k8s_resource::K8sResource,
+    env::Env,
+    k8s_cluster::{K8sCluster, NewK8sCluster, UpdateK8sClusterStatus},
+    k8s_cluster_rule::K8sClusterRule,
+    k8s_resource::{K8sResource, K8sResourceStatus},
 };
+use serde::de::DeserializeOwned;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::{RwLock, broadcast, watch};
+use std::time::Duration;
+use tokio::sync::{RwLock, broadcast, mpsc, watch};
 use tokio::{select, task};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -88,13 +101,13 @@ impl K8sTracker {
                 break;
             };
             tracing::debug!(cluster_name=?cluster, "Got cluster update");
-            let db_cluster = match NewK8sCluster::from(cluster.as_ref()).insert().await {
+            let db_cluster = match save_db_cluster(&cluster).await {
                 Ok(db_cluster) => {
                     debug!("Updated in database: {:?}", db_cluster);
                     db_cluster
                 }
                 Err(err) => {
-                    error!("Failed updating cluster in database: {:?}", err);
+                    error!("Failed updating cluster {} in database: {:?}", cluster, err);
                     continue;
                 }
             };
@@ -134,7 +147,7 @@ impl K8sTracker {
         });
     }
 
-    async fn stop_watching_cluster(&self, id: Uuid) {
+    pub async fn stop_watching_cluster(&self, id: Uuid) {
         let mut inner = self.inner.write().await;
         inner.clusters.remove(&id);
         match inner.tasks.remove(&id) {
@@ -176,83 +189,240 @@ impl K8sTracker {
     }
 }
 
+/// Saves a discovered cluster to the database. Clusters seen for the first
+/// time without a default env get the first matching cluster rule applied.
+async fn save_db_cluster(cluster: &K8s) -> Result<K8sCluster> {
+    let new_cluster = new_db_cluster(cluster).await?;
+    let existing = K8sCluster::find_by_provider_id(new_cluster.provider_id.clone()).await?;
+    let apply_rules = existing.is_none() && new_cluster.env_id.is_none();
+    let db_cluster = new_cluster.insert().await?;
+    if existing.is_some_and(|cluster| cluster.missing_since.is_some()) {
+        info!(
+            cluster_id = %db_cluster.id,
+            "Missing cluster {} was discovered again",
+            db_cluster.name,
+        );
+        platz_db::schema::deployment::Deployment::unorphan_all_in_cluster(db_cluster.id).await?;
+    }
+    if !apply_rules {
+        return Ok(db_cluster);
+    }
+    let (db_cluster, rule) = K8sClusterRule::apply_first_match(db_cluster).await?;
+    if let Some(rule) = rule {
+        info!(
+            cluster_id = %db_cluster.id,
+            rule_id = %rule.id,
+            "Applied cluster rule {:?} to new cluster {}",
+            rule.name,
+            db_cluster.name,
+        );
+    }
+    Ok(db_cluster)
+}
+
+/// Converts a discovered cluster to its database representation, resolving
+/// its default env (if any) so new clusters are attached and watched.
+async fn new_db_cluster(cluster: &K8s) -> Result<NewK8sCluster> {
+    let mut new_cluster = NewK8sCluster::from(cluster);
+    if let Some(env) = cluster.default_env() {
+        let env = match Uuid::parse_str(env) {
+            Ok(env_id) => Env::find(env_id).await?,
+            Err(_) => Env::find_by_name(env)
+                .await?
+                .ok_or_else(|| anyhow!("Unknown default env {env:?}"))?,
+        };
+        new_cluster.env_id = Some(env.id);
+        new_cluster.ignore = Some(false);
+    }
+    Ok(new_cluster)
+}
+
+/// Flush batched resource writes at least this often
+const RESOURCE_FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+/// Flush batched resource writes early once this many changes are pending
+const RESOURCE_FLUSH_MAX_PENDING: usize = 500;
+
 async fn watch_for_cluster_changes(cluster_id: Uuid, client: kube::Client) -> Result<()> {
     debug!("watching");
-    let start_time = Utc::now();
-    let ns_api = Api::<k8s_openapi::api::core::v1::Namespace>::all(client.clone());
-    let mut namespaces = Api::<k8s_openapi::api::core::v1::Namespace>::all(client.clone())
-        .watch(
-            &WatchParams::default().labels(&DEPLOYMENT_NAMESPACE_LABELS_SELECTOR),
-            "0",
-        )
-        .await?
-        .boxed();
-    let mut deployments = Api::<k8s_openapi::api::apps::v1::Deployment>::all(client.clone())
-        .watch(&WatchParams::default(), "0")
-        .await?
-        .boxed();
-    let mut statefulsets = Api::<k8s_openapi::api::apps::v1::StatefulSet>::all(client.clone())
-        .watch(&WatchParams::default(), "0")
-        .await?
-        .boxed();
-    let mut jobs = Api::<k8s_openapi::api::batch::v1::Job>::all(client)
-        .watch(&WatchParams::default(), "0")
-        .await?
-        .boxed();
 
-    // Delete unfamiliar resources after 1 minute of successfully getting updates from k8s
-    let mut delete_resources_timeout = tokio::time::sleep(tokio::time::Duration::from_secs(60))
-        .fuse()
-        .boxed();
+    // Only namespaces created by Platz are watched (and cached). Resources of
+    // other kinds carry no Platz label, so they're watched separately in each
+    // cached Platz namespace, letting the API server do the filtering.
+    let (namespaces, namespaces_writer) = reflector::store::<Namespace>();
+    let mut namespace_events = watcher::watcher(
+        Api::<Namespace>::all(client.clone()),
+        watcher::Config::default().labels(&DEPLOYMENT_NAMESPACE_LABELS_SELECTOR),
+    )
+    .default_backoff()
+    .reflect(namespaces_writer)
+    .boxed();
+
+    let mut is_ok = true;
+
+    // Resources are attributed to deployments by their namespace, so wait for
+    // the initial namespace listing before starting to watch resources.
+    while let Some(event) = namespace_events.next().await {
+        let init_done = matches!(event, Ok(watcher::Event::InitDone));
+        handle_namespace_event(cluster_id, event, &mut is_ok).await?;
+        if init_done {
+            break;
+        }
+    }
+
+    let (changes_tx, mut resource_changes) = mpsc::channel(RESOURCE_FLUSH_MAX_PENDING);
+    let mut watches = HashMap::new();
+    sync_namespace_watches(cluster_id, &client, &namespaces, &mut watches, &changes_tx).await?;
+    // Resources of namespaces deleted while the cluster wasn't watched
+    delete_resources_outside_watches(cluster_id, &watches).await?;
+
+    let mut batch = ResourceBatch::new(cluster_id, namespaces.clone());
+    let mut flush_interval = tokio::time::interval(RESOURCE_FLUSH_INTERVAL);
+    flush_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
     loop {
         select! {
-            result = namespaces.try_next() => {
-                match result? {
+            event = namespace_events.next() => {
+                match event {
                     Some(event) => {
-                        tracing::debug!(namespace_event=?event);
-                        handle_namespace_event(event).await?;
+                        handle_namespace_event(cluster_id, event, &mut is_ok).await?;
+                        sync_namespace_watches(
+                            cluster_id,
+                            &client,
+                            &namespaces,
+                            &mut watches,
+                            &changes_tx,
+                        )
+                        .await?;
                     }
                     None => break,
                 }
             }
-            result = deployments.try_next() => {
-                match result? {
-                    Some(event) => {
-                        tracing::debug!(deployment_event=?event);
-                        handle_resource_event(cluster_id, event, &ns_api, k8s_deployment_status).await?;
+            change = resource_changes.recv() => {
+                match change {
+                    Some(ResourceChange::Error(kind, err)) => {
+                        warn!(%kind, ?err, "Resource watch failed, reconnecting");
+                        WATCH_RECONNECTS
+                            .with_label_values(&[&cluster_id.to_string(), kind])
+                            .inc();
+                        set_watch_status(cluster_id, &mut is_ok, Some(err)).await?;
                     }
-                    None => break,
-                }
-            }
-            result = statefulsets.try_next() => {
-                match result? {
-                    Some(event) => {
-                        tracing::debug!(statefulset_event=?event);
-                        handle_resource_event(cluster_id, event, &ns_api, k8s_statefulset_status).await?;
+                    Some(change) => {
+                        set_watch_status(cluster_id, &mut is_ok, None).await?;
+                        batch.push(change).await?;
                     }
                     None => break,
                 }
             }
-            result = jobs.try_next() => {
-                match result? {
-                    Some(event) => {
-                        tracing::debug!(job_event=?event);
-                        handle_resource_event(cluster_id, event, &ns_api, k8s_job_status).await?;
-                    }
-                    None => break,
-                }
+            _ = flush_interval.tick() => {
+                batch.flush().await?;
             }
-            _ = delete_resources_timeout.as_mut() => {
-                debug!("Deleting old K8sResources");
-                for resource in K8sResource::find_older_than(cluster_id, start_time).await? {
-                    tracing::debug!(?resource);
-                    resource.delete().await?;
+        }
+    }
+
+    batch.flush().await?;
+    Ok(())
+}
+
+/// Resource watches of a single Platz namespace, stopped when dropped
+struct NamespaceWatch {
+    deployment_id: Uuid,
+    task: task::JoinHandle<()>,
+}
+
+impl Drop for NamespaceWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Starts watching resources in namespaces added to the namespace store and
+/// stops watching removed ones, deleting the resources of their deployments.
+async fn sync_namespace_watches(
+    cluster_id: Uuid,
+    client: &kube::Client,
+    namespaces: &reflector::Store<Namespace>,
+    watches: &mut HashMap<String, NamespaceWatch>,
+    changes_tx: &mpsc::Sender<ResourceChange>,
+) -> Result<()> {
+    let namespaces = namespaces
+        .state()
+        .iter()
+        .filter_map(|ns| Some((ns.name_any(), namespace_deployment_id(ns)?)))
+        .collect::<HashMap<_, _>>();
+
+    let watched = watches.len();
+    watches.retain(|name, watch| namespaces.get(name) == Some(&watch.deployment_id));
+    let removed = watched != watches.len();
+
+    for (name, deployment_id) in namespaces {
+        watches.entry(name).or_insert_with_key(|name| {
+            debug!(namespace = name, %deployment_id, "Watching namespace resources");
+            watch_namespace(client.clone(), name, deployment_id, changes_tx.clone())
+        });
+    }
+
+    if removed {
+        delete_resources_outside_watches(cluster_id, watches).await?;
+    }
+    Ok(())
+}
+
+async fn delete_resources_outside_watches(
+    cluster_id: Uuid,
+    watches: &HashMap<String, NamespaceWatch>,
+) -> Result<()> {
+    let deleted = K8sResource::delete_outside_deployments(
+        cluster_id,
+        watches.values().map(|watch| watch.deployment_id).collect(),
+    )
+    .await?;
+    debug!(deleted, "Deleted K8sResources of unwatched namespaces");
+    Ok(())
+}
+
+fn watch_namespace(
+    client: kube::Client,
+    namespace: &str,
+    deployment_id: Uuid,
+    changes_tx: mpsc::Sender<ResourceChange>,
+) -> NamespaceWatch {
+    let mut changes = futures::stream::select_all([
+        watch_resources::<Deployment>(client.clone(), namespace),
+        watch_resources::<StatefulSet>(client.clone(), namespace),
+        watch_resources::<DaemonSet>(client.clone(), namespace),
+        watch_resources::<Job>(client.clone(), namespace),
+        watch_resources::<CronJob>(client.clone(), namespace),
+        watch_resources::<Pod>(client.clone(), namespace),
+        watch_resources::<Ingress>(client.clone(), namespace),
+        watch_resources::<PersistentVolumeClaim>(client, namespace),
+    ]);
+    NamespaceWatch {
+        deployment_id,
+        task: task::spawn(async move {
+            while let Some(change) = changes.next().await {
+                if changes_tx.send(change).await.is_err() {
+                    break;
                 }
             }
-        }
+        }),
     }
+}
 
+fn namespace_deployment_id(namespace: &Namespace) -> Option<Uuid> {
+    namespace
+        .annotations()
+        .get(NAMESPACE_ANNOTATION_DEPLOYMENT_ID)
+        .and_then(|id| Uuid::parse_str(id).ok())
+}
+
+/// Keeps the cluster status in sync with the health of its watches, only
+/// writing to the database when the status changes.
+async fn set_watch_status(cluster_id: Uuid, is_ok: &mut bool, error: Option<String>) -> Result<()> {
+    let now_ok = error.is_none();
+    if *is_ok != now_ok {
+        set_cluster_status(cluster_id, now_ok, error).await?;
+        *is_ok = now_ok;
+    }
     Ok(())
 }
 
@@ -302,10 +472,26 @@ pub async fn handle_already_cleared_namespaces(
 }
 
 async fn handle_namespace_event(
-    event: WatchEvent<k8s_openapi::api::core::v1::Namespace>,
+    cluster_id: Uuid,
+    event: Result<watcher::Event<Namespace>, watcher::Error>,
+    is_ok: &mut bool,
 ) -> Result<()> {
+    let event = match event {
+        Ok(event) => {
+            set_watch_status(cluster_id, is_ok, None).await?;
+            event
+        }
+        Err(err) => {
+            warn!(?err, "Namespace watch failed, reconnecting");
+            WATCH_RECONNECTS
+                .with_label_values(&[&cluster_id.to_string(), "Namespace"])
+                .inc();
+            return set_watch_status(cluster_id, is_ok, Some(err.to_string())).await;
+        }
+    };
+
     match event {
-        WatchEvent::Added(ns) | WatchEvent::Modified(ns) => {
+        watcher::Event::Apply(ns) | watcher::Event::InitApply(ns) => {
             match find_deployment_from_namespace(&ns).await? {
                 Some(_deployment) => {}
                 None => {
@@ -319,7 +505,7 @@ async fn handle_namespace_event(
             };
             Ok(())
         }
-        WatchEvent::Deleted(ns) => {
+        watcher::Event::Delete(ns) => {
             match find_deployment_from_namespace(&ns).await? {
                 Some(deployment) => {
                     deployment_removal_completed(deployment).await?;
@@ -334,8 +520,7 @@ async fn handle_namespace_event(
             };
             Ok(())
         }
-        WatchEvent::Bookmark(_) => Ok(()),
-        WatchEvent::Error(err) => Err(err.into()),
+        watcher::Event::Init | watcher::Event::InitDone => Ok(()),
     }
 }
 
@@ -357,92 +542,263 @@ async fn deployment_removal_completed(
     Ok(())
 }
 
-#[tracing::instrument(err, skip_all, fields(%cluster_id))]
-async fn handle_resource_event<T, G>(
-    cluster_id: Uuid,
-    event: WatchEvent<T>,
-    ns_api: &Api<k8s_openapi::api::core::v1::Namespace>,
-    get_status_color: G,
-) -> Result<()>
+/// A change to a tracked resource, already converted from its Kubernetes
+/// type so changes of all kinds can be batched together.
+enum ResourceChange {
+    Upsert(ChangedResource),
+    Delete(Uuid),
+    /// The watcher started (re)listing all resources of this kind in a namespace
+    RelistStarted(String, &'static str),
+    /// The watcher finished (re)listing, any resource of this kind in the
+    /// namespace not seen since the relist started no longer exists
+    RelistDone(String, &'static str),
+    Error(&'static str, String),
+}
+
+struct ChangedResource {
+    id: Uuid,
+    namespace: String,
+    kind: &'static str,
+    api_version: &'static str,
+    name: String,
+    status_color: Vec<String>,
+    status: K8sResourceStatus,
+    metadata: serde_json::Value,
+    last_updated_at: DateTime<Utc>,
+}
+
+fn watch_resources<T>(client: kube::Client, namespace: &str) -> BoxStream<'static, ResourceChange>
 where
-    T: k8s_openapi::Resource
-        + k8s_openapi::Metadata<Ty = k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta>
-        + std::fmt::Debug,
-    G: Fn(&T) -> Vec<DeploymentReportedStatusColor>,
+    T: TrackedResource
+        + kube::Resource<DynamicType = ()>
+        + Clone
+        + DeserializeOwned
+        + Send
+        + Sync
+        + 'static,
 {
-    let (resource, is_create) = match event {
-        WatchEvent::Added(resource) | WatchEvent::Modified(resource) => (resource, true),
-        WatchEvent::Deleted(resource) => (resource, false),
-        WatchEvent::Bookmark(_) => return Ok(()),
-        WatchEvent::Error(err) => return Err(err.into()),
-    };
-
-    let metadata = resource.metadata();
+    // The watcher takes care of resourceVersions and bookmarks, resuming
+    // from the last seen version and relisting only when it has expired.
+    let namespace = namespace.to_owned();
+    watcher::watcher(
+        Api::<T>::namespaced(client, &namespace),
+        watcher::Config::default().any_semantic(),
+    )
+    .default_backoff()
+    .filter_map(move |event| futures::future::ready(resource_change(&namespace, event)))
+    .boxed()
+}
 
-    let namespace = match metadata.namespace.as_ref() {
-        Some(ns) => ns_api.get(ns).await?,
-        None => {
-            tracing::warn!(?resource, "Resource has no namespace");
-            return Ok(());
-        }
+fn resource_change<T>(
+    namespace: &str,
+    event: Result<watcher::Event<T>, watcher::Error>,
+) -> Option<ResourceChange>
+where
+    T: TrackedResource,
+{
+    let event = match event {
+        Ok(event) => event,
+        Err(err) => return Some(ResourceChange::Error(T::KIND, err.to_string())),
     };
 
-    let deployment = match find_deployment_from_namespace(&namespace).await? {
-        None => {
-            tracing::warn!(
-                namespace=?namespace.metadata.name,
-                "Could not find deployment for namespace"
-            );
-            return Ok(());
+    match event {
+        watcher::Event::Init => Some(ResourceChange::RelistStarted(namespace.to_owned(), T::KIND)),
+        watcher::Event::InitDone => Some(ResourceChange::RelistDone(namespace.to_owned(), T::KIND)),
+        watcher::Event::Apply(resource) | watcher::Event::InitApply(resource) => {
+            match changed_resource(&resource) {
+                Ok(changed) => changed.map(ResourceChange::Upsert),
+                Err(err) => {
+                    warn!(?resource, ?err, "Ignoring resource");
+                    None
+                }
+            }
         }
-        Some(deployment) => deployment,
+        watcher::Event::Delete(resource) => resource_uid(&resource).map(ResourceChange::Delete),
+    }
+}
+
+fn resource_uid<T>(resource: &T) -> Option<Uuid>
+where
+    T: TrackedResource,
+{
+    resource
+        .metadata()
+        .uid
+        .as_deref()
+        .and_then(|uid| Uuid::parse_str(uid).ok())
+}
+
+fn changed_resource<T>(resource: &T) -> Result<Option<ChangedResource>>
+where
+    T: TrackedResource,
+{
+    let metadata = resource.metadata();
+    let Some(namespace) = metadata.namespace.clone() else {
+        return Ok(None);
     };
+    Ok(Some(ChangedResource {
+        id: resource_uid(resource).ok_or_else(|| anyhow!("Resource has no uid"))?,
+        namespace,
+        kind: T::KIND,
+        api_version: T::API_VERSION,
+        name: metadata
+            .name
+            .clone()
+            .ok_or_else(|| anyhow!("Resource has no name"))?,
+        status_color: resource
+            .status_color()
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect(),
+        status: resource.status(),
+        metadata: serde_json::to_value(metadata)?,
+        last_updated_at: Utc::now(),
+    }))
+}
 
-    let id = Uuid::parse_str(
-        metadata
-            .uid
-            .as_ref()
-            .ok_or_else(|| anyhow!("Resource has no uid"))?,
-    )?;
+enum PendingWrite {
+    Upsert(ChangedResource),
+    Delete,
+}
 
-    if is_create {
-        K8sResource {
-            id,
+/// Debounces resource changes and writes them to the database in batches.
+/// Only the latest change of each resource is written.
+struct ResourceBatch {
+    cluster_id: Uuid,
+    namespaces: reflector::Store<Namespace>,
+    pending: HashMap<Uuid, PendingWrite>,
+    relists: HashMap<(String, &'static str), HashSet<Uuid>>,
+}
+
+impl ResourceBatch {
+    fn new(cluster_id: Uuid, namespaces: reflector::Store<Namespace>) -> Self {
+        Self {
             cluster_id,
-            deployment_id: deployment.id,
-            kind: k8s_openapi::kind(&resource).to_owned(),
-            api_version: k8s_openapi::api_version(&resource).to_owned(),
-            name: metadata
-                .name
-                .as_ref()
-                .ok_or_else(|| {
-                    anyhow!(
-                        "[cluster {}] Resource has no name: {:?}",
-                        cluster_id,
-                        resource
+            namespaces,
+            pending: Default::default(),
+            relists: Default::default(),
+        }
+    }
+
+    async fn push(&mut self, change: ResourceChange) -> Result<()> {
+        match change {
+            ResourceChange::Upsert(resource) => {
+                if let Some(seen) = self
+                    .relists
+                    .get_mut(&(resource.namespace.clone(), resource.kind))
+                {
+                    seen.insert(resource.id);
+                }
+                self.pending
+                    .insert(resource.id, PendingWrite::Upsert(resource));
+            }
+            ResourceChange::Delete(id) => {
+                self.pending.insert(id, PendingWrite::Delete);
+            }
+            ResourceChange::RelistStarted(namespace, kind) => {
+                self.relists.insert((namespace, kind), Default::default());
+            }
+            ResourceChange::RelistDone(namespace, kind) => {
+                self.flush().await?;
+                let seen = self.relists.remove(&(namespace.clone(), kind));
+                // Resources of removed namespaces are deleted along with their watch
+                if let (Some(seen), Some(deployment_id)) = (seen, self.deployment_id(&namespace)) {
+                    let deleted = K8sResource::delete_stale(
+                        self.cluster_id,
+                        deployment_id,
+                        kind,
+                        seen.into_iter().collect(),
                     )
-                })?
-                .to_owned(),
-            status_color: get_status_color(&resource)
-                .into_iter()
-                .map(|s| s.to_string())
-                .collect(),
-            metadata: serde_json::to_value(metadata)?,
-            last_updated_at: Utc::now(),
+                    .await?;
+                    debug!(%namespace, %kind, deleted, "Deleted stale K8sResources after relist");
+                }
+            }
+            ResourceChange::Error(_, _) => {}
         }
-        .save()
-        .await?;
-    } else {
-        match K8sResource::delete_by_id(id).await {
-            Ok(_) => tracing::debug!(%id, "Deleted K8sResource"),
-            Err(err) => tracing::error!(
-                %id, ?err,
-                "Failed deleting K8sResource"
-            ),
+
+        if self.pending.len() >= RESOURCE_FLUSH_MAX_PENDING {
+            self.flush().await?;
         }
+        Ok(())
     }
 
-    Ok(())
+    fn deployment_id(&self, namespace: &str) -> Option<Uuid> {
+        self.namespaces
+            .get(&ObjectRef::new(namespace))
+            .and_then(|namespace| namespace_deployment_id(&namespace))
+    }
+
+    #[tracing::instrument(err, skip_all, fields(cluster_id=%self.cluster_id))]
+    async fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut upserts = Vec::new();
+        let mut deletes = Vec::new();
+        for (id, write) in self.pending.drain() {
+            match write {
+                PendingWrite::Upsert(resource) => upserts.push(resource),
+                PendingWrite::Delete => deletes.push(id),
+            }
+        }
+
+        let resources = upserts
+            .into_iter()
+            .filter_map(|resource| {
+                // Namespaces removed since the change was seen aren't in the store
+                let deployment_id = self.deployment_id(&resource.namespace)?;
+                Some((deployment_id, resource))
+            })
+            .collect::<Vec<_>>();
+
+        let deployment_ids = platz_db::schema::deployment::Deployment::existing_ids(
+            resources
+                .iter()
+                .map(|(deployment_id, _)| *deployment_id)
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect(),
+        )
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+        let resources = resources
+            .into_iter()
+            .filter_map(|(deployment_id, resource)| {
+                if !deployment_ids.contains(&deployment_id) {
+                    warn!(
+                        namespace = resource.namespace,
+                        %deployment_id,
+                        "Could not find deployment for namespace"
+                    );
+                    return None;
+                }
+                Some(K8sResource {
+                    id: resource.id,
+                    cluster_id: self.cluster_id,
+                    deployment_id,
+                    kind: resource.kind.to_owned(),
+                    api_version: resource.api_version.to_owned(),
+                    name: resource.name,
+                    status_color: resource.status_color,
+                    metadata: resource.metadata,
+                    status: Json(resource.status),
+                    last_updated_at: resource.last_updated_at,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        debug!(
+            upserts = resources.len(),
+            deletes = deletes.len(),
+            "Writing K8sResources"
+        );
+        K8sResource::save_all(resources).await?;
+        K8sResource::delete_by_ids(deletes).await?;
+        Ok(())
+    }
 }
 
 async fn set_cluster_status(id: Uuid, is_ok: bool, reason: Option<String>) -> Result<()> {
@@ -454,63 +810,3 @@ async fn set_cluster_status(id: Uuid, is_ok: bool, reason: Option<String>) -> Re
     .await?;
     Ok(())
 }
-
-fn k8s_deployment_status(
-    deployment: &k8s_openapi::api::apps::v1::Deployment,
-) -> Vec<DeploymentReportedStatusColor> {
-    let status = match &deployment.status {
-        Some(status) => status,
-        None => return Vec::new(),
-    };
-
-    let available = status.available_replicas.unwrap_or_default() as usize;
-    let unavailable = status.unavailable_replicas.unwrap_or_default() as usize;
-
-    std::iter::repeat_n(DeploymentReportedStatusColor::Success, available)
-        .chain(std::iter::repeat_n(
-            DeploymentReportedStatusColor::Danger,
-            unavailable,
-        ))
-        .collect()
-}
-
-fn k8s_statefulset_status(
-    statefulset: &k8s_openapi::api::apps::v1::StatefulSet,
-) -> Vec<DeploymentReportedStatusColor> {
-    let status = match &statefulset.status {
-        Some(status) => status,
-        None => return Vec::new(),
-    };
-
-    let replicas = status.replicas as usize;
-    let ready = status.ready_replicas.unwrap_or_default() as usize;
-
-    std::iter::repeat_n(DeploymentReportedStatusColor::Success, ready)
-        .take(ready)
-        .chain(std::iter::repeat_n(
-            DeploymentReportedStatusColor::Danger,
-            replicas - ready,
-        ))
-        .collect()
-}
-
-fn k8s_job_status(job: &k8s_openapi::api::batch::v1::Job) -> Vec<DeploymentReportedStatusColor> {
-    let status = match &job.status {
-        Some(status) => status,
-        None => return Vec::new(),
-    };
-
-    std::iter::repeat_n(
-        DeploymentReportedStatusColor::Primary,
-        status.active.unwrap_or_default() as usize,
-    )
-    .chain(std::iter::repeat_n(
-        DeploymentReportedStatusColor::Success,
-        status.succeeded.unwrap_or_default() as usize,
-    ))
-    .chain(std::iter::repeat_n(
-        DeploymentReportedStatusColor::Danger,
-        status.failed.unwrap_or_default() as usize,
-    ))
-    .collect()
-}
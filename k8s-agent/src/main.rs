@@ -1,10 +1,16 @@
+mod cluster_drain;
 mod config;
 mod deployment_creds;
 mod k8s;
+mod metrics;
+mod registry_secrets;
 mod task_runner;
 mod utils;
 
-use crate::{config::Config, k8s::cluster_discovery::run_cluster_discovery};
+use crate::{
+    config::Config,
+    k8s::{cluster_discovery::run_cluster_discovery, cluster_health::run_cluster_health},
+};
 use anyhow::Result;
 use clap::Parser;
 use platz_db::{DbTable, init_db};
@@ -21,7 +27,7 @@ async fn main() -> Result<()> {
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .expect("Failed installing default crypto provider");
-    platz_otel::init()?;
+    let _otel = platz_otel::init(env!("CARGO_PKG_NAME"))?;
     info!("Starting K8S Agent");
     let mut sigterm = signal(SignalKind::terminate())?;
     let mut sigint = signal(SignalKind::interrupt())?;
@@ -48,11 +54,26 @@ async fn main() -> Result<()> {
             result.map_err(Into::into)
         }
 
+        result = platz_otel::server::serve() => {
+            warn!("Health and metrics server finished: {result:?}");
+            result
+        }
+
         result = run_cluster_discovery(&config.cluster_discovery) => {
             warn!("Cluster discovery task finished");
             result
         }
 
+        result = run_cluster_health(&config.cluster_health) => {
+            warn!("Cluster health task finished");
+            result
+        }
+
+        result = cluster_drain::start() => {
+            warn!("Cluster drain task finished");
+            result
+        }
+
         result = task_runner::start(&config, db) => {
             warn!("Task runner finished");
             result
@@ -62,5 +83,10 @@ async fn main() -> Result<()> {
             warn!("Deployment creds task finished");
             result
         }
+
+        result = registry_secrets::start(&config) => {
+            warn!("Registry secrets task finished");
+            result
+        }
     }
 }
@@ -0,0 +1,33 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    Histogram, HistogramVec, IntCounterVec, exponential_buckets, register_histogram,
+    register_histogram_vec, register_int_counter_vec,
+};
+
+lazy_static! {
+    pub static ref TASK_DURATION: HistogramVec = register_histogram_vec!(
+        "platz_task_duration_seconds",
+        "Time spent running deployment tasks",
+        &["operation", "result"],
+        exponential_buckets(1.0, 2.0, 12).unwrap(),
+    )
+    .unwrap();
+    pub static ref TASK_QUEUE_LATENCY: Histogram = register_histogram!(
+        "platz_task_queue_latency_seconds",
+        "Time from when deployment tasks were due until they started",
+        exponential_buckets(0.1, 2.0, 14).unwrap(),
+    )
+    .unwrap();
+    pub static ref HELM_POD_STARTUP: Histogram = register_histogram!(
+        "platz_helm_pod_startup_seconds",
+        "Time from creating Helm pods until they started running",
+        exponential_buckets(0.5, 2.0, 8).unwrap(),
+    )
+    .unwrap();
+    pub static ref WATCH_RECONNECTS: IntCounterVec = register_int_counter_vec!(
+        "platz_cluster_watch_reconnects_total",
+        "Number of times cluster watches failed and reconnected",
+        &["cluster_id", "kind"],
+    )
+    .unwrap();
+}
@@ -0,0 +1,152 @@
+use crate::{config::Config, k8s::tracker::K8S_TRACKER};
+use anyhow::{Context, Result, anyhow, bail};
+use futures::future::join_all;
+use k8s_openapi::{
+    api::core::v1::Secret as K8sSecret, apimachinery::pkg::apis::meta::v1::ObjectMeta,
+};
+use kube::{
+    ResourceExt,
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams},
+};
+use maplit::btreemap;
+use platz_db::schema::{deployment::Deployment, k8s_cluster::K8sCluster, secret::Secret};
+use std::collections::{BTreeMap, HashSet};
+use tokio::{
+    select,
+    time::{self, interval},
+};
+use tracing::{debug, error};
+
+const REGISTRY_SECRET_LABEL_KEY: &str = "platz-registry-secret";
+const REGISTRY_SECRET_LABEL_VALUE: &str = "yes";
+const DOCKER_CONFIG_JSON_KEY: &str = ".dockerconfigjson";
+const REFRESH_CHUNK_SIZE: usize = 10;
+const REFRESH_SLEEP_BETWEEN_CHUNKS: time::Duration = time::Duration::from_secs(1);
+
+#[tracing::instrument(err, skip_all, name = "registry-secrets")]
+pub async fn start(config: &Config) -> Result<()> {
+    debug!("starting");
+    let refresh_every: time::Duration = config.registry_secrets_refresh_interval.into();
+    if refresh_every.is_zero() {
+        bail!("PLATZ_REGISTRY_SECRETS_REFRESH_INTERVAL must be greater than zero");
+    }
+    let mut interval = interval(refresh_every);
+    let mut k8s_events_rx = K8S_TRACKER.outbound_notifications_rx().await;
+
+    loop {
+        select! {
+            _ = interval.tick() => {
+                debug!("interval");
+            }
+            k8s_event = k8s_events_rx.changed() => {
+                tracing::debug!(?k8s_event);
+                k8s_event?;
+            }
+        }
+
+        if let Err(err) = refresh_registry_secrets().await {
+            error!("Error refreshing registry secrets: {:?}", err);
+        }
+    }
+}
+
+#[tracing::instrument(err, skip_all, name = "refresh")]
+async fn refresh_registry_secrets() -> Result<()> {
+    debug!("started");
+
+    let cluster_ids = K8S_TRACKER.get_ids().await;
+
+    for deploy_chunk in Deployment::find_by_cluster_ids(cluster_ids)
+        .await?
+        .chunks(REFRESH_CHUNK_SIZE)
+    {
+        for result in join_all(
+            deploy_chunk
+                .iter()
+                .filter(|deployment| deployment.enabled)
+                .map(apply_registry_secrets),
+        )
+        .await
+        {
+            // One deployment's namespace failing shouldn't stop the others
+            if let Err(err) = result {
+                error!("{err:?}");
+            }
+        }
+        time::sleep(REFRESH_SLEEP_BETWEEN_CHUNKS).await;
+    }
+
+    Ok(())
+}
+
+/// Creates or updates the env's registry credentials as image pull secrets in
+/// the deployment's namespace, and deletes ones that were removed from the env.
+#[tracing::instrument(err, skip_all, fields(deployment=%deployment.id), name = "apply-registry-secrets")]
+pub(crate) async fn apply_registry_secrets(deployment: &Deployment) -> Result<()> {
+    let env_id = K8sCluster::find(deployment.cluster_id)
+        .await?
+        .env_id
+        .ok_or_else(|| anyhow!("Could not find cluster for deployment"))?;
+    let namespace = deployment.namespace_name().await?;
+    let api = Api::<K8sSecret>::namespaced(
+        K8S_TRACKER
+            .get_cluster(deployment.cluster_id)
+            .await?
+            .kube_client()
+            .await?,
+        &namespace,
+    );
+
+    let mut wanted = HashSet::new();
+    for secret in Secret::all_image_pull_secrets(env_id).await? {
+        let name = secret.image_pull_secret_name();
+        wanted.insert(name.clone());
+        let contents = secret.resolve_contents().await?;
+        if let Err(err) = secret.secret_type.validate(&contents) {
+            // Keep whatever was applied before rather than breaking pulls
+            error!("Not applying {}/{}: {err}", secret.collection, secret.name);
+            continue;
+        }
+        apply(&api, &name, &secret, contents).await?;
+    }
+
+    let selector = format!("{REGISTRY_SECRET_LABEL_KEY}={REGISTRY_SECRET_LABEL_VALUE}");
+    for existing in api
+        .list(&ListParams::default().labels(&selector))
+        .await
+        .context("Failed listing registry secrets")?
+    {
+        let name = existing.name_any();
+        if !wanted.contains(&name) {
+            debug!("deleting {name}");
+            api.delete(&name, &DeleteParams::default())
+                .await
+                .context("Failed deleting registry secret")?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn apply(api: &Api<K8sSecret>, name: &str, secret: &Secret, contents: String) -> Result<()> {
+    let k8s_secret = K8sSecret {
+        metadata: ObjectMeta {
+            name: Some(name.to_owned()),
+            labels: Some(btreemap! {
+                REGISTRY_SECRET_LABEL_KEY.to_owned() => REGISTRY_SECRET_LABEL_VALUE.to_owned(),
+            }),
+            ..Default::default()
+        },
+        type_: Some(secret.secret_type.k8s_type().to_owned()),
+        string_data: Some(BTreeMap::from([(
+            DOCKER_CONFIG_JSON_KEY.to_owned(),
+            contents,
+        )])),
+        ..Default::default()
+    };
+    debug!("applying {name}");
+    api.patch(name, &PatchParams::apply(name), &Patch::Apply(&k8s_secret))
+        .await
+        .context("Failed applying registry secret")?;
+    Ok(())
+}
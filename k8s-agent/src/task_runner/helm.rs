@@ -112,6 +112,14 @@ async fn helm_pod(
             ..Default::default()
         },
     ];
+    // Lets tools in the helm image that support it join the task's trace
+    if let Some(traceparent) = platz_otel::current_traceparent() {
+        env_vars.push(EnvVar {
+            name: "TRACEPARENT".into(),
+            value: Some(traceparent),
+            ..Default::default()
+        });
+    }
     if let Some(region_name) = registry.region_name()? {
         env_vars.push(EnvVar {
             name: "HELM_REGISTRY_REGION".into(),
@@ -6,6 +6,7 @@ use crate::{
         annotations::{DEPLOYMENT_NAMESPACE_LABELS, deployment_namespace_annotations},
         tracker::K8S_TRACKER,
     },
+    registry_secrets::apply_registry_secrets,
 };
 use anyhow::Result;
 use k8s_openapi::{api::core::v1::Namespace, apimachinery::pkg::apis::meta::v1::ObjectMeta};
@@ -42,6 +43,7 @@ impl RunnableDeploymentOperation for DeploymentInstallTask {
             config.deployment_token_duration()?,
         )
         .await?;
+        apply_registry_secrets(deployment).await?;
         match run_helm(config, "install", deployment, task).await {
             Ok(output) => {
                 deployment.set_revision(Some(task.id)).await?;
@@ -142,6 +144,7 @@ impl RunnableDeploymentOperation for DeploymentRecreaseTask {
             config.deployment_token_duration()?,
         )
         .await?;
+        apply_registry_secrets(deployment).await?;
         Ok("".to_owned())
     }
 }
@@ -1,6 +1,7 @@
 mod helm;
 mod install_and_upgrade;
 mod invoke_action;
+mod refresh_secrets;
 mod restart_k8s_resource;
 mod runnable_task;
 mod secrets;
@@ -10,11 +11,17 @@ use crate::{config::Config, k8s::tracker::K8S_TRACKER, utils::create_interval_st
 use anyhow::Result;
 use futures::StreamExt;
 use platz_db::{Db, DbEvent, DbEventOperation, DbTable, schema::deployment_task::DeploymentTask};
+use platz_otel::health;
 use runnable_task::RunnableDeploymentTask;
 pub use secrets::apply_secret;
+use std::time::Duration;
 use tokio::{select, sync::watch};
 use tracing::{Instrument, debug, error, info};
 
+/// Longer than any single task should take, Helm pods alone may take over ten
+/// minutes
+const TASK_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
 #[tracing::instrument(err, skip_all, name = "task_runner")]
 pub async fn start(config: &Config, db: &Db) -> Result<()> {
     let (db_events_tx, mut db_events_rx) = watch::channel(());
@@ -38,6 +45,7 @@ pub async fn start(config: &Config, db: &Db) -> Result<()> {
     let mut updates_interval_stream = create_interval_stream(std::time::Duration::from_secs(60));
 
     loop {
+        health::heartbeat("task_runner", TASK_HEARTBEAT_TIMEOUT);
         run_pending_tasks(config).await?;
         debug!("polling...");
         select! {
@@ -72,6 +80,7 @@ async fn run_pending_tasks(config: &Config) -> Result<()> {
     debug!("fetching tasks...");
     let mut fetch_start_time = Instant::now();
     while let Some(task) = DeploymentTask::next_pending(&cluster_ids).await? {
+        health::heartbeat("task_runner", TASK_HEARTBEAT_TIMEOUT);
         let task_id = task.id;
 
         debug!(
@@ -79,7 +88,17 @@ async fn run_pending_tasks(config: &Config) -> Result<()> {
             Instant::now().duration_since(fetch_start_time)
         );
 
-        let span = tracing::debug_span!("task", task_id = %task_id);
+        // Continue the trace of the request that created the task, with the
+        // time it spent waiting in the queue
+        let queue_wait = chrono::Utc::now() - task.created_at.max(task.execute_at);
+        let span = tracing::info_span!(
+            "task",
+            task_id = %task_id,
+            queue_wait_ms = queue_wait.num_milliseconds(),
+        );
+        if let Some(traceparent) = task.traceparent.as_deref() {
+            platz_otel::set_traceparent(&span, traceparent);
+        }
 
         async move {
             info!("Starting...");
@@ -0,0 +1,234 @@
+use super::{runnable_task::RunnableDeploymentOperation, secrets::apply_changed_secrets};
+use crate::{config::Config, k8s::tracker::K8S_TRACKER};
+use anyhow::{Result, anyhow, bail};
+use k8s_openapi::{
+    NamespaceResourceScope,
+    api::{
+        apps::v1::{DaemonSet, Deployment as K8sDeployment, StatefulSet},
+        core::v1::PodSpec,
+    },
+};
+use kube::{
+    Client, Resource, ResourceExt,
+    api::{Api, ListParams, Restart},
+};
+use platz_chart_ext::UiSchema;
+use platz_db::schema::{
+    deployment::{Deployment, DeploymentStatus},
+    deployment_task::{DeploymentRefreshSecretsTask, DeploymentReinstallTask, DeploymentTask},
+    k8s_cluster::K8sCluster,
+};
+use serde::de::DeserializeOwned;
+use std::{collections::HashSet, fmt::Debug};
+use tracing::{debug, warn};
+
+impl RunnableDeploymentOperation for DeploymentRefreshSecretsTask {
+    async fn run(
+        &self,
+        deployment: &Deployment,
+        task: &DeploymentTask,
+        config: &Config,
+    ) -> Result<String> {
+        match refresh_secrets(deployment).await {
+            Ok(output) => Ok(output),
+            Err(err) => {
+                warn!("Refreshing secrets failed, reinstalling instead: {err:?}");
+                let output = DeploymentReinstallTask {
+                    reason: self.reason.clone(),
+                }
+                .run(deployment, task, config)
+                .await?;
+                Ok(format!(
+                    "Refreshing secrets failed ({err}), reinstalled instead\n{output}"
+                ))
+            }
+        }
+    }
+}
+
+async fn refresh_secrets(deployment: &Deployment) -> Result<String> {
+    if deployment.status != DeploymentStatus::Running {
+        bail!("Deployment is {}", deployment.status);
+    }
+    let revision_task = deployment.revision_task().await?;
+    let chart = revision_task.helm_chart().await?;
+    let Some(values_ui) = chart.values_ui.clone() else {
+        return Ok("The chart has no secrets".to_owned());
+    };
+    let ui_schema: UiSchema = serde_json::from_value(values_ui)?;
+    let env_id = K8sCluster::find(deployment.cluster_id)
+        .await?
+        .env_id
+        .ok_or_else(|| anyhow!("Could not find cluster for deployment"))?;
+
+    let applied = apply_changed_secrets(env_id, &ui_schema, deployment, &revision_task).await?;
+    if applied.is_empty() {
+        return Ok("All secrets are up to date".to_owned());
+    }
+
+    let client = K8S_TRACKER
+        .get_cluster(deployment.cluster_id)
+        .await?
+        .kube_client()
+        .await?;
+    let namespace = deployment.namespace_name().await?;
+    let secret_names = applied.iter().map(String::as_str).collect::<HashSet<_>>();
+    let mut restarted = Vec::new();
+    restarted.extend(
+        restart_using::<K8sDeployment>(&client, &namespace, &secret_names, |resource| {
+            resource.spec.as_ref()?.template.spec.as_ref()
+        })
+        .await?,
+    );
+    restarted.extend(
+        restart_using::<StatefulSet>(&client, &namespace, &secret_names, |resource| {
+            resource.spec.as_ref()?.template.spec.as_ref()
+        })
+        .await?,
+    );
+    restarted.extend(
+        restart_using::<DaemonSet>(&client, &namespace, &secret_names, |resource| {
+            resource.spec.as_ref()?.template.spec.as_ref()
+        })
+        .await?,
+    );
+
+    Ok(format!(
+        "Applied secrets: {}\nRestarted: {}",
+        applied.join(", "),
+        if restarted.is_empty() {
+            "none".to_owned()
+        } else {
+            restarted.join(", ")
+        }
+    ))
+}
+
+/// Rollout-restarts the workloads of kind `K` whose pods use any of the
+/// secrets, returning their names.
+async fn restart_using<K>(
+    client: &Client,
+    namespace: &str,
+    secret_names: &HashSet<&str>,
+    pod_spec: impl Fn(&K) -> Option<&PodSpec>,
+) -> Result<Vec<String>>
+where
+    K: Resource<Scope = NamespaceResourceScope> + Restart + Clone + DeserializeOwned + Debug,
+    K::DynamicType: Default,
+{
+    let api = Api::<K>::namespaced(client.clone(), namespace);
+    let mut restarted = Vec::new();
+    for resource in api.list(&ListParams::default()).await? {
+        if pod_uses_secrets(pod_spec(&resource), secret_names) {
+            let name = resource.name_any();
+            debug!("Restarting {}/{name}", K::kind(&Default::default()));
+            api.restart(&name).await?;
+            restarted.push(name);
+        }
+    }
+    Ok(restarted)
+}
+
+/// Whether the pod mounts any of the secrets as a volume, or reads them
+/// into environment variables.
+fn pod_uses_secrets(pod_spec: Option<&PodSpec>, secret_names: &HashSet<&str>) -> bool {
+    let Some(pod_spec) = pod_spec else {
+        return false;
+    };
+    let in_volumes = pod_spec.volumes.iter().flatten().any(|volume| {
+        volume
+            .secret
+            .as_ref()
+            .and_then(|secret| secret.secret_name.as_deref())
+            .is_some_and(|name| secret_names.contains(name))
+            || volume
+                .projected
+                .as_ref()
+                .and_then(|projected| projected.sources.as_ref())
+                .into_iter()
+                .flatten()
+                .filter_map(|source| source.secret.as_ref())
+                .any(|secret| secret_names.contains(secret.name.as_str()))
+    });
+    let in_env = pod_spec
+        .containers
+        .iter()
+        .chain(pod_spec.init_containers.iter().flatten())
+        .any(|container| {
+            container
+                .env_from
+                .iter()
+                .flatten()
+                .filter_map(|env_from| env_from.secret_ref.as_ref())
+                .any(|secret| secret_names.contains(secret.name.as_str()))
+                || container
+                    .env
+                    .iter()
+                    .flatten()
+                    .filter_map(|env| env.value_from.as_ref()?.secret_key_ref.as_ref())
+                    .any(|secret| secret_names.contains(secret.name.as_str()))
+        });
+    in_volumes || in_env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pod_uses_secrets() {
+        let secret_names = HashSet::from(["db-credentials", "api-keys"]);
+        let uses = |pod_spec: serde_json::Value| {
+            let pod_spec: PodSpec = serde_json::from_value(pod_spec).unwrap();
+            pod_uses_secrets(Some(&pod_spec), &secret_names)
+        };
+
+        assert!(uses(json!({
+            "containers": [{ "name": "app" }],
+            "volumes": [{ "name": "creds", "secret": { "secretName": "db-credentials" } }],
+        })));
+        assert!(uses(json!({
+            "containers": [{ "name": "app" }],
+            "volumes": [{
+                "name": "all",
+                "projected": { "sources": [
+                    { "configMap": { "name": "settings" } },
+                    { "secret": { "name": "api-keys" } },
+                ] },
+            }],
+        })));
+        assert!(uses(json!({
+            "containers": [{ "name": "app", "envFrom": [{ "secretRef": { "name": "api-keys" } }] }],
+        })));
+        assert!(uses(json!({
+            "containers": [{ "name": "app" }],
+            "initContainers": [{
+                "name": "migrate",
+                "env": [{
+                    "name": "DB_PASSWORD",
+                    "valueFrom": { "secretKeyRef": { "name": "db-credentials", "key": "password" } },
+                }],
+            }],
+        })));
+
+        assert!(!uses(json!({
+            "containers": [{
+                "name": "app",
+                "envFrom": [{ "configMapRef": { "name": "api-keys" } }],
+                "env": [
+                    { "name": "MODE", "value": "db-credentials" },
+                    {
+                        "name": "TOKEN",
+                        "valueFrom": { "secretKeyRef": { "name": "other", "key": "token" } },
+                    },
+                ],
+            }],
+            "volumes": [
+                { "name": "other", "secret": { "secretName": "other" } },
+                { "name": "cache", "emptyDir": {} },
+            ],
+        })));
+        assert!(!pod_uses_secrets(None, &secret_names));
+    }
+}
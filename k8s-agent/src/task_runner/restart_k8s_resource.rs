@@ -42,6 +42,11 @@ impl RunnableDeploymentOperation for DeploymentRestartK8sResourceTask {
                 api.restart(&resource.name).await?;
                 Ok("".to_owned())
             }
+            "DaemonSet" => {
+                let api = Api::<k8s_openapi::api::apps::v1::DaemonSet>::namespaced(client, &ns);
+                api.restart(&resource.name).await?;
+                Ok("".to_owned())
+            }
             _ => Err(anyhow!(
                 "Resource {} of kind {} doesn't support restart",
                 resource.name,
@@ -1,4 +1,7 @@
-use crate::config::Config;
+use crate::{
+    config::Config,
+    metrics::{TASK_DURATION, TASK_QUEUE_LATENCY},
+};
 use anyhow::Result;
 use platz_db::{
     Json,
@@ -7,6 +10,7 @@ use platz_db::{
         deployment_task::{DeploymentTask, DeploymentTaskOperation, DeploymentTaskStatus},
     },
 };
+use std::time::Instant;
 use tracing::{debug, instrument};
 
 pub trait RunnableDeploymentTask: Send + Sync {
@@ -19,8 +23,14 @@ impl RunnableDeploymentTask for DeploymentTask {
         debug!("fetching deployment...");
         let deployment = Deployment::find(self.deployment_id).await?;
         debug!("updating status to Started...");
-        self.set_status(DeploymentTaskStatus::Started, None).await?;
+        let started = self.set_status(DeploymentTaskStatus::Started, None).await?;
         debug!("status updated");
+        if let Some(started_at) = started.started_at {
+            let latency = (started_at - self.execute_at).to_std().unwrap_or_default();
+            TASK_QUEUE_LATENCY.observe(latency.as_secs_f64());
+        }
+
+        let start_time = Instant::now();
 
         let result = match &self.operation {
             Json(DeploymentTaskOperation::Install(inner)) => {
@@ -35,6 +45,9 @@ impl RunnableDeploymentTask for DeploymentTask {
             Json(DeploymentTaskOperation::Reinstall(inner)) => {
                 inner.run(&deployment, &self, config).await
             }
+            Json(DeploymentTaskOperation::RefreshSecrets(inner)) => {
+                inner.run(&deployment, &self, config).await
+            }
             Json(DeploymentTaskOperation::Uninstall(inner)) => {
                 inner.run(&deployment, &self, config).await
             }
@@ -46,6 +59,13 @@ impl RunnableDeploymentTask for DeploymentTask {
             }
         };
 
+        TASK_DURATION
+            .with_label_values(&[
+                self.operation.0.as_ref(),
+                if result.is_ok() { "done" } else { "failed" },
+            ])
+            .observe(start_time.elapsed().as_secs_f64());
+
         match result {
             Ok(reason) => {
                 self.set_status(DeploymentTaskStatus::Done, Some(reason))
@@ -1,7 +1,7 @@
 use crate::k8s::tracker::K8S_TRACKER;
 use anyhow::{Context, Result};
 use k8s_openapi::api::core::v1::Secret;
-use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::{ByteString, apimachinery::pkg::apis::meta::v1::ObjectMeta};
 use kube::api::{Api, Patch, PatchParams};
 use platz_chart_ext::UiSchema;
 use platz_db::{
@@ -21,7 +21,8 @@ pub async fn apply_secrets(
     let inputs = task.get_config()?;
     for secret in ui_schema
         .get_secrets::<DbTableOrDeploymentResource>(env_id, inputs)
-        .await?
+        .await
+        .with_context(|| format!("Failed resolving secrets of {}", deployment.name))?
         .into_iter()
     {
         apply_secret(
@@ -35,6 +36,56 @@ pub async fn apply_secrets(
     Ok(())
 }
 
+/// Like [`apply_secrets`], but only applies secrets whose data differs from
+/// the secret in the cluster. Returns the names of the applied secrets.
+pub async fn apply_changed_secrets(
+    env_id: Uuid,
+    ui_schema: &UiSchema,
+    deployment: &Deployment,
+    task: &DeploymentTask,
+) -> Result<Vec<String>> {
+    let inputs = task.get_config()?;
+    let namespace = deployment.namespace_name().await?;
+    let api = Api::<Secret>::namespaced(
+        K8S_TRACKER
+            .get_cluster(deployment.cluster_id)
+            .await?
+            .kube_client()
+            .await?,
+        &namespace,
+    );
+    let mut applied = Vec::new();
+    for secret in ui_schema
+        .get_secrets::<DbTableOrDeploymentResource>(env_id, inputs)
+        .await
+        .with_context(|| format!("Failed resolving secrets of {}", deployment.name))?
+        .into_iter()
+    {
+        let data: BTreeMap<String, ByteString> = secret
+            .attrs
+            .iter()
+            .map(|(key, value)| (key.clone(), ByteString(value.clone().into_bytes())))
+            .collect();
+        let current = api
+            .get_opt(&secret.name)
+            .await
+            .context("Failed getting secret")?;
+        if current.is_some_and(|current| current.data.unwrap_or_default() == data) {
+            debug!("{} is up to date", secret.name);
+            continue;
+        }
+        apply_secret(
+            deployment.cluster_id,
+            &namespace,
+            &secret.name,
+            secret.attrs,
+        )
+        .await?;
+        applied.push(secret.name);
+    }
+    Ok(applied)
+}
+
 #[tracing::instrument(err, skip_all, fields(%cluster_id, %namespace, %name))]
 pub async fn apply_secret(
     cluster_id: Uuid,
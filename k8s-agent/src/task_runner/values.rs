@@ -6,7 +6,7 @@ use platz_db::{
     DbTableOrDeploymentResource,
     schema::{
         deployment::Deployment, deployment_kind::DeploymentKind, deployment_task::DeploymentTask,
-        env::Env, k8s_cluster::K8sCluster,
+        env::Env, k8s_cluster::K8sCluster, secret::Secret,
     },
 };
 use serde::Serialize;
@@ -36,6 +36,14 @@ struct PlatzInfo<'a> {
     deployment_kind: String,
     revision_id: Uuid,
     own_url: Url,
+    /// The env's registry credentials, in the format of a pod's
+    /// `imagePullSecrets`
+    image_pull_secrets: Vec<ImagePullSecret>,
+}
+
+#[derive(Clone, Serialize)]
+struct ImagePullSecret {
+    name: String,
 }
 
 #[derive(Default, Serialize)]
@@ -134,6 +142,13 @@ pub async fn create_values_and_secrets(
         deployment_kind: kind_obj.name.to_owned(),
         revision_id: task.id,
         own_url: platz_url.to_owned(),
+        image_pull_secrets: Secret::all_image_pull_secrets(env.id)
+            .await?
+            .iter()
+            .map(|secret| ImagePullSecret {
+                name: secret.image_pull_secret_name(),
+            })
+            .collect(),
     };
 
     let mut values = serde_json::to_value(ChartValues {
@@ -0,0 +1,84 @@
+//! Worker health, served by [`crate::server`].
+//!
+//! Readiness is made of named checks that workers set as their dependencies
+//! come and go, and the worker is ready when all of them pass. Liveness is
+//! made of heartbeats: loops beat as they make progress, and a loop that
+//! misses its deadline is considered wedged.
+
+use lazy_static::lazy_static;
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    static ref CHECKS: Mutex<BTreeMap<&'static str, Result<(), String>>> = Default::default();
+    static ref HEARTBEATS: Mutex<BTreeMap<&'static str, (Instant, Duration)>> = Default::default();
+}
+
+/// Sets the result of a readiness check
+pub fn set_ready(name: &'static str, result: Result<(), String>) {
+    CHECKS.lock().unwrap().insert(name, result);
+}
+
+/// Records that a loop is making progress. The worker stops being live if
+/// the loop doesn't beat again within `max_interval`.
+pub fn heartbeat(name: &'static str, max_interval: Duration) {
+    HEARTBEATS
+        .lock()
+        .unwrap()
+        .insert(name, (Instant::now(), max_interval));
+}
+
+/// Failed readiness checks
+pub(crate) fn readiness_failures() -> Vec<String> {
+    CHECKS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|(name, result)| result.as_ref().err().map(|err| format!("{name}: {err}")))
+        .collect()
+}
+
+/// Loops that missed their heartbeat
+pub(crate) fn liveness_failures() -> Vec<String> {
+    HEARTBEATS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, (last_beat, max_interval))| last_beat.elapsed() > *max_interval)
+        .map(|(name, (last_beat, _))| format!("{name}: no heartbeat for {:?}", last_beat.elapsed()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health() {
+        set_ready("test_check", Err("Not yet".to_owned()));
+        assert!(readiness_failures().contains(&"test_check: Not yet".to_owned()));
+        set_ready("test_check", Ok(()));
+        assert!(
+            !readiness_failures()
+                .iter()
+                .any(|failure| failure.starts_with("test_check"))
+        );
+
+        heartbeat("test_loop", Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(
+            liveness_failures()
+                .iter()
+                .any(|failure| failure.starts_with("test_loop"))
+        );
+        heartbeat("test_loop", Duration::from_secs(60));
+        assert!(
+            !liveness_failures()
+                .iter()
+                .any(|failure| failure.starts_with("test_loop"))
+        );
+    }
+}
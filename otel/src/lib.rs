@@ -1,11 +1,119 @@
-use anyhow::Result;
+//! Logging and tracing for all Platz workers.
+//!
+//! Logs are written to stdout and filtered by `RUST_LOG`. Spans are also
+//! exported over OTLP when `OTEL_EXPORTER_OTLP_ENDPOINT` or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` is set. The exporter is configured by
+//! the standard `OTEL_*` environment variables, with
+//! `OTEL_EXPORTER_OTLP_PROTOCOL` choosing between `grpc`, `http/protobuf` (the
+//! default) and `http/json`.
+
+pub mod health;
+pub mod server;
+
+use anyhow::{Result, bail};
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use std::{collections::HashMap, env};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
-pub fn init() -> Result<()> {
+const TRACEPARENT: &str = "traceparent";
+
+/// Flushes pending spans when dropped, keep it until the worker exits
+#[must_use]
+pub struct OtelGuard {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take()
+            && let Err(err) = provider.shutdown()
+        {
+            eprintln!("Failed flushing traces: {err}");
+        }
+    }
+}
+
+/// Installs the global tracing subscriber. `service_name` is used unless
+/// `OTEL_SERVICE_NAME` is set. Must be called from within a Tokio runtime,
+/// which the gRPC exporter uses.
+pub fn init(service_name: &'static str) -> Result<OtelGuard> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = if otlp_enabled() {
+        let mut resource = Resource::builder();
+        if env::var_os("OTEL_SERVICE_NAME").is_none() {
+            resource = resource.with_service_name(service_name);
+        }
+        let provider = SdkTracerProvider::builder()
+            .with_resource(resource.build())
+            .with_batch_exporter(span_exporter()?)
+            .build();
+        global::set_tracer_provider(provider.clone());
+        Some(provider)
+    } else {
+        None
+    };
+
     tracing_subscriber::registry()
         .with(fmt::layer())
+        .with(provider.as_ref().map(|provider| {
+            tracing_opentelemetry::layer().with_tracer(provider.tracer(service_name))
+        }))
         .with(EnvFilter::from_default_env().add_directive("tokio_postgres=warn".parse()?))
         .init();
 
-    Ok(())
+    Ok(OtelGuard { provider })
+}
+
+fn otlp_enabled() -> bool {
+    let var_is = |name, value: &str| env::var(name).is_ok_and(|v| v.eq_ignore_ascii_case(value));
+    !var_is("OTEL_SDK_DISABLED", "true")
+        && !var_is("OTEL_TRACES_EXPORTER", "none")
+        && [
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+            "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+        ]
+        .into_iter()
+        .any(|name| env::var_os(name).is_some())
+}
+
+fn span_exporter() -> Result<SpanExporter> {
+    let protocol = env::var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")
+        .or_else(|_| env::var("OTEL_EXPORTER_OTLP_PROTOCOL"))
+        .unwrap_or_else(|_| "http/protobuf".to_owned());
+    let builder = SpanExporter::builder();
+    Ok(match protocol.as_str() {
+        "grpc" => builder.with_tonic().build()?,
+        "http/protobuf" => builder
+            .with_http()
+            .with_protocol(Protocol::HttpBinary)
+            .build()?,
+        "http/json" => builder
+            .with_http()
+            .with_protocol(Protocol::HttpJson)
+            .build()?,
+        other => bail!("Unsupported OTLP protocol: {other}"),
+    })
+}
+
+/// The W3C `traceparent` of the current span, for continuing its trace in
+/// another worker. `None` when traces aren't exported.
+pub fn current_traceparent() -> Option<String> {
+    let context = tracing::Span::current().context();
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+    carrier.remove(TRACEPARENT)
+}
+
+/// Makes `span` continue the trace of a W3C `traceparent`. Must be called
+/// before the span is first entered.
+pub fn set_traceparent(span: &tracing::Span, traceparent: &str) {
+    let carrier = HashMap::from([(TRACEPARENT.to_owned(), traceparent.to_owned())]);
+    let context = global::get_text_map_propagator(|propagator| propagator.extract(&carrier));
+    if let Err(err) = span.set_parent(context) {
+        tracing::debug!("Failed continuing trace {traceparent}: {err}");
+    }
 }
@@ -0,0 +1,65 @@
+//! A small HTTP server for the workers on `PLATZ_WORKER_HTTP_PORT` (default
+//! 9090), serving:
+//!
+//! * `/metrics`: Prometheus metrics.
+//! * `/livez`: 503 if any loop missed its heartbeat, see [`crate::health`].
+//! * `/readyz`: 503 if any readiness check fails.
+
+use crate::health;
+use actix_web::{App, HttpResponse, HttpServer, web};
+use anyhow::{Context, Result};
+use prometheus::Encoder;
+use std::env;
+
+const PORT_VAR: &str = "PLATZ_WORKER_HTTP_PORT";
+const DEFAULT_PORT: u16 = 9090;
+
+pub async fn serve() -> Result<()> {
+    let port = match env::var(PORT_VAR) {
+        Ok(port) => port
+            .parse()
+            .with_context(|| format!("Invalid {PORT_VAR}: {port}"))?,
+        Err(_) => DEFAULT_PORT,
+    };
+
+    // Workers handle signals themselves
+    Ok(HttpServer::new(|| {
+        App::new()
+            .route("/metrics", web::get().to(metrics))
+            .route("/livez", web::get().to(livez))
+            .route("/readyz", web::get().to(readyz))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("0.0.0.0", port))?
+    .run()
+    .await?)
+}
+
+async fn metrics() -> HttpResponse {
+    let encoder = prometheus::TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(err) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        return HttpResponse::InternalServerError().body(err.to_string());
+    }
+    match String::from_utf8(buffer) {
+        Ok(body) => HttpResponse::Ok().body(body),
+        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    }
+}
+
+async fn livez() -> HttpResponse {
+    health_response(health::liveness_failures())
+}
+
+async fn readyz() -> HttpResponse {
+    health_response(health::readiness_failures())
+}
+
+fn health_response(failures: Vec<String>) -> HttpResponse {
+    if failures.is_empty() {
+        HttpResponse::Ok().body("ok")
+    } else {
+        HttpResponse::ServiceUnavailable().body(failures.join("\n"))
+    }
+}
@@ -1,8 +1,10 @@
+mod metrics;
 mod task;
 
 use crate::task::{monitor_deployment_resource_changes, scrub_deployment_resources};
 use anyhow::Result;
 use platz_db::{DbTable, init_db};
+use platz_otel::health;
 use tokio::{
     select,
     signal::unix::{SignalKind, signal},
@@ -11,7 +13,7 @@ use tracing::{info, warn};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    platz_otel::init()?;
+    let _otel = platz_otel::init(env!("CARGO_PKG_NAME"))?;
     info!("Starting deployment resource sync worker");
     let mut sigterm = signal(SignalKind::terminate())?;
     let mut sigint = signal(SignalKind::interrupt())?;
@@ -20,9 +22,16 @@ async fn main() -> Result<()> {
 
     let fut = tokio::spawn(monitor_deployment_resource_changes(db));
 
-    info!("Scrubbing all existing deployment resources");
-    scrub_deployment_resources().await?;
-    info!("Finished scrubbing, will now watch for changes");
+    // Scrub while serving health, so a long scrub isn't mistaken for a dead
+    // worker
+    let sync_fut = async {
+        health::set_ready("scrub", Err("Scrubbing existing resources".to_owned()));
+        info!("Scrubbing all existing deployment resources");
+        scrub_deployment_resources().await?;
+        info!("Finished scrubbing, will now watch for changes");
+        health::set_ready("scrub", Ok(()));
+        fut.await?
+    };
 
     select! {
         _ = sigterm.recv() => {
@@ -44,8 +53,13 @@ async fn main() -> Result<()> {
             result.map_err(Into::into)
         }
 
-        result = fut => {
-            result?
+        result = platz_otel::server::serve() => {
+            warn!("Health and metrics server finished: {result:?}");
+            result
+        }
+
+        result = sync_fut => {
+            result
         }
     }
 }
@@ -0,0 +1,11 @@
+use lazy_static::lazy_static;
+use prometheus::{IntCounterVec, register_int_counter_vec};
+
+lazy_static! {
+    pub static ref RESOURCE_SYNCS: IntCounterVec = register_int_counter_vec!(
+        "platz_resource_sync_total",
+        "Number of deployment resource syncs by the status they synced and their outcome",
+        &["sync_status", "outcome"],
+    )
+    .unwrap();
+}
@@ -1,3 +1,4 @@
+use crate::metrics::RESOURCE_SYNCS;
 use anyhow::{Result, anyhow};
 use platz_chart_ext::resource_types::{
     ChartExtResourceLifecycleActionV1Beta1, ChartExtResourceLifecycleV1Beta1,
@@ -44,25 +45,35 @@ async fn sync_resource(resource: DeploymentResource) -> Result<()> {
         resource.id, resource.name
     );
 
-    match resource.sync_status {
+    let synced = match resource.sync_status {
         DeploymentResourceSyncStatus::Creating => {
             info!("Creating {} ({})", resource.id, resource.name);
-            call_lifecycle_target(&resource, |lifecycle| lifecycle.create.as_ref()).await?;
+            call_lifecycle_target(&resource, |lifecycle| lifecycle.create.as_ref()).await?
         }
         DeploymentResourceSyncStatus::Updating => {
             info!("Updating {} ({})", resource.id, resource.name);
-            call_lifecycle_target(&resource, |lifecycle| lifecycle.update.as_ref()).await?;
+            call_lifecycle_target(&resource, |lifecycle| lifecycle.update.as_ref()).await?
         }
         DeploymentResourceSyncStatus::Deleting => {
             info!("Deleting {} ({})", resource.id, resource.name);
-            if call_lifecycle_target(&resource, |lifecycle| lifecycle.delete.as_ref()).await? {
+            let synced =
+                call_lifecycle_target(&resource, |lifecycle| lifecycle.delete.as_ref()).await?;
+            if synced {
                 resource.delete().await?;
             }
+            synced
         }
         DeploymentResourceSyncStatus::Ready | DeploymentResourceSyncStatus::Error => {
             debug!("Nothing to do for {} ({})", resource.id, resource.name);
+            return Ok(());
         }
-    }
+    };
+    RESOURCE_SYNCS
+        .with_label_values(&[
+            resource.sync_status.to_string().as_str(),
+            if synced { "synced" } else { "error" },
+        ])
+        .inc();
     Ok(())
 }
 
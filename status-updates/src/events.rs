@@ -2,6 +2,7 @@ use crate::tracker::StatusTracker;
 use anyhow::Result;
 use futures::future::join_all;
 use platz_db::{Db, DbEventOperation, DbTable, schema::deployment::Deployment};
+use platz_otel::health;
 use tokio::time;
 use tracing::debug;
 
@@ -11,6 +12,7 @@ const DEPLOYMENT_SLEEP_BETWEEN_CHUNKS: time::Duration = time::Duration::from_sec
 pub async fn watch_deployments(db: &Db, tracker: StatusTracker) -> Result<()> {
     let mut db_rx = db.subscribe_to_events();
 
+    health::set_ready("deployments", Err("Loading deployments".to_owned()));
     for deploy_chunk in Deployment::all().await?.chunks(DEPLOYMENT_CHUNK_SIZE) {
         join_all(
             deploy_chunk
@@ -20,6 +22,7 @@ pub async fn watch_deployments(db: &Db, tracker: StatusTracker) -> Result<()> {
         .await;
         time::sleep(DEPLOYMENT_SLEEP_BETWEEN_CHUNKS).await;
     }
+    health::set_ready("deployments", Ok(()));
 
     loop {
         let event = db_rx.recv().await?;
@@ -1,4 +1,5 @@
 mod events;
+mod metrics;
 mod status_config;
 mod tracker;
 
@@ -16,12 +17,13 @@ async fn main() -> Result<()> {
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .expect("Failed installing default crypto provider");
-    platz_otel::init()?;
+    let _otel = platz_otel::init(env!("CARGO_PKG_NAME"))?;
     info!("Starting status updates worker");
     let mut sigterm = signal(SignalKind::terminate())?;
     let mut sigint = signal(SignalKind::interrupt())?;
 
     let db = init_db().await?;
+    let tracker = StatusTracker::new();
 
     select! {
         _ = sigterm.recv() => {
@@ -43,7 +45,17 @@ async fn main() -> Result<()> {
             result.map_err(Into::into)
         }
 
-        result = events::watch_deployments(db, StatusTracker::new()) => {
+        result = platz_otel::server::serve() => {
+            warn!("Health and metrics server finished: {result:?}");
+            result
+        }
+
+        result = events::watch_deployments(db, tracker.clone()) => {
+            result
+        }
+
+        result = tracker.check_health() => {
+            warn!("Status tracker health check finished: {result:?}");
             result
         }
     }
@@ -0,0 +1,18 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    Histogram, IntCounter, exponential_buckets, register_histogram, register_int_counter,
+};
+
+lazy_static! {
+    pub static ref POLL_DURATION: Histogram = register_histogram!(
+        "platz_status_poll_duration_seconds",
+        "Time spent querying deployment status endpoints",
+        exponential_buckets(0.01, 2.0, 11).unwrap(),
+    )
+    .unwrap();
+    pub static ref POLL_ERRORS: IntCounter = register_int_counter!(
+        "platz_status_poll_errors_total",
+        "Number of failed queries of deployment status endpoints"
+    )
+    .unwrap();
+}
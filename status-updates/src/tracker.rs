@@ -1,16 +1,26 @@
-use crate::status_config::StatusConfig;
+use crate::{
+    metrics::{POLL_DURATION, POLL_ERRORS},
+    status_config::StatusConfig,
+};
 use anyhow::Result;
-use futures::TryFutureExt;
+use futures::{FutureExt, TryFutureExt};
 use platz_db::schema::{
     deployment::{Deployment, UpdateDeploymentReportedStatus},
     deployment_status::{DeploymentReportedStatus, DeploymentReportedStatusContent},
 };
-use std::{collections::HashMap, sync::Arc, time::Duration};
-use tokio::{sync::RwLock, task};
+use platz_otel::health;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::RwLock, task, time};
 use tracing::{error, info, warn};
 use url::Url;
 use uuid::Uuid;
 
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Default, Clone)]
 pub struct StatusTracker {
     inner: Arc<Inner>,
@@ -87,12 +97,47 @@ impl StatusTracker {
         let mut tasks = self.inner.tasks.write().await;
         if let Some(handle) = tasks.insert(
             deployment.id,
-            task::spawn(periodic_deployment_status_update(deployment, new_config)),
+            task::spawn(periodic_deployment_status_update(deployment.id, new_config)),
         ) {
             handle.abort();
         }
     }
 
+    /// Restarts status updates of deployments whose task died, and reports
+    /// the tracker as wedged if its tasks can't be checked.
+    pub async fn check_health(&self) -> Result<()> {
+        let mut interval = time::interval(HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.restart_dead_tasks().await;
+            health::heartbeat("status_tracker", HEALTH_CHECK_INTERVAL * 4);
+        }
+    }
+
+    async fn restart_dead_tasks(&self) {
+        // Same lock order as `add`, configs first
+        let configs = self.inner.configs.read().await;
+        let mut tasks = self.inner.tasks.write().await;
+        // Tasks run until removed, so finished tasks have died
+        let dead: Vec<Uuid> = tasks
+            .iter()
+            .filter(|(_, handle)| handle.is_finished())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in dead {
+            if let Some(Err(err)) = tasks.remove(&id).and_then(|handle| handle.now_or_never()) {
+                error!("Status updates for deployment {id} died: {err}");
+            }
+            if let Some(config) = configs.get(&id) {
+                warn!("Restarting status updates for deployment {id}");
+                tasks.insert(
+                    id,
+                    task::spawn(periodic_deployment_status_update(id, config.clone())),
+                );
+            }
+        }
+    }
+
     pub async fn remove(&self, id: Uuid) {
         info!("Removing deployment {}", id);
         self.inner.configs.write().await.remove(&id);
@@ -103,6 +148,16 @@ impl StatusTracker {
 }
 
 async fn get_deployment_reported_status(url: &Url) -> Result<DeploymentReportedStatusContent> {
+    let start_time = Instant::now();
+    let result = fetch_deployment_reported_status(url).await;
+    POLL_DURATION.observe(start_time.elapsed().as_secs_f64());
+    if result.is_err() {
+        POLL_ERRORS.inc();
+    }
+    result
+}
+
+async fn fetch_deployment_reported_status(url: &Url) -> Result<DeploymentReportedStatusContent> {
     Ok(reqwest::Client::new()
         .get(url.to_owned())
         .timeout(Duration::from_secs(10))
@@ -113,7 +168,7 @@ async fn get_deployment_reported_status(url: &Url) -> Result<DeploymentReportedS
         .await?)
 }
 
-async fn periodic_deployment_status_update(deployment: Deployment, status_config: StatusConfig) {
+async fn periodic_deployment_status_update(deployment_id: Uuid, status_config: StatusConfig) {
     let mut interval = status_config.interval();
 
     loop {
@@ -127,7 +182,7 @@ async fn periodic_deployment_status_update(deployment: Deployment, status_config
             .await;
 
         let update_result = UpdateDeploymentReportedStatus::new(Some(reported_status))
-            .save(deployment.id)
+            .save(deployment_id)
             .await;
 
         if let Err(err) = update_result {